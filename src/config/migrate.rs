@@ -0,0 +1,93 @@
+use serde_yaml::{Mapping, Value};
+
+/// A migration upgrading a configuration document by one version, in place.
+type Migration = fn(&mut Mapping) -> Result<(), String>;
+
+/// The migrations of the configuration format: `MIGRATIONS[i]` upgrades a configuration
+/// from version `i + 1` to version `i + 2`.
+///
+/// Changing the format of a step or option in a way that breaks existing configurations
+/// requires a migration rewriting them into the new format.
+const MIGRATIONS: &[Migration] = &[];
+
+/// The current version of the configuration format.
+pub const VERSION: u64 = MIGRATIONS.len() as u64 + 1;
+
+/// The top-level key holding the version of the configuration format.
+pub const VERSION_KEY: &str = "version";
+
+/// Returns the version of the configuration document, `1` if not set.
+pub fn version(document: &Mapping) -> Result<u64, String> {
+    match document.get(VERSION_KEY) {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .filter(|version| *version > 0)
+            .ok_or_else(|| format!("invalid version {version:?}, expected a positive integer")),
+    }
+}
+
+/// Upgrades the configuration document to the current version.
+///
+/// Returns the version the document was upgraded from.
+pub fn migrate(document: &mut Mapping) -> Result<u64, String> {
+    migrate_with(document, MIGRATIONS)
+}
+
+fn migrate_with(document: &mut Mapping, migrations: &[Migration]) -> Result<u64, String> {
+    let from = version(document)?;
+    let latest = migrations.len() as u64 + 1;
+    if from > latest {
+        return Err(format!(
+            "version {from} is newer than the latest supported version {latest}"
+        ));
+    }
+
+    for (idx, migration) in migrations.iter().enumerate().skip(from as usize - 1) {
+        migration(document).map_err(|err| {
+            format!(
+                "failed to migrate from version {} to {}: {err}",
+                idx + 1,
+                idx + 2
+            )
+        })?;
+    }
+
+    // the version leads the document
+    document.remove(VERSION_KEY);
+    let mut migrated = Mapping::new();
+    migrated.insert(VERSION_KEY.into(), Value::from(latest));
+    migrated.extend(std::mem::take(document));
+    *document = migrated;
+
+    Ok(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename_echo(document: &mut Mapping) -> Result<(), String> {
+        document.insert("renamed".into(), Value::Bool(true));
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut document: Mapping = serde_yaml::from_str("echo: {type: SERVICE}").unwrap();
+        assert_eq!(version(&document), Ok(1));
+
+        assert_eq!(migrate_with(&mut document, &[rename_echo]), Ok(1));
+        assert_eq!(
+            serde_yaml::to_string(&document).unwrap(),
+            "version: 2\necho:\n  type: SERVICE\nrenamed: true\n"
+        );
+
+        // already migrated documents are left as is
+        assert_eq!(migrate_with(&mut document, &[rename_echo]), Ok(2));
+        assert_eq!(version(&document), Ok(2));
+
+        assert!(migrate_with(&mut document, &[]).is_err());
+        assert!(version(&serde_yaml::from_str("version: zero").unwrap()).is_err());
+    }
+}
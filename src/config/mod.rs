@@ -0,0 +1,750 @@
+use std::collections::BTreeMap;
+
+use restate_sdk::discovery::{HandlerType, ServiceType};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub use migrate::{migrate, VERSION};
+pub use report::{Location, Report};
+
+use crate::mock::{
+    DETECT_DUPLICATES, GET_DUPLICATES, GET_HISTORY, MIRROR, RECORD_HISTORY, REQUIRE_HEADER,
+};
+
+mod migrate;
+mod report;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServiceConfig {
+    #[serde(rename = "type")]
+    pub ty: ServiceType,
+    /// Disabled services are not bound to the endpoint. Defaults to `true`.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Steps executed before the steps of every handler of this service.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<StepConfig>,
+    /// Steps executed after the steps of every handler of this service.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<StepConfig>,
+    #[serde(default)]
+    pub handlers: BTreeMap<String, HandlerConfig>,
+    /// Names of stub handlers. Shorthand for handlers with `stub: true`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stubs: Vec<String>,
+    /// Records the received invocations in the state of every key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<HistoryConfig>,
+    /// Detects and counts duplicate deliveries on every key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicates: Option<DuplicatesConfig>,
+}
+
+/// Maintains a bounded history of the invocations received by every key of a service,
+/// readable through a companion handler.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HistoryConfig {
+    /// The name of the handler returning the history. Defaults to `history`.
+    #[serde(default = "default_history_handler")]
+    pub handler: String,
+    /// The state key holding the history. Defaults to `history`.
+    #[serde(default = "default_history_key")]
+    pub key: String,
+    /// The maximum number of entries kept per key. Defaults to `100`.
+    #[serde(default = "default_history_max")]
+    pub max: usize,
+}
+
+fn default_history_handler() -> String {
+    "history".to_owned()
+}
+
+fn default_history_key() -> String {
+    "history".to_owned()
+}
+
+fn default_history_max() -> usize {
+    100
+}
+
+impl HistoryConfig {
+    /// Returns the step recording the invocations of `handler`.
+    pub fn record_step(&self, handler: &str) -> StepConfig {
+        StepConfig::new(
+            RECORD_HISTORY,
+            serde_json::json!({
+                "key": self.key,
+                "handler": handler,
+                "max": self.max,
+            }),
+        )
+    }
+
+    /// Returns the configuration of the (shared) handler returning the history.
+    pub fn history_handler(&self) -> HandlerConfig {
+        HandlerConfig::shared(vec![StepConfig::new(
+            GET_HISTORY,
+            serde_json::json!({ "key": self.key }),
+        )])
+    }
+}
+
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HandlerConfig {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub ty: Option<HandlerType>,
+    /// Disabled handlers are excluded from the service discovery. Defaults to `true`.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Stub handlers fail every invocation with a "not implemented" terminal error.
+    #[serde(default)]
+    pub stub: bool,
+    /// The latency target of the handler. Slower executions are counted as SLO violations.
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slo: Option<humantime::Duration>,
+    /// Mirrors a copy of every input to a shadow target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror_to: Option<MirrorConfig>,
+    /// Requires a request header, simulating authentication.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AuthConfig>,
+    /// The input of the invocations without a body. Defaults to `null`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_input: Option<serde_json::Value>,
+    /// How the input and the output of the handler are encoded. Defaults to `json`.
+    #[serde(default)]
+    pub content: HandlerContent,
+    #[serde(default)]
+    pub steps: Vec<StepConfig>,
+    /// Fire-and-forget steps executed after the response of the handler is finalized.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after_return: Vec<StepConfig>,
+}
+
+impl HandlerConfig {
+    /// Creates the configuration of a stub handler.
+    pub fn stub() -> Self {
+        Self {
+            ty: None,
+            enabled: true,
+            stub: true,
+            slo: None,
+            mirror_to: None,
+            auth: None,
+            default_input: None,
+            content: HandlerContent::Json,
+            steps: Vec::new(),
+            after_return: Vec::new(),
+        }
+    }
+
+    /// Creates the configuration of a shared handler running the given steps.
+    pub fn shared(steps: Vec<StepConfig>) -> Self {
+        Self {
+            ty: Some(HandlerType::Shared),
+            stub: false,
+            steps,
+            ..Self::stub()
+        }
+    }
+
+    /// Returns the effective type of the handler in a service of the given type, with
+    /// the defaults applied: handlers of virtual objects are exclusive and handlers of
+    /// workflows are shared unless configured otherwise. `None` for handlers of a `SERVICE`.
+    pub fn effective_type(&self, service_type: ServiceType) -> Option<HandlerType> {
+        match service_type {
+            ServiceType::Service => None,
+            ServiceType::VirtualObject => Some(self.ty.unwrap_or(HandlerType::Exclusive)),
+            ServiceType::Workflow => Some(self.ty.unwrap_or(HandlerType::Shared)),
+        }
+    }
+
+    /// Returns `true` if the handler has exclusive access to the state of its key,
+    /// which is required to write state.
+    pub fn is_exclusive(&self, service_type: ServiceType) -> bool {
+        matches!(
+            self.effective_type(service_type),
+            Some(HandlerType::Exclusive | HandlerType::Workflow)
+        )
+    }
+}
+
+/// The shadow target receiving a copy of the input of a handler.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MirrorConfig {
+    /// The type of the shadow target.
+    pub target_type: ServiceType,
+    pub service: String,
+    pub handler: String,
+    /// The key of the shadow target. Defaults to the key of the mirrored invocation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// The fraction (0.0 to 1.0) of the invocations that are mirrored. Defaults to `1.0`.
+    #[serde(default = "default_sample")]
+    pub sample: f64,
+}
+
+fn default_sample() -> f64 {
+    1.0
+}
+
+impl MirrorConfig {
+    /// Returns the step mirroring the input.
+    pub fn step(&self) -> StepConfig {
+        StepConfig::new(MIRROR, self)
+    }
+}
+
+/// How the payloads of a handler are encoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HandlerContent {
+    /// The input is parsed as JSON and the output is returned as JSON.
+    #[default]
+    Json,
+    /// The input is passed through as bytes, in the `input` variable, and the output is
+    /// returned as raw bytes.
+    Raw,
+}
+
+/// The header a handler requires, missing or wrong credentials fail the invocation.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuthConfig {
+    /// The name of the required header. Defaults to `authorization`.
+    #[serde(default = "default_auth_header")]
+    pub header: String,
+    /// The exact value (or bearer token) the header must have.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// A regular expression the whole value (or bearer token) must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// The header must hold a `Bearer <token>` value. Defaults to `false`.
+    #[serde(default)]
+    pub bearer: bool,
+    /// The code of the terminal error. Defaults to `401`.
+    #[serde(default = "default_auth_code")]
+    pub code: u16,
+    /// The message of the terminal error. Defaults to `unauthorized`.
+    #[serde(default = "default_auth_message")]
+    pub message: String,
+}
+
+fn default_auth_header() -> String {
+    "authorization".to_owned()
+}
+
+fn default_auth_code() -> u16 {
+    401
+}
+
+fn default_auth_message() -> String {
+    "unauthorized".to_owned()
+}
+
+impl AuthConfig {
+    /// Returns the step checking the header.
+    pub fn step(&self) -> StepConfig {
+        StepConfig::new(REQUIRE_HEADER, self)
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StepConfig {
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// Identifies the step in the statistics, next to its type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "serde_yaml::Value::is_null")]
+    pub params: serde_yaml::Value,
+    /// Makes the step fail randomly, see [`FlakyConfig`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flaky: Option<FlakyConfig>,
+}
+
+impl StepConfig {
+    /// Creates the configuration of a step of type `ty` with the given parameters.
+    pub fn new(ty: &str, params: impl Serialize) -> Self {
+        Self {
+            ty: ty.to_owned(),
+            name: None,
+            params: serde_yaml::to_value(params).expect("valid params"),
+            flaky: None,
+        }
+    }
+}
+
+/// The code of the injected terminal errors, unless configured otherwise.
+pub const DEFAULT_ERROR_CODE: u16 = 500;
+
+/// The kind of an injected error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorKind {
+    /// A retryable error, the invocation is retried by Restate.
+    #[default]
+    Retryable,
+    /// A terminal error, the invocation fails.
+    Terminal,
+}
+
+/// Injects random failures in a step, before the step itself runs.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FlakyConfig {
+    /// The probability (0.0 to 1.0) that the step fails.
+    pub probability: f64,
+    /// The kind of the injected error. Defaults to `retryable`.
+    #[serde(default)]
+    pub error: ErrorKind,
+    /// The code of the injected terminal error. Defaults to `500`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<u16>,
+}
+
+/// Steps executed around every handler of every service in the configuration.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct HooksConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<StepConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<StepConfig>,
+}
+
+impl HooksConfig {
+    /// Returns `true` if no hook steps are configured.
+    pub fn is_empty(&self) -> bool {
+        self.before.is_empty() && self.after.is_empty()
+    }
+}
+
+/// Records the request ID of every invocation received by a service and counts the
+/// duplicate deliveries, readable through a companion handler.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DuplicatesConfig {
+    /// The name of the handler returning the statistics. Defaults to `duplicates`.
+    #[serde(default = "default_duplicates")]
+    pub handler: String,
+    /// The state key holding the statistics. Defaults to `duplicates`.
+    #[serde(default = "default_duplicates")]
+    pub key: String,
+    /// A JSONPath selecting the request ID in the input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// The request header holding the request ID, used if `field` is not set.
+    /// Defaults to `idempotency-key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    /// Fail duplicate deliveries with a terminal error. Defaults to `false`.
+    #[serde(default)]
+    pub fail: bool,
+}
+
+fn default_duplicates() -> String {
+    "duplicates".to_owned()
+}
+
+impl DuplicatesConfig {
+    /// Returns the step detecting the duplicate deliveries.
+    pub fn detect_step(&self) -> StepConfig {
+        let mut params = serde_json::json!({
+            "key": self.key,
+            "fail": self.fail,
+        });
+        if let Some(field) = &self.field {
+            params["field"] = field.as_str().into();
+        }
+        if let Some(header) = &self.header {
+            params["header"] = header.as_str().into();
+        }
+
+        StepConfig::new(DETECT_DUPLICATES, params)
+    }
+
+    /// Returns the configuration of the (shared) handler returning the statistics.
+    pub fn stats_handler(&self) -> HandlerConfig {
+        HandlerConfig::shared(vec![StepConfig::new(
+            GET_DUPLICATES,
+            serde_json::json!({ "key": self.key }),
+        )])
+    }
+}
+
+/// Controls the logging of the handler inputs and outputs.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LoggingConfig {
+    /// Whether the inputs and outputs are logged. Defaults to `false`.
+    #[serde(default)]
+    pub payloads: bool,
+    /// The maximum length of a logged payload, longer payloads are truncated. Defaults to `1024`.
+    #[serde(default = "default_max_length")]
+    pub max_length: usize,
+    /// Regular expressions matching the names of the fields whose values are redacted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact: Vec<String>,
+}
+
+fn default_max_length() -> usize {
+    1024
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            payloads: false,
+            max_length: default_max_length(),
+            redact: Vec::new(),
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// Returns `true` if all the options have their default value.
+    pub fn is_default(&self) -> bool {
+        !self.payloads && self.max_length == default_max_length() && self.redact.is_empty()
+    }
+}
+
+/// The top-level key holding the global hooks.
+const HOOKS: &str = "hooks";
+
+/// The top-level key holding the logging options. All other top-level keys (but the
+/// `version`) are services.
+const LOGGING: &str = "logging";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Configuration {
+    /// The version of the configuration format, see [`VERSION`].
+    pub version: u64,
+    #[serde(skip_serializing_if = "HooksConfig::is_empty")]
+    pub hooks: HooksConfig,
+    #[serde(skip_serializing_if = "LoggingConfig::is_default")]
+    pub logging: LoggingConfig,
+    #[serde(flatten)]
+    pub services: BTreeMap<String, ServiceConfig>,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            hooks: HooksConfig::default(),
+            logging: LoggingConfig::default(),
+            services: BTreeMap::new(),
+        }
+    }
+}
+
+impl Configuration {
+    /// Returns the effective configuration: disabled services and handlers are removed,
+    /// stub handlers listed in `stubs` are expanded into handlers, the `history`,
+    /// `duplicates`, `auth` and `mirror_to` options are expanded into their steps and handlers,
+    /// and all defaults are made explicit when serialized.
+    pub fn effective(mut self) -> Self {
+        self.services.retain(|_, service| service.enabled);
+
+        for service in self.services.values_mut() {
+            for stub in std::mem::take(&mut service.stubs) {
+                service
+                    .handlers
+                    .entry(stub)
+                    .or_insert_with(HandlerConfig::stub);
+            }
+
+            service.handlers.retain(|_, handler| handler.enabled);
+
+            for handler in service.handlers.values_mut() {
+                if let Some(mirror) = handler.mirror_to.take() {
+                    handler.steps.insert(0, mirror.step());
+                }
+                if let Some(auth) = handler.auth.take() {
+                    handler.steps.insert(0, auth.step());
+                }
+            }
+
+            // record steps are inserted at the front, in reverse order of execution
+            if let Some(duplicates) = service.duplicates.take() {
+                for handler in service.handlers.values_mut() {
+                    if !handler.stub && handler.is_exclusive(service.ty) {
+                        handler.steps.insert(0, duplicates.detect_step());
+                    }
+                }
+
+                service
+                    .handlers
+                    .entry(duplicates.handler.clone())
+                    .or_insert_with(|| duplicates.stats_handler());
+            }
+
+            if let Some(history) = service.history.take() {
+                for (name, handler) in service.handlers.iter_mut() {
+                    if !handler.stub && handler.is_exclusive(service.ty) {
+                        handler.steps.insert(0, history.record_step(name));
+                    }
+                }
+
+                service
+                    .handlers
+                    .entry(history.handler.clone())
+                    .or_insert_with(|| history.history_handler());
+            }
+        }
+
+        self
+    }
+
+    /// Parses the configuration from a YAML document.
+    ///
+    /// Every top-level element is deserialized on its own, so all invalid elements are
+    /// recorded in the `report` (with their path and location) instead of stopping at
+    /// the first one. Invalid elements are left out of the returned configuration, and
+    /// configurations of another version than [`VERSION`] are rejected.
+    pub fn parse(source: &str, report: &mut Report) -> Self {
+        let mut config = Self::default();
+
+        let mapping: serde_yaml::Mapping = match serde_yaml::from_str(source) {
+            Ok(mapping) => mapping,
+            Err(err) => {
+                match err.location() {
+                    Some(location) => report.push_at(
+                        ".",
+                        Location {
+                            line: location.line(),
+                            column: location.column(),
+                        },
+                        err,
+                    ),
+                    None => report.push(".", err),
+                }
+
+                return config;
+            }
+        };
+
+        match migrate::version(&mapping) {
+            _ if mapping
+                .get(migrate::VERSION_KEY)
+                .is_some_and(declares_service) =>
+            {
+                report.push(migrate::VERSION_KEY, reserved_name(migrate::VERSION_KEY))
+            }
+            Ok(version) if version > VERSION => report.push(
+                migrate::VERSION_KEY,
+                format!("Unsupported version {version}, the latest supported version is {VERSION}"),
+            ),
+            Ok(version) if version < VERSION => report.push(
+                migrate::VERSION_KEY,
+                format!(
+                    "Outdated version {version}, upgrade the configuration to version {VERSION} \
+                     with the `migrate` subcommand"
+                ),
+            ),
+            Ok(_) => {}
+            Err(err) => report.push(migrate::VERSION_KEY, format!("Invalid version: {err}")),
+        }
+
+        for (key, value) in mapping {
+            let Some(name) = key.as_str() else {
+                report.push(".", format!("invalid top-level key {key:?}"));
+                continue;
+            };
+
+            if [HOOKS, LOGGING].contains(&name) && declares_service(&value) {
+                report.push(name, reserved_name(name));
+            } else if name == migrate::VERSION_KEY {
+                continue;
+            } else if name == HOOKS {
+                if let Some(hooks) = deserialize_at(name, value, report) {
+                    config.hooks = hooks;
+                }
+            } else if name == LOGGING {
+                if let Some(logging) = deserialize_at(name, value, report) {
+                    config.logging = logging;
+                }
+            } else if let Some(service) = deserialize_at(name, value, report) {
+                config.services.insert(name.to_owned(), service);
+            }
+        }
+
+        config
+    }
+}
+
+/// Returns whether the `value` of a reserved top-level key is a service declaration (it has a
+/// `type`) rather than the value the key is reserved for.
+fn declares_service(value: &serde_yaml::Value) -> bool {
+    value.get("type").is_some()
+}
+
+/// The error recorded for a service declared under the reserved top-level key `name`.
+fn reserved_name(name: &str) -> String {
+    format!("`{name}` is a reserved top-level key and can not be used as a service name")
+}
+
+/// Deserializes `value` found at `path`, recording the exact path of the offending
+/// element in the `report` on failure.
+fn deserialize_at<T>(path: &str, value: serde_yaml::Value, report: &mut Report) -> Option<T>
+where
+    T: DeserializeOwned,
+{
+    match serde_path_to_error::deserialize(value) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            let inner = err.path().to_string();
+            let path = match inner.as_str() {
+                "." => path.to_owned(),
+                inner if inner.starts_with('[') => format!("{path}{inner}"),
+                inner => format!("{path}.{inner}"),
+            };
+
+            report.push(path, err.into_inner());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[allow(clippy::single_component_path_imports)]
+    use serde_yaml;
+    use std::collections::HashMap;
+
+    const SAMPLE_CONFIG: &str = include_str!("../../assets/test_config.yaml");
+
+    #[test]
+    fn test_parse_config() {
+        let config: HashMap<String, ServiceConfig> = serde_yaml::from_str(SAMPLE_CONFIG).unwrap();
+
+        // Test service level configuration
+        let counter_service = config.get("counter").unwrap();
+        assert_eq!(counter_service.ty, ServiceType::Service);
+
+        // Test handlers
+        let handlers = &counter_service.handlers;
+        assert_eq!(handlers.len(), 2);
+
+        // Test increment handler
+        let increment = handlers.get("increment").unwrap();
+        assert_eq!(increment.ty, Some(HandlerType::Exclusive));
+        assert_eq!(increment.steps.len(), 2);
+
+        // Test get_count handler
+        let get_count = handlers.get("get_count").unwrap();
+        assert_eq!(get_count.ty, Some(HandlerType::Shared));
+        assert_eq!(get_count.steps.len(), 1);
+
+        // Test step configuration
+        let increment_steps = &increment.steps;
+        assert_eq!(increment_steps[0].ty, "sleep");
+        assert_eq!(increment_steps[1].ty, "success");
+    }
+
+    #[test]
+    fn test_parse_service_hooks() {
+        let source = "\
+type: SERVICE
+before:
+  - type: busy
+    params:
+      duration: 10ms
+handlers:
+  echo:
+    steps:
+      - type: echo
+";
+        let service: ServiceConfig = serde_yaml::from_str(source).unwrap();
+        assert_eq!(service.before.len(), 1);
+        assert_eq!(service.before[0].ty, "busy");
+        assert!(service.after.is_empty());
+    }
+
+    #[test]
+    fn test_parse_enabled() {
+        let source = "\
+type: SERVICE
+enabled: false
+handlers:
+  on:
+    steps: []
+  off:
+    enabled: false
+    steps: []
+";
+        let service: ServiceConfig = serde_yaml::from_str(source).unwrap();
+        assert!(!service.enabled);
+        assert!(service.handlers["on"].enabled);
+        assert!(!service.handlers["off"].enabled);
+
+        let service: ServiceConfig = serde_yaml::from_str("{type: SERVICE}").unwrap();
+        assert!(service.enabled);
+    }
+
+    #[test]
+    fn test_parse_reserved_names() {
+        let source = "\
+hooks:
+  type: SERVICE
+  handlers:
+    echo:
+      steps:
+        - type: echo
+logging:
+  type: SERVICE
+  handlers: {}
+version:
+  type: SERVICE
+  handlers: {}
+echo:
+  type: SERVICE
+  handlers:
+    echo:
+      steps:
+        - type: echo
+";
+        let mut report = Report::new(source);
+        let config = Configuration::parse(source, &mut report);
+
+        assert!(config.hooks.is_empty());
+        assert!(config.logging.is_default());
+        assert_eq!(config.services.keys().collect::<Vec<_>>(), ["echo"]);
+        let report = report.finish().unwrap_err().to_string();
+        assert!(report.contains(
+            "hooks (line 1 column 1): `hooks` is a reserved top-level key and can not be used as a service name"
+        ), "{report}");
+        assert!(report.contains("logging (line 7 column 1): `logging` is a reserved"));
+        assert!(report.contains("version (line 10 column 1): `version` is a reserved"));
+        assert!(!report.contains("Invalid version"), "{report}");
+    }
+
+    #[test]
+    fn test_parse_config_report() {
+        let source = "\
+hooks:
+  before: []
+echo:
+  type: SERVICE
+  handlers:
+    echo:
+      steps:
+        - params: {}
+broken:
+  type: UNKNOWN
+  handlers: {}
+";
+        let mut report = Report::new(source);
+        let config = Configuration::parse(source, &mut report);
+
+        assert!(config.services.is_empty());
+        let report = report.finish().unwrap_err().to_string();
+        assert!(
+            report.contains("echo.handlers.echo.steps[0] (line 8 column 11): missing field `type`")
+        );
+        assert!(report.contains("broken.type (line 10 column 3): unknown variant `UNKNOWN`"));
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use yaml_rust2::{parser::Parser, Event};
+
+/// A line/column position in the configuration file (both 1-indexed).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} column {}", self.line, self.column)
+    }
+}
+
+/// Index of the locations of all elements of a YAML document, keyed by their path.
+///
+/// Paths use `.` to separate mapping keys and `[idx]` for sequence entries, for example
+/// `echo.handlers.echo.steps[0]`. This is the same format used by `serde_path_to_error`.
+#[derive(Debug, Default)]
+pub struct Locations {
+    locations: HashMap<String, Location>,
+}
+
+enum Frame {
+    Mapping {
+        path: String,
+        key: Option<String>,
+        is_key: bool,
+    },
+    Sequence {
+        path: String,
+        index: usize,
+        is_key: bool,
+    },
+}
+
+impl Frame {
+    fn is_key(&self) -> bool {
+        match self {
+            Frame::Mapping { is_key, .. } | Frame::Sequence { is_key, .. } => *is_key,
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+impl Locations {
+    /// Builds the location index of the given YAML document. Invalid documents produce
+    /// a partial (possibly empty) index, the syntax error itself is reported by the
+    /// deserializer.
+    pub fn new(source: &str) -> Self {
+        let mut locations = HashMap::new();
+        let mut stack: Vec<Frame> = Vec::new();
+        let mut parser = Parser::new_from_str(source);
+
+        while let Ok((event, marker)) = parser.next_token() {
+            let location = Location {
+                line: marker.line(),
+                column: marker.col() + 1,
+            };
+
+            // the path of the current node, and whether it is a mapping key
+            let (path, is_key) = match stack.last() {
+                None => (String::new(), false),
+                Some(Frame::Mapping {
+                    path, key: None, ..
+                }) => (path.clone(), true),
+                Some(Frame::Mapping {
+                    path,
+                    key: Some(key),
+                    ..
+                }) => (join(path, key), false),
+                Some(Frame::Sequence { path, index, .. }) => (format!("{path}[{index}]"), false),
+            };
+
+            match event {
+                Event::Scalar(value, ..) => {
+                    if is_key {
+                        // block mappings start at the value indicator of their first key,
+                        // prefer the location of the key itself
+                        if let Some(start) = locations.get_mut(&path) {
+                            *start = location.min(*start);
+                        }
+
+                        locations.entry(join(&path, &value)).or_insert(location);
+                        Self::complete(&mut stack, Some(value));
+                    } else {
+                        locations.entry(path).or_insert(location);
+                        Self::complete(&mut stack, None);
+                    }
+                }
+                Event::Alias(_) => {
+                    if is_key {
+                        Self::complete(&mut stack, Some("*".to_owned()));
+                    } else {
+                        locations.entry(path).or_insert(location);
+                        Self::complete(&mut stack, None);
+                    }
+                }
+                Event::MappingStart(..) | Event::SequenceStart(..) => {
+                    let path = if is_key {
+                        join(&path, "?")
+                    } else {
+                        locations.entry(path.clone()).or_insert(location);
+                        path
+                    };
+
+                    stack.push(match event {
+                        Event::MappingStart(..) => Frame::Mapping {
+                            path,
+                            key: None,
+                            is_key,
+                        },
+                        _ => Frame::Sequence {
+                            path,
+                            index: 0,
+                            is_key,
+                        },
+                    });
+                }
+                Event::MappingEnd | Event::SequenceEnd => {
+                    let is_key = stack.pop().map(|frame| frame.is_key()).unwrap_or_default();
+                    Self::complete(&mut stack, is_key.then(|| "?".to_owned()));
+                }
+                Event::StreamEnd => break,
+                _ => {}
+            }
+        }
+
+        Self { locations }
+    }
+
+    /// Moves the parent frame forward after one of its children was fully parsed.
+    /// `key` is set if the parsed node was a mapping key.
+    fn complete(stack: &mut [Frame], key: Option<String>) {
+        match stack.last_mut() {
+            Some(Frame::Mapping { key: current, .. }) => {
+                *current = match current {
+                    None => key,
+                    Some(_) => None,
+                }
+            }
+            Some(Frame::Sequence { index, .. }) => *index += 1,
+            None => {}
+        }
+    }
+
+    /// Returns the location of the element at `path`. If the element itself is unknown
+    /// (for example a missing field), the location of the closest known parent is returned.
+    pub fn get(&self, path: &str) -> Option<Location> {
+        let mut path = path;
+        loop {
+            if let Some(location) = self.locations.get(path) {
+                return Some(*location);
+            }
+
+            let parent = path.rfind(['.', '['])?;
+            path = &path[..parent];
+        }
+    }
+}
+
+/// A single problem found in the configuration.
+#[derive(Debug)]
+pub struct Problem {
+    /// The path of the offending element.
+    pub path: String,
+    /// The location of the offending element in the configuration file, if known.
+    pub location: Option<Location>,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{} ({location}): {}", self.path, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+/// Collects all the problems found while loading a configuration, so they can be
+/// reported at once instead of failing on the first one.
+#[derive(Debug, Default)]
+pub struct Report {
+    locations: Locations,
+    problems: Vec<Problem>,
+}
+
+impl Report {
+    /// Creates a new, empty report for the given configuration source.
+    pub fn new(source: &str) -> Self {
+        Self {
+            locations: Locations::new(source),
+            problems: Vec::new(),
+        }
+    }
+
+    /// Records a problem with the element at `path`.
+    pub fn push(&mut self, path: impl Into<String>, error: impl Display) {
+        let path = path.into();
+        self.problems.push(Problem {
+            location: self.locations.get(&path),
+            path,
+            message: error.to_string(),
+        });
+    }
+
+    /// Records a problem at an explicit location.
+    pub fn push_at(&mut self, path: impl Into<String>, location: Location, error: impl Display) {
+        self.problems.push(Problem {
+            path: path.into(),
+            location: Some(location),
+            message: error.to_string(),
+        });
+    }
+
+    /// Returns the problems recorded so far.
+    pub fn problems(&self) -> &[Problem] {
+        &self.problems
+    }
+
+    /// Returns `Ok(())` if no problems were recorded, otherwise returns the report as an error.
+    pub fn finish(self) -> Result<(), Self> {
+        if self.problems.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Found {} problem(s) in configuration:",
+            self.problems.len()
+        )?;
+        for problem in &self.problems {
+            writeln!(f, "  - {problem}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Report {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+echo:
+  type: SERVICE
+  handlers:
+    echo:
+      steps:
+        - type: sleep
+          params:
+            duration: 2s
+        - type: echo
+";
+
+    #[test]
+    fn test_locations() {
+        let locations = Locations::new(SOURCE);
+
+        assert_eq!(
+            locations.get("echo.handlers"),
+            Some(Location { line: 3, column: 3 })
+        );
+        assert_eq!(
+            locations.get("echo.handlers.echo.steps[1]"),
+            Some(Location {
+                line: 9,
+                column: 11
+            })
+        );
+        assert_eq!(
+            locations.get("echo.handlers.echo.steps[0].params.duration"),
+            Some(Location {
+                line: 8,
+                column: 13
+            })
+        );
+        // unknown elements resolve to their closest parent
+        assert_eq!(
+            locations.get("echo.handlers.echo.steps[1].params"),
+            Some(Location {
+                line: 9,
+                column: 11
+            })
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use std::{fmt::Display, net::SocketAddr};
+
+use restate_sdk::endpoint::{Endpoint, Response};
+
+/// Arguments of the `doctor` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct DoctorArgs {
+    /// URL of the Restate admin API to check (e.g. http://localhost:9070)
+    #[clap(long, value_parser)]
+    admin_url: Option<String>,
+    /// URL of the Restate ingress to check (e.g. http://localhost:8080)
+    #[clap(long, value_parser)]
+    ingress_url: Option<String>,
+    /// Timeout of each connectivity check
+    #[clap(long, value_parser, default_value = "5s")]
+    timeout: humantime::Duration,
+}
+
+/// Collects the outcome of the doctor checks and prints them as they complete.
+#[derive(Default)]
+struct Checks {
+    failed: usize,
+}
+
+impl Checks {
+    fn ok(&mut self, check: &str, detail: impl Display) {
+        println!("[ OK ] {check}: {detail}");
+    }
+
+    fn fail(&mut self, check: &str, error: impl Display, hint: &str) {
+        self.failed += 1;
+        println!("[FAIL] {check}: {error}");
+        println!("       hint: {hint}");
+    }
+
+    fn skip(&mut self, check: &str, reason: &str) {
+        println!("[SKIP] {check}: {reason}");
+    }
+}
+
+/// Runs the environment checks, printing actionable diagnostics for every failure.
+///
+/// `endpoint` is the result of loading the configuration and building the endpoint.
+pub async fn run(
+    args: &DoctorArgs,
+    listen_address: &str,
+    endpoint: anyhow::Result<Endpoint>,
+) -> anyhow::Result<()> {
+    let mut checks = Checks::default();
+
+    let endpoint = match endpoint {
+        Ok(endpoint) => {
+            checks.ok("configuration", "loaded successfully");
+            Some(endpoint)
+        }
+        Err(err) => {
+            checks.fail(
+                "configuration",
+                format!("{err:#}"),
+                "fix the reported problems in the config file",
+            );
+            None
+        }
+    };
+
+    check_listen_address(&mut checks, listen_address).await;
+
+    match endpoint {
+        Some(endpoint) => check_discovery(&mut checks, &endpoint),
+        None => checks.skip("discovery", "configuration could not be loaded"),
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(args.timeout.into())
+        .build()?;
+
+    match &args.admin_url {
+        Some(url) => {
+            check_health(
+                &mut checks,
+                &client,
+                "admin",
+                &format!("{}/health", url.trim_end_matches('/')),
+                "make sure restate-server is running and the admin API is reachable (default port 9070)",
+            )
+            .await
+        }
+        None => checks.skip("admin", "no --admin-url provided"),
+    }
+
+    match &args.ingress_url {
+        Some(url) => check_health(
+            &mut checks,
+            &client,
+            "ingress",
+            &format!("{}/restate/health", url.trim_end_matches('/')),
+            "make sure restate-server is running and the ingress is reachable (default port 8080)",
+        )
+        .await,
+        None => checks.skip("ingress", "no --ingress-url provided"),
+    }
+
+    if checks.failed > 0 {
+        anyhow::bail!("{} check(s) failed", checks.failed);
+    }
+
+    Ok(())
+}
+
+async fn check_listen_address(checks: &mut Checks, listen_address: &str) {
+    const CHECK: &str = "listen address";
+
+    let addr: SocketAddr = match listen_address.parse() {
+        Ok(addr) => addr,
+        Err(err) => {
+            checks.fail(
+                CHECK,
+                format!("invalid address {listen_address}: {err}"),
+                "use the <ip>:<port> format, e.g. 0.0.0.0:9200",
+            );
+            return;
+        }
+    };
+
+    match tokio::net::TcpListener::bind(addr).await {
+        Ok(_) => checks.ok(CHECK, format!("{addr} is bindable")),
+        Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => checks.fail(
+            CHECK,
+            format!("{addr} is already in use"),
+            "stop the process using the port (another mock-service?) or pick another --listen-address",
+        ),
+        Err(err) => checks.fail(
+            CHECK,
+            format!("can not bind {addr}: {err}"),
+            "ports below 1024 need elevated privileges, the ip must belong to this host",
+        ),
+    }
+}
+
+fn check_discovery(checks: &mut Checks, endpoint: &Endpoint) {
+    const CHECK: &str = "discovery";
+    const HINT: &str = "this is likely a bug, please report it along with your config file";
+
+    let response = match endpoint.resolve("/discover", Vec::<(String, String)>::new()) {
+        Ok(response) => response,
+        Err(err) => return checks.fail(CHECK, err, HINT),
+    };
+
+    let body = match response {
+        Response::ReplyNow {
+            status_code: 200,
+            body,
+            ..
+        } => body,
+        Response::ReplyNow { status_code, .. } | Response::BidiStream { status_code, .. } => {
+            return checks.fail(CHECK, format!("unexpected status {status_code}"), HINT)
+        }
+    };
+
+    match serde_json::from_slice::<serde_json::Value>(&body) {
+        Ok(manifest) => {
+            let services = manifest["services"].as_array().map(Vec::len);
+            checks.ok(
+                CHECK,
+                format!(
+                    "endpoint exposes {} service(s)",
+                    services.unwrap_or_default()
+                ),
+            )
+        }
+        Err(err) => checks.fail(CHECK, format!("invalid manifest: {err}"), HINT),
+    }
+}
+
+async fn check_health(
+    checks: &mut Checks,
+    client: &reqwest::Client,
+    check: &str,
+    url: &str,
+    hint: &str,
+) {
+    match client.get(url).send().await {
+        Ok(response) if response.status().is_success() => {
+            checks.ok(check, format!("{url} is healthy"))
+        }
+        Ok(response) => checks.fail(
+            check,
+            format!("{url} responded with {}", response.status()),
+            hint,
+        ),
+        Err(err) if err.is_timeout() => checks.fail(
+            check,
+            format!("{url} timed out"),
+            "check the URL and any firewall in between, or increase --timeout",
+        ),
+        Err(err) => checks.fail(
+            check,
+            format!("{url} is not reachable: {:#}", anyhow::Error::from(err)),
+            hint,
+        ),
+    }
+}
@@ -0,0 +1,112 @@
+use restate_sdk::discovery::ServiceType;
+use serde_json::{json, Map, Value};
+
+use crate::{mock::CATCH_ALL_HANDLER, summary::Summary};
+
+/// Builds an OpenAPI 3 description of the ingress endpoints of the summarized handlers.
+///
+/// Every handler is a `POST /<service>/<handler>` operation, or `POST /<service>/<key>/<handler>`
+/// for virtual objects and workflows. The mock handlers accept and return any JSON value, and
+/// their behavior is described by the chain of their step types, also listed in the
+/// `x-mock-steps` extension. The catch-all handlers are left out as they are not discoverable.
+pub fn openapi(summary: &Summary) -> Value {
+    let mut paths = Map::new();
+
+    for row in summary.rows() {
+        if row.handler == CATCH_ALL_HANDLER {
+            continue;
+        }
+
+        let (path, parameters) = match row.service_type {
+            ServiceType::Service => (format!("/{}/{}", row.service, row.handler), json!([])),
+            _ => (
+                format!("/{}/{{key}}/{}", row.service, row.handler),
+                json!([{
+                    "name": "key",
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                }]),
+            ),
+        };
+
+        let mut operation = json!({
+            "operationId": format!("{}.{}", row.service, row.handler),
+            "tags": [row.service],
+            "summary": row.steps.join(" -> "),
+            "parameters": parameters,
+            "requestBody": {
+                "content": { "application/json": { "schema": {} } },
+            },
+            "responses": {
+                "200": {
+                    "description": "The output of the handler",
+                    "content": { "application/json": { "schema": {} } },
+                },
+            },
+            "x-restate-service-type": row.service_type.to_string(),
+            "x-mock-steps": row.steps,
+        });
+        if let Some(handler_type) = row.handler_type {
+            operation["x-restate-handler-type"] = handler_type.to_string().into();
+        }
+
+        paths.insert(path, json!({ "post": operation }));
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "mock-service",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use restate_sdk::discovery::HandlerType;
+
+    use super::*;
+
+    #[test]
+    fn test_openapi() {
+        let mut summary = Summary::default();
+        summary.add(
+            "counter",
+            ServiceType::VirtualObject,
+            "get",
+            Some(HandlerType::Shared),
+            vec!["get".to_owned(), "return".to_owned()],
+        );
+        summary.add(
+            "echo",
+            ServiceType::Service,
+            "echo",
+            None,
+            vec!["echo".to_owned()],
+        );
+        summary.add(
+            "echo",
+            ServiceType::Service,
+            CATCH_ALL_HANDLER,
+            None,
+            vec!["echo".to_owned()],
+        );
+
+        let openapi = openapi(&summary);
+        let paths = openapi["paths"].as_object().unwrap();
+        assert_eq!(paths.len(), 2);
+
+        let get = &paths["/counter/{key}/get"]["post"];
+        assert_eq!(get["summary"], "get -> return");
+        assert_eq!(get["parameters"][0]["name"], "key");
+        assert_eq!(get["x-restate-handler-type"], "SHARED");
+
+        let echo = &paths["/echo/echo"]["post"];
+        assert_eq!(echo["parameters"], json!([]));
+        assert_eq!(echo["x-restate-service-type"], "SERVICE");
+        assert!(echo.get("x-restate-handler-type").is_none());
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+};
+
+use crate::mock::{InvocationKind, Step};
+
+/// The output format of the `graph` subcommand.
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum Format {
+    /// A Graphviz DOT digraph
+    Dot,
+    /// A Mermaid flowchart
+    Mermaid,
+}
+
+/// Arguments of the `graph` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct GraphArgs {
+    /// Format of the diagram
+    #[clap(long, value_enum, default_value_t = Format::Dot)]
+    pub format: Format,
+}
+
+/// A handler, identified by its service and name.
+type Node = (String, String);
+
+/// The call topology of the configured services: the handlers and the handlers their
+/// steps call or send to.
+#[derive(Default)]
+pub struct Graph {
+    /// The configured handlers.
+    handlers: BTreeSet<Node>,
+    /// The invocations made by the steps of the handlers.
+    edges: BTreeSet<(Node, Node, InvocationKind)>,
+}
+
+impl Graph {
+    /// Adds a handler along with the invocations made by its steps, nested ones included.
+    pub fn add_handler<'a>(
+        &mut self,
+        service: &str,
+        handler: &str,
+        steps: impl IntoIterator<Item = &'a dyn Step>,
+    ) {
+        let from = (service.to_owned(), handler.to_owned());
+        self.handlers.insert(from.clone());
+
+        let mut steps: Vec<&dyn Step> = steps.into_iter().collect();
+        while let Some(step) = steps.pop() {
+            for target in step.targets() {
+                let to = (target.service.to_owned(), target.handler.to_owned());
+                self.edges.insert((from.clone(), to, target.kind));
+            }
+
+            for branch in step.branches() {
+                steps.extend(branch.steps.iter().map(|step| step.as_ref()));
+            }
+        }
+    }
+
+    /// Returns the handlers grouped by service, along with whether they are configured.
+    ///
+    /// The targets of the invocations are included, they may belong to services that are
+    /// not part of the configuration.
+    fn services(&self) -> BTreeMap<&str, BTreeMap<&str, bool>> {
+        let mut services: BTreeMap<&str, BTreeMap<&str, bool>> = BTreeMap::new();
+
+        for (_, (service, handler), _) in &self.edges {
+            services.entry(service).or_default().insert(handler, false);
+        }
+        for (service, handler) in &self.handlers {
+            services.entry(service).or_default().insert(handler, true);
+        }
+
+        services
+    }
+
+    /// Renders the graph in the given format.
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Dot => self.dot(),
+            Format::Mermaid => self.mermaid(),
+        }
+    }
+
+    /// Renders the graph as a DOT digraph, with a cluster per service. Sends are dashed,
+    /// and so are the handlers missing from the configuration.
+    fn dot(&self) -> String {
+        let id = |service: &str, handler: &str| format!("{:?}", format!("{service}/{handler}"));
+
+        let mut dot = String::from("digraph mocks {\n  rankdir=LR;\n");
+        for (service, handlers) in self.services() {
+            let _ = writeln!(dot, "  subgraph {:?} {{", format!("cluster_{service}"));
+            let _ = writeln!(dot, "    label={service:?};");
+            for (handler, configured) in handlers {
+                let style = if configured { "" } else { ", style=dashed" };
+                let _ = writeln!(
+                    dot,
+                    "    {} [label={handler:?}{style}];",
+                    id(service, handler)
+                );
+            }
+            dot.push_str("  }\n");
+        }
+
+        for ((from_service, from_handler), (to_service, to_handler), kind) in &self.edges {
+            let style = match kind {
+                InvocationKind::Call => "",
+                InvocationKind::Send => " [style=dashed]",
+            };
+            let _ = writeln!(
+                dot,
+                "  {} -> {}{style};",
+                id(from_service, from_handler),
+                id(to_service, to_handler)
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as a Mermaid flowchart, with a subgraph per service. Sends are
+    /// dotted, and the handlers missing from the configuration are dashed.
+    fn mermaid(&self) -> String {
+        let label = |text: &str| format!("\"{}\"", text.replace('"', "#quot;"));
+
+        let mut ids: HashMap<(&str, &str), String> = HashMap::new();
+        let mut mermaid = String::from("flowchart LR\n");
+        for (idx, (service, handlers)) in self.services().into_iter().enumerate() {
+            let _ = writeln!(mermaid, "  subgraph s{idx} [{}]", label(service));
+            for (handler, configured) in handlers {
+                let id = format!("n{}", ids.len());
+                let class = if configured { "" } else { ":::missing" };
+                let _ = writeln!(mermaid, "    {id}[{}]{class}", label(handler));
+                ids.insert((service, handler), id);
+            }
+            mermaid.push_str("  end\n");
+        }
+
+        for ((from_service, from_handler), (to_service, to_handler), kind) in &self.edges {
+            let arrow = match kind {
+                InvocationKind::Call => "-->",
+                InvocationKind::Send => "-.->",
+            };
+            let _ = writeln!(
+                mermaid,
+                "  {} {arrow} {}",
+                ids[&(from_service.as_str(), from_handler.as_str())],
+                ids[&(to_service.as_str(), to_handler.as_str())]
+            );
+        }
+
+        mermaid.push_str("  classDef missing stroke-dasharray: 5 5\n");
+        mermaid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StepConfig;
+    use crate::mock::create_step;
+
+    #[tokio::test]
+    async fn test_graph() {
+        let configs: Vec<StepConfig> = serde_yaml::from_str(
+            "
+- type: call
+  params: {target_type: SERVICE, service: echo, handler: echo}
+- type: loop
+  params:
+    count: 2
+    steps:
+      - type: send
+        params: {target_type: SERVICE, service: external, handler: notify}
+",
+        )
+        .unwrap();
+        let mut steps = Vec::new();
+        for config in configs {
+            steps.push(create_step(config).await.unwrap());
+        }
+
+        let mut graph = Graph::default();
+        graph.add_handler("caller", "run", steps.iter().map(|step| step.as_ref()));
+        graph.add_handler("echo", "echo", []);
+
+        assert_eq!(
+            graph.render(Format::Dot),
+            r#"digraph mocks {
+  rankdir=LR;
+  subgraph "cluster_caller" {
+    label="caller";
+    "caller/run" [label="run"];
+  }
+  subgraph "cluster_echo" {
+    label="echo";
+    "echo/echo" [label="echo"];
+  }
+  subgraph "cluster_external" {
+    label="external";
+    "external/notify" [label="notify", style=dashed];
+  }
+  "caller/run" -> "echo/echo";
+  "caller/run" -> "external/notify" [style=dashed];
+}
+"#
+        );
+
+        assert_eq!(
+            graph.render(Format::Mermaid),
+            r#"flowchart LR
+  subgraph s0 ["caller"]
+    n0["run"]
+  end
+  subgraph s1 ["echo"]
+    n1["echo"]
+  end
+  subgraph s2 ["external"]
+    n2["notify"]:::missing
+  end
+  n0 --> n1
+  n0 -.-> n2
+  classDef missing stroke-dasharray: 5 5
+"#
+        );
+    }
+}
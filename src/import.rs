@@ -0,0 +1,206 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+use restate_sdk::discovery::{HandlerType, ServiceType};
+use serde::Deserialize;
+
+use crate::config::{Configuration, HandlerConfig, ServiceConfig, StepConfig};
+
+/// Arguments of the `import` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct ImportArgs {
+    /// URL of the Restate admin API to import the services from (e.g. http://localhost:9070)
+    #[clap(long, value_parser)]
+    admin_url: String,
+    /// Overwrite the config file if it already exists
+    #[clap(long)]
+    force: bool,
+}
+
+/// The services registered in Restate, as returned by `GET /services`.
+#[derive(Debug, Deserialize)]
+struct Services {
+    services: Vec<ServiceMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ServiceMetadata {
+    name: String,
+    ty: String,
+    handlers: Handlers,
+}
+
+/// The handlers of a service, listed or keyed by name depending on the Restate version.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Handlers {
+    List(Vec<HandlerMetadata>),
+    Map(BTreeMap<String, HandlerMetadata>),
+}
+
+#[derive(Debug, Deserialize)]
+struct HandlerMetadata {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    ty: Option<String>,
+}
+
+/// Generates a mock configuration matching the services registered in Restate, every
+/// handler echoing its input, and writes it to `config_file`.
+pub async fn run(args: &ImportArgs, config_file: &Path) -> anyhow::Result<()> {
+    if config_file.exists() && !args.force {
+        anyhow::bail!(
+            "{} already exists, use --force to overwrite it",
+            config_file.display()
+        );
+    }
+
+    let admin = args.admin_url.trim_end_matches('/');
+    let services: Services = reqwest::get(format!("{admin}/services"))
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("Failed to list the services of {admin}"))?
+        .json()
+        .await
+        .with_context(|| format!("Invalid services returned by {admin}"))?;
+
+    let config = scaffold(services)?;
+    std::fs::write(config_file, serde_yaml::to_string(&config)?)
+        .with_context(|| format!("Failed to write config file {}", config_file.display()))?;
+
+    println!(
+        "Imported {} service(s) into {}",
+        config.services.len(),
+        config_file.display()
+    );
+
+    Ok(())
+}
+
+/// Builds the mock configuration of the registered services.
+fn scaffold(services: Services) -> anyhow::Result<Configuration> {
+    let mut config = Configuration::default();
+
+    for service in services.services {
+        let ty = service_type(&service.ty)
+            .with_context(|| format!("Unknown type {} of service {}", service.ty, service.name))?;
+
+        let handlers = match service.handlers {
+            Handlers::List(handlers) => handlers,
+            Handlers::Map(handlers) => handlers
+                .into_iter()
+                .map(|(name, handler)| HandlerMetadata { name, ..handler })
+                .collect(),
+        };
+
+        let mut service_config = ServiceConfig {
+            ty,
+            enabled: true,
+            before: Vec::new(),
+            after: Vec::new(),
+            handlers: BTreeMap::new(),
+            stubs: Vec::new(),
+            history: None,
+            duplicates: None,
+        };
+
+        for handler in handlers {
+            let handler_type = match (ty, &handler.ty) {
+                // handlers of a SERVICE can not define a type
+                (ServiceType::Service, _) | (_, None) => None,
+                (_, Some(handler_type)) => {
+                    Some(handler_type_of(handler_type).with_context(|| {
+                        format!(
+                            "Unknown type {handler_type} of handler {}/{}",
+                            service.name, handler.name
+                        )
+                    })?)
+                }
+            };
+
+            service_config.handlers.insert(
+                handler.name,
+                HandlerConfig {
+                    ty: handler_type,
+                    stub: false,
+                    steps: vec![StepConfig::new("echo", serde_yaml::Value::Null)],
+                    ..HandlerConfig::stub()
+                },
+            );
+        }
+
+        config.services.insert(service.name, service_config);
+    }
+
+    Ok(config)
+}
+
+/// Normalizes a type name, the admin API uses `VirtualObject` where the configuration
+/// uses `VIRTUAL_OBJECT`.
+fn normalize(ty: &str) -> String {
+    ty.replace('_', "").to_lowercase()
+}
+
+fn service_type(ty: &str) -> Option<ServiceType> {
+    match normalize(ty).as_str() {
+        "service" => Some(ServiceType::Service),
+        "virtualobject" => Some(ServiceType::VirtualObject),
+        "workflow" => Some(ServiceType::Workflow),
+        _ => None,
+    }
+}
+
+fn handler_type_of(ty: &str) -> Option<HandlerType> {
+    match normalize(ty).as_str() {
+        "exclusive" => Some(HandlerType::Exclusive),
+        "shared" => Some(HandlerType::Shared),
+        "workflow" => Some(HandlerType::Workflow),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaffold() {
+        let services: Services = serde_json::from_value(serde_json::json!({
+            "services": [
+                {
+                    "name": "greeter",
+                    "ty": "Service",
+                    "handlers": [{ "name": "greet", "ty": null }],
+                },
+                {
+                    "name": "counter",
+                    "ty": "VirtualObject",
+                    "handlers": {
+                        "add": { "ty": "Exclusive" },
+                        "get": { "ty": "Shared" },
+                    },
+                },
+            ],
+        }))
+        .unwrap();
+
+        let config = scaffold(services).unwrap();
+
+        let greeter = &config.services["greeter"];
+        assert_eq!(greeter.ty, ServiceType::Service);
+        assert_eq!(greeter.handlers["greet"].ty, None);
+        assert_eq!(greeter.handlers["greet"].steps[0].ty, "echo");
+
+        let counter = &config.services["counter"];
+        assert_eq!(counter.ty, ServiceType::VirtualObject);
+        assert_eq!(counter.handlers["add"].ty, Some(HandlerType::Exclusive));
+        assert_eq!(counter.handlers["get"].ty, Some(HandlerType::Shared));
+
+        let unknown: Services = serde_json::from_value(serde_json::json!({
+            "services": [{ "name": "broken", "ty": "Actor", "handlers": [] }],
+        }))
+        .unwrap();
+        assert!(scaffold(unknown).is_err());
+    }
+}
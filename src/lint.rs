@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::config::Report;
+use crate::mock::{Step, ALL_VARIABLES};
+
+/// Collects the problems of a configuration that do not prevent it from loading but are
+/// likely mistakes: unused variables and steps that never run. Handlers nobody calls are
+/// only warned about, since they may well be invoked from outside the configuration.
+///
+/// Steps shared by several handlers (hooks, `before` and `after` steps) are linted along
+/// with every handler running them, but every problem is reported only once.
+#[derive(Default)]
+pub struct Lint {
+    /// The problems found so far, by path.
+    problems: BTreeSet<(String, String)>,
+    /// Whether the variables written by a step (keyed by the step path and the variable
+    /// name) are read by a step of any handler running it.
+    variables: BTreeMap<(String, String), bool>,
+    /// The handlers that should be the target of a step, with their path.
+    handlers: Vec<(String, String, String)>,
+    /// The `(service, handler)` pairs invoked by any step.
+    targets: HashSet<(String, String)>,
+}
+
+impl Lint {
+    /// Lints the steps of a handler, in execution order, along with their path. The
+    /// `after_return` steps run even if the handler ends early.
+    ///
+    /// If `called` is set, the handler is warned about unless a step of the configuration
+    /// invokes it.
+    pub fn add_handler<'a>(
+        &mut self,
+        service: &str,
+        handler: &str,
+        path: &str,
+        called: bool,
+        steps: impl IntoIterator<Item = (String, &'a dyn Step)>,
+        after_return: impl IntoIterator<Item = (String, &'a dyn Step)>,
+    ) {
+        if called {
+            self.handlers
+                .push((service.to_owned(), handler.to_owned(), path.to_owned()));
+        }
+
+        let mut visited = Vec::new();
+        self.visit(steps.into_iter().collect(), true, &mut visited);
+        self.visit(after_return.into_iter().collect(), true, &mut visited);
+
+        let mut inputs = HashSet::new();
+        for (_, step, runs) in &visited {
+            if *runs {
+                inputs.extend(step.inputs());
+                for target in step.targets() {
+                    self.targets
+                        .insert((target.service.to_owned(), target.handler.to_owned()));
+                }
+            }
+        }
+
+        for (path, step, _) in &visited {
+            for output in step.outputs() {
+                let used = inputs.contains(ALL_VARIABLES) || inputs.contains(output);
+                *self
+                    .variables
+                    .entry((path.clone(), output.to_owned()))
+                    .or_default() |= used;
+            }
+        }
+    }
+
+    /// Appends the steps and all their nested steps to `visited`, in execution order, along
+    /// with whether they can run at all. `runs` tells whether the list itself can run.
+    ///
+    /// The first step following a step that always ends the handler in the same list is
+    /// reported, and so are the nested lists that never run.
+    fn visit<'a>(
+        &mut self,
+        steps: Vec<(String, &'a dyn Step)>,
+        runs: bool,
+        visited: &mut Vec<(String, &'a dyn Step, bool)>,
+    ) {
+        let mut end: Option<String> = None;
+        let mut reported = false;
+
+        for (path, step) in steps {
+            if let Some(end) = end
+                .as_deref()
+                .filter(|end| !reported && list(end) == list(&path))
+            {
+                self.problems.insert((
+                    path.clone(),
+                    format!("Step never runs, the handler always ends at {end}"),
+                ));
+                reported = true;
+            }
+
+            let step_runs = runs && end.is_none();
+            visited.push((path.clone(), step, step_runs));
+
+            for branch in step.branches() {
+                let branch_path = format!("{path}.{}", branch.path);
+                if !branch.reachable {
+                    self.problems
+                        .insert((branch_path.clone(), "Nested steps never run".to_owned()));
+                }
+
+                let nested = branch
+                    .steps
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, nested)| (format!("{branch_path}[{idx}]"), nested.as_ref()))
+                    .collect();
+                self.visit(nested, step_runs && branch.reachable, visited);
+            }
+
+            if end.is_none() && step.ends_handler() {
+                end = Some(path);
+            }
+        }
+    }
+
+    /// Records all the problems found in the `report`, and the handlers never called in
+    /// `warnings`.
+    pub fn finish(self, report: &mut Report, warnings: &mut Report) {
+        let mut problems = self.problems;
+
+        for ((path, name), used) in self.variables {
+            if !used {
+                problems.insert((path, format!("Variable `{name}` is never used")));
+            }
+        }
+
+        for (service, handler, path) in self.handlers {
+            if !self.targets.contains(&(service, handler)) {
+                warnings.push(path, "Handler is never called or sent to by a step");
+            }
+        }
+
+        for (path, problem) in problems {
+            report.push(path, problem);
+        }
+    }
+}
+
+/// Returns the path of the step list holding the step at `path`.
+fn list(path: &str) -> &str {
+    path.rfind('[').map_or(path, |idx| &path[..idx])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StepConfig;
+    use crate::mock::create_step;
+
+    async fn steps(source: &str) -> Vec<Box<dyn Step>> {
+        let configs: Vec<StepConfig> = serde_yaml::from_str(source).unwrap();
+
+        let mut steps = Vec::new();
+        for config in configs {
+            steps.push(create_step(config).await.unwrap());
+        }
+
+        steps
+    }
+
+    fn with_paths<'a>(steps: &'a [Box<dyn Step>], path: &str) -> Vec<(String, &'a dyn Step)> {
+        steps
+            .iter()
+            .enumerate()
+            .map(|(idx, step)| (format!("{path}[{idx}]"), step.as_ref()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_lint() {
+        let caller = steps(
+            "
+- type: random
+  params: {size: 8, output: seed}
+- type: random
+  params: {size: 8, output: unused}
+- type: loop
+  params:
+    count: 2
+    steps:
+      - type: call
+        params: {target_type: SERVICE, service: echo, handler: echo, input: seed}
+      - type: return
+        params: {output: seed, early: true}
+      - type: echo
+- type: send
+  params: {target_type: SERVICE, service: echo, handler: never}
+",
+        )
+        .await;
+        let notify =
+            steps("[{type: send, params: {target_type: SERVICE, service: echo, handler: later}}]")
+                .await;
+        let echo = steps("[{type: loop, params: {count: 0, steps: [{type: echo}]}}]").await;
+
+        let mut lint = Lint::default();
+        lint.add_handler(
+            "caller",
+            "run",
+            "caller",
+            false,
+            with_paths(&caller, "steps"),
+            with_paths(&notify, "after_return"),
+        );
+        lint.add_handler(
+            "echo",
+            "echo",
+            "echo",
+            true,
+            with_paths(&echo, "echo"),
+            Vec::new(),
+        );
+        // only sent to by a step that never runs
+        lint.add_handler("echo", "never", "never", true, Vec::new(), Vec::new());
+        lint.add_handler("echo", "nobody", "nobody", true, Vec::new(), Vec::new());
+        // sent to after the return, even though the handler ends early
+        lint.add_handler("echo", "later", "later", true, Vec::new(), Vec::new());
+
+        let mut report = Report::new("");
+        let mut warnings = Report::new("");
+        lint.finish(&mut report, &mut warnings);
+
+        assert_eq!(
+            warnings.to_string(),
+            "Found 2 problem(s) in configuration:
+  - never: Handler is never called or sent to by a step
+  - nobody: Handler is never called or sent to by a step
+"
+        );
+        assert_eq!(
+            report.to_string(),
+            "Found 4 problem(s) in configuration:
+  - echo[0].params.steps: Nested steps never run
+  - steps[1]: Variable `unused` is never used
+  - steps[2].params.steps[2]: Step never runs, the handler always ends at steps[2].params.steps[1]
+  - steps[3]: Step never runs, the handler always ends at steps[2]
+"
+        );
+    }
+}
@@ -1,19 +1,47 @@
 use anyhow::Context;
-use config::{Configuration, StepConfig};
-use mock::{MockHandler, MockService, STEPS};
-use restate_sdk::{
-    discovery::{HandlerName, ServiceName, ServiceType},
-    prelude::*,
+use config::{
+    Configuration, HandlerConfig, HandlerContent, HooksConfig, LoggingConfig, Report,
+    ServiceConfig, StepConfig,
 };
+use mock::{
+    create_step, Hooks, JsonValue, MockHandler, MockService, PayloadLogging, CATCH_ALL_HANDLER,
+    NOT_IMPLEMENTED, VERIFY_ADD, VERIFY_TOTALS,
+};
+use regex::Regex;
+use restate_sdk::discovery::{HandlerName, HandlerType, ServiceName, ServiceType};
 
 mod config;
+mod doctor;
+mod export;
+mod graph;
+mod import;
+mod lint;
 mod mock;
-use clap::Parser;
+mod server;
+mod shard;
+mod stats;
+mod summary;
+mod verify;
+use clap::{Parser, Subcommand};
 use restate_sdk::endpoint::Endpoint;
-use std::{fs::File, io::BufReader, path::PathBuf, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
+use crate::graph::Graph;
+use crate::lint::Lint;
 use crate::mock::{Step, StepError};
+use crate::shard::Shard;
+use crate::stats::STATS;
+use crate::summary::Summary;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None, trailing_var_arg = true)]
@@ -24,23 +52,817 @@ struct Args {
     listen_address: String,
     #[clap(long, value_parser, default_value = "info")]
     log_level: String,
+    /// Address to serve the handler statistics on (`GET /stats`)
+    #[clap(long, value_parser)]
+    stats_address: Option<String>,
+    /// Number of recent invocations served on `GET /debug/invocations` of the stats address
+    #[clap(long, value_parser, default_value_t = 100)]
+    recent_invocations: usize,
+    /// Serve only the shard `<index>/<count>` of the configured services (e.g. `0/4`)
+    #[clap(long, value_parser)]
+    shard: Option<Shard>,
+    /// Serve an additional endpoint `<config_file>=<ip>:<port>`, can be repeated
+    #[clap(long = "endpoint", value_parser)]
+    endpoints: Vec<EndpointArg>,
+    /// URL of the Restate admin API. If set, the deployment is registered again on redeploy (`SIGHUP`)
+    #[clap(long, value_parser)]
+    admin_url: Option<String>,
+    /// URL of the mock service as seen by Restate, used for the registration on redeploy
+    #[clap(long, value_parser, default_value = "http://localhost:9200")]
+    deployment_url: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
-fn step_from_config(
+/// An additional endpoint served by the process, with its own configuration.
+#[derive(Debug, Clone)]
+struct EndpointArg {
+    config_file: PathBuf,
+    listen_address: SocketAddr,
+}
+
+impl FromStr for EndpointArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (config_file, listen_address) = s
+            .rsplit_once('=')
+            .ok_or_else(|| "expected <config_file>=<ip>:<port>".to_owned())?;
+
+        Ok(Self {
+            config_file: config_file.into(),
+            listen_address: listen_address
+                .parse()
+                .map_err(|err| format!("invalid listen address: {err}"))?,
+        })
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check the configuration and the environment for the most common setup problems
+    Doctor(doctor::DoctorArgs),
+    /// Print the effective configuration, with all defaults and shorthands expanded
+    Render,
+    /// Run the exactly-once verification loop against a Restate server
+    Verify(verify::VerifyArgs),
+    /// Report unused variables, steps that never run and handlers that are never called
+    Lint,
+    /// Print the call topology of the handlers as a DOT or Mermaid diagram
+    Graph(graph::GraphArgs),
+    /// Print an OpenAPI description of the handlers, to generate clients against the mocks
+    Export,
+    /// Generate the config file from the services registered in Restate, every handler echoing its input
+    Import(import::ImportArgs),
+    /// Upgrade the config file to the current version of the configuration format
+    Migrate {
+        /// Overwrite the config file instead of printing the upgraded configuration
+        #[clap(long)]
+        in_place: bool,
+    },
+}
+
+/// The static analyses of the handlers, collected while loading a configuration.
+#[derive(Default)]
+struct Analysis {
+    lint: Lint,
+    graph: Graph,
+}
+
+async fn step_from_config(
     service_type: ServiceType,
+    handler_type: Option<HandlerType>,
     step_config: StepConfig,
 ) -> Result<Box<dyn Step>, StepError> {
-    let factory = STEPS
-        .get(step_config.ty.as_str())
-        .ok_or_else(|| StepError::UnknownStepType(step_config.ty.clone()))?;
-
-    let step = factory.create(step_config.params)?;
+    let step = create_step(step_config).await?;
 
-    step.validate(service_type)?;
+    step.validate(service_type, handler_type)?;
 
     Ok(step)
 }
 
+/// Records a step creation error for the step at `path`, pointing at the offending
+/// parameter if known.
+///
+/// Steps of a step list (e.g. `steps[0]`) hold their parameters under `params`, while steps
+/// generated from an option (e.g. `mirror_to`) take them from the option itself. Errors of
+/// nested steps (e.g. the `steps` of a loop) point at the nested step.
+fn push_step_error(report: &mut Report, path: String, err: StepError) {
+    let params = if path.ends_with(']') {
+        format!("{path}.params")
+    } else {
+        path.clone()
+    };
+
+    match err {
+        StepError::Nested {
+            path: nested,
+            source,
+        } => push_step_error(report, format!("{params}.{nested}"), *source),
+        StepError::InvalidParameter { ref field, .. } | StepError::Load { ref field, .. } => {
+            report.push(format!("{params}.{field}"), err)
+        }
+        StepError::InvalidFlaky(_) => report.push(format!("{path}.flaky"), err),
+        _ => report.push(path, err),
+    }
+}
+
+async fn hooks_from_config(config: HooksConfig, report: &mut Report) -> Hooks {
+    let mut hooks = Hooks::default();
+
+    for (idx, step_cfg) in config.before.into_iter().enumerate() {
+        let path = format!("hooks.before[{idx}]");
+        match create_step(step_cfg).await {
+            Ok(step) => hooks.before.push((path, step)),
+            Err(err) => push_step_error(report, path, err),
+        }
+    }
+
+    for (idx, step_cfg) in config.after.into_iter().enumerate() {
+        let path = format!("hooks.after[{idx}]");
+        match create_step(step_cfg).await {
+            Ok(step) => hooks.after.push((path, step)),
+            Err(err) => push_step_error(report, path, err),
+        }
+    }
+
+    hooks
+}
+
+/// Builds the payload logging from its configuration.
+fn logging_from_config(config: LoggingConfig, report: &mut Report) -> PayloadLogging {
+    let mut redact = Vec::new();
+    for (idx, pattern) in config.redact.iter().enumerate() {
+        match Regex::new(pattern) {
+            Ok(pattern) => redact.push(pattern),
+            Err(err) => report.push(
+                format!("logging.redact[{idx}]"),
+                format!("Invalid regular expression: {err}"),
+            ),
+        }
+    }
+
+    PayloadLogging::new(config.payloads, config.max_length, redact)
+}
+
+/// Checks that the service option `option` adding the companion handler `handler` can be
+/// used with the service, recording the problem in the `report` otherwise.
+fn companion_handler_allowed(
+    service: &str,
+    service_config: &ServiceConfig,
+    option: &str,
+    handler: &str,
+    report: &mut Report,
+) -> bool {
+    let problem = if service_config.ty == ServiceType::Service {
+        format!("`{option}` requires a VIRTUAL_OBJECT or WORKFLOW service")
+    } else if service_config.handlers.contains_key(handler) {
+        format!("Handler {handler} is already defined")
+    } else {
+        return true;
+    };
+
+    report.push(format!("{service}.{option}"), problem);
+    false
+}
+
+/// Checks that the totals counted by every `verify-add` step of the service are returned
+/// by a `verify-totals` step of another handler, the checker queried by the `verify`
+/// subcommand, recording the problems in the `report`.
+fn check_verify_totals(service: &str, service_config: &ServiceConfig, report: &mut Report) {
+    let key = |step: &StepConfig| {
+        step.params
+            .get("key")
+            .and_then(serde_yaml::Value::as_str)
+            .map(str::to_owned)
+    };
+
+    let handlers = service_config
+        .handlers
+        .iter()
+        .filter(|(_, handler_config)| handler_config.enabled);
+    let checked: HashSet<String> = handlers
+        .clone()
+        .flat_map(|(_, handler_config)| &handler_config.steps)
+        .filter(|step| step.ty == VERIFY_TOTALS)
+        .filter_map(key)
+        .collect();
+
+    for (handler_name, handler_config) in handlers {
+        for (idx, step) in handler_config.steps.iter().enumerate() {
+            let Some(key) = key(step).filter(|_| step.ty == VERIFY_ADD) else {
+                continue;
+            };
+
+            if !checked.contains(&key) {
+                report.push(
+                    format!("{service}.handlers.{handler_name}.steps[{idx}]"),
+                    format!("No handler returns the totals of key `{key}` (with a `{VERIFY_TOTALS}` step)"),
+                );
+            }
+        }
+    }
+}
+
+/// Checks that the handler types are valid for the type of the service, recording
+/// the problems in the `report`.
+///
+/// Handlers of a `SERVICE` have no type, handlers of a `VIRTUAL_OBJECT` are `EXCLUSIVE`
+/// or `SHARED`, and a `WORKFLOW` has exactly one `WORKFLOW` handler (the run handler)
+/// while the others are `SHARED`.
+fn check_handler_types(service: &str, service_config: &ServiceConfig, report: &mut Report) {
+    let allowed: &[HandlerType] = match service_config.ty {
+        ServiceType::Service => &[],
+        ServiceType::VirtualObject => &[HandlerType::Exclusive, HandlerType::Shared],
+        ServiceType::Workflow => &[HandlerType::Workflow, HandlerType::Shared],
+    };
+
+    let handlers = service_config
+        .handlers
+        .iter()
+        .filter(|(_, handler_config)| handler_config.enabled);
+
+    let mut run_handlers = Vec::new();
+    for (handler_name, handler_config) in handlers {
+        let Some(ty) = handler_config.ty else {
+            continue;
+        };
+
+        if !allowed.contains(&ty) {
+            let problem = match service_config.ty {
+                ServiceType::Service => "Handlers of a SERVICE can not define a type".to_owned(),
+                service_type => format!(
+                    "Handler type {} is not allowed in a {}, expected one of: {}",
+                    ty.to_string(),
+                    service_type.to_string(),
+                    allowed
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            };
+            report.push(format!("{service}.handlers.{handler_name}.type"), problem);
+        } else if ty == HandlerType::Workflow && handler_name != CATCH_ALL_HANDLER {
+            run_handlers.push(handler_name.as_str());
+        }
+    }
+
+    if service_config.ty == ServiceType::Workflow && run_handlers.len() != 1 {
+        report.push(
+            format!("{service}.handlers"),
+            format!(
+                "A WORKFLOW must define exactly one handler of type WORKFLOW, found {}",
+                match run_handlers.as_slice() {
+                    [] => "none".to_owned(),
+                    handlers => handlers.join(", "),
+                }
+            ),
+        );
+    }
+}
+
+/// Records the variables written by the step at `path` in `outputs`, which maps every
+/// variable to the path of the step writing it, and returns a problem for every variable
+/// already written by a previous step of the handler.
+fn check_outputs(
+    path: &str,
+    step: &dyn Step,
+    outputs: &mut HashMap<String, String>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for output in step.outputs() {
+        match outputs.get(output) {
+            Some(other) if other == path => problems.push(format!(
+                "Variable `{output}` is the output of more than one nested step"
+            )),
+            Some(other) => problems.push(format!(
+                "Variable `{output}` is already the output of {other}"
+            )),
+            None => {
+                outputs.insert(output.to_owned(), path.to_owned());
+            }
+        }
+    }
+
+    problems
+}
+
+/// Builds the `MockService` for the given service configuration.
+///
+/// All the problems found are recorded in the `report`, and the handlers are added to the
+/// `analysis`. `None` is returned if the service is disabled or its name is invalid.
+async fn service_from_config(
+    service: &str,
+    mut service_config: ServiceConfig,
+    hooks: &Hooks,
+    summary: &mut Summary,
+    analysis: &mut Analysis,
+    report: &mut Report,
+) -> Option<MockService> {
+    if !service_config.enabled {
+        tracing::info!("Skipping disabled service '{service}'");
+        return None;
+    }
+
+    tracing::debug!(
+        "Setting up service '{service}' of type {:?}",
+        service_config.ty
+    );
+
+    let service_name = match ServiceName::from_str(service) {
+        Ok(name) => name,
+        Err(err) => {
+            report.push(service, format!("Invalid service name: {err}"));
+            return None;
+        }
+    };
+
+    let mut mock_service = MockService::new(service_name, service_config.ty);
+    // steps shared by all the handlers (e.g. `before`) report their collisions only once
+    let mut collisions = HashSet::new();
+
+    for stub in std::mem::take(&mut service_config.stubs) {
+        if service_config.handlers.contains_key(&stub) {
+            report.push(
+                format!("{service}.stubs"),
+                format!("Stub handler {stub} is already defined"),
+            );
+            continue;
+        }
+
+        service_config.handlers.insert(stub, HandlerConfig::stub());
+    }
+
+    let history = service_config.history.take().filter(|history| {
+        companion_handler_allowed(
+            service,
+            &service_config,
+            "history",
+            &history.handler,
+            report,
+        )
+    });
+    if let Some(history) = &history {
+        service_config
+            .handlers
+            .insert(history.handler.clone(), history.history_handler());
+    }
+
+    let duplicates = service_config.duplicates.take().filter(|duplicates| {
+        companion_handler_allowed(
+            service,
+            &service_config,
+            "duplicates",
+            &duplicates.handler,
+            report,
+        )
+    });
+    if let Some(duplicates) = &duplicates {
+        service_config
+            .handlers
+            .insert(duplicates.handler.clone(), duplicates.stats_handler());
+    }
+
+    check_handler_types(service, &service_config, report);
+    check_verify_totals(service, &service_config, report);
+
+    for (handler_name, mut handler_config) in service_config.handlers {
+        let handler_path = format!("{service}.handlers.{handler_name}");
+
+        if !handler_config.enabled {
+            tracing::info!("Skipping disabled handler '{handler_name}' of service '{service}'");
+            continue;
+        }
+
+        tracing::info!("Adding handler '{handler_name}'to service '{service}'");
+
+        let handler_type = handler_config.effective_type(service_config.ty);
+
+        if handler_config.content == HandlerContent::Raw && handler_config.default_input.is_some() {
+            report.push(&handler_path, "Raw handler can not define a default input");
+        }
+
+        for (path, step) in hooks.steps() {
+            if let Err(err) = step.validate(service_config.ty, handler_type) {
+                report.push(path, format!("{err} (in handler {service}/{handler_name})"));
+            }
+        }
+
+        let after_return_configs: Vec<(String, StepConfig)> =
+            std::mem::take(&mut handler_config.after_return)
+                .into_iter()
+                .enumerate()
+                .map(|(idx, cfg)| (format!("{handler_path}.after_return[{idx}]"), cfg))
+                .collect();
+
+        let step_configs: Vec<(String, StepConfig)> = if handler_config.stub {
+            if !handler_config.steps.is_empty() || !after_return_configs.is_empty() {
+                report.push(&handler_path, "Stub handler can not define steps");
+            }
+
+            vec![(
+                handler_path.clone(),
+                StepConfig::new(NOT_IMPLEMENTED, serde_yaml::Value::Null),
+            )]
+        } else {
+            let before = service_config
+                .before
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(idx, cfg)| (format!("{service}.before[{idx}]"), cfg));
+            let exclusive = handler_config.is_exclusive(service_config.ty);
+            let record = history.as_ref().filter(|_| exclusive).map(|history| {
+                (
+                    format!("{service}.history"),
+                    history.record_step(&handler_name),
+                )
+            });
+            let detect = duplicates
+                .as_ref()
+                .filter(|_| exclusive)
+                .map(|duplicates| (format!("{service}.duplicates"), duplicates.detect_step()));
+            let auth = handler_config
+                .auth
+                .as_ref()
+                .map(|auth| (format!("{handler_path}.auth"), auth.step()));
+            let mirror = handler_config
+                .mirror_to
+                .as_ref()
+                .map(|mirror| (format!("{handler_path}.mirror_to"), mirror.step()));
+            let steps = handler_config
+                .steps
+                .into_iter()
+                .enumerate()
+                .map(|(idx, cfg)| (format!("{handler_path}.steps[{idx}]"), cfg));
+            let after = service_config
+                .after
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(idx, cfg)| (format!("{service}.after[{idx}]"), cfg));
+
+            before
+                .chain(record)
+                .chain(detect)
+                .chain(auth)
+                .chain(mirror)
+                .chain(steps)
+                .chain(after)
+                .collect()
+        };
+
+        summary.add(
+            service,
+            service_config.ty,
+            &handler_name,
+            handler_config.ty,
+            step_configs
+                .iter()
+                .chain(&after_return_configs)
+                .map(|(_, cfg)| cfg.ty.clone())
+                .collect(),
+        );
+
+        let mut steps: Vec<(String, Box<dyn Step>)> = Vec::new();
+        let mut after_return: Vec<(String, Box<dyn Step>)> = Vec::new();
+        let mut outputs = HashMap::new();
+        let step_configs = step_configs
+            .into_iter()
+            .map(|step| (step, false))
+            .chain(after_return_configs.into_iter().map(|step| (step, true)));
+        for ((path, step_cfg), returned) in step_configs {
+            match step_from_config(service_config.ty, handler_type, step_cfg).await {
+                Ok(step) if returned && !step.fire_and_forget() => report.push(
+                    &path,
+                    "Only fire-and-forget steps (e.g. `send`) can run after the return",
+                ),
+                Ok(step) => {
+                    for problem in check_outputs(&path, step.as_ref(), &mut outputs) {
+                        if collisions.insert((path.clone(), problem.clone())) {
+                            report.push(&path, problem);
+                        }
+                    }
+                    if returned {
+                        after_return.push((path, step));
+                    } else {
+                        steps.push((path, step));
+                    }
+                }
+                Err(err) => push_step_error(report, path, err),
+            }
+        }
+
+        // companion handlers are read by the tests, not called by other handlers
+        let companion = history.as_ref().map(|history| &history.handler) == Some(&handler_name)
+            || duplicates.as_ref().map(|duplicates| &duplicates.handler) == Some(&handler_name);
+        // the `after` hooks run even if the handler ends early, like the `after_return` steps
+        let before = hooks.before.iter().map(|(path, step)| (path.clone(), step));
+        let all_steps: Vec<(String, &dyn Step)> = before
+            .chain(steps.iter().map(|(path, step)| (path.clone(), step)))
+            .map(|(path, step)| (path, step.as_ref()))
+            .collect();
+        let after_steps = hooks
+            .after
+            .iter()
+            .chain(after_return.iter())
+            .map(|(path, step)| (path.clone(), step.as_ref()));
+
+        analysis.graph.add_handler(
+            service,
+            &handler_name,
+            all_steps
+                .iter()
+                .map(|(_, step)| *step)
+                .chain(after_steps.clone().map(|(_, step)| step)),
+        );
+        analysis.lint.add_handler(
+            service,
+            &handler_name,
+            &handler_path,
+            !companion && handler_name != CATCH_ALL_HANDLER,
+            all_steps,
+            after_steps,
+        );
+
+        let handler = MockHandler {
+            steps: steps.into_iter().map(|(_, step)| step).collect(),
+            ty: handler_config.ty,
+            slo: handler_config.slo.map(Into::into),
+            default_input: handler_config.default_input.map(JsonValue),
+            content: handler_config.content,
+            after_return: after_return.into_iter().map(|(_, step)| step).collect(),
+        };
+
+        if handler_name == CATCH_ALL_HANDLER {
+            mock_service.set_catch_all_handler(handler);
+            continue;
+        }
+
+        match HandlerName::from_str(&handler_name) {
+            Ok(name) => mock_service.add_handler(name, handler),
+            Err(err) => report.push(handler_path, format!("Invalid handler name: {err}")),
+        }
+    }
+
+    Some(mock_service)
+}
+
+/// Loads the configuration file and builds the endpoint with all the configured services.
+///
+/// If a `shard` is given, only its services are bound. The services of the other shards are
+/// still validated, so all the shards reject an invalid configuration alike.
+///
+/// Returns the endpoint along with the summary of the bound handlers. The handlers of all the
+/// shards are added to the `analysis`.
+async fn load_endpoint(
+    config_file: &Path,
+    shard: Option<Shard>,
+    analysis: &mut Analysis,
+) -> anyhow::Result<(Endpoint, Summary)> {
+    let (config, report) = read_config(config_file)?;
+    build_endpoint(config_file, config, report, shard, analysis).await
+}
+
+/// Reads and parses the configuration file. The problems found are recorded in the
+/// returned report.
+fn read_config(config_file: &Path) -> anyhow::Result<(Configuration, Report)> {
+    tracing::debug!("Loading configuration from: {:?}", config_file);
+    let source = std::fs::read_to_string(config_file)
+        .with_context(|| format!("Failed to open config file {}", config_file.display()))?;
+
+    let mut report = Report::new(&source);
+    let config = Configuration::parse(&source, &mut report);
+
+    Ok((config, report))
+}
+
+/// Builds the endpoint with all the services of the parsed configuration, see
+/// [`load_endpoint`].
+async fn build_endpoint(
+    config_file: &Path,
+    config: Configuration,
+    mut report: Report,
+    shard: Option<Shard>,
+    analysis: &mut Analysis,
+) -> anyhow::Result<(Endpoint, Summary)> {
+    let hooks = Arc::new(hooks_from_config(config.hooks, &mut report).await);
+    let logging = Arc::new(logging_from_config(config.logging, &mut report));
+
+    let mut summary = Summary::default();
+    let mut services = Vec::new();
+
+    for (service, service_config) in config.services {
+        if shard.is_none_or(|shard| shard.contains(&service)) {
+            if let Some(mock_service) = service_from_config(
+                &service,
+                service_config,
+                &hooks,
+                &mut summary,
+                analysis,
+                &mut report,
+            )
+            .await
+            {
+                services.push(mock_service);
+            }
+        } else {
+            tracing::debug!("Skipping service '{service}' of another shard");
+            service_from_config(
+                &service,
+                service_config,
+                &hooks,
+                &mut Summary::default(),
+                analysis,
+                &mut report,
+            )
+            .await;
+        }
+    }
+
+    report
+        .finish()
+        .with_context(|| format!("Failed to load config file {}", config_file.display()))?;
+
+    if summary.is_empty() {
+        match shard {
+            Some(shard) => anyhow::bail!(
+                "No handlers configured for shard {shard} in {}",
+                config_file.display()
+            ),
+            None => anyhow::bail!("No handlers configured in {}", config_file.display()),
+        }
+    }
+
+    let mut endpoint_builder = Endpoint::builder();
+    for mock_service in services {
+        endpoint_builder = mock_service
+            .bind(endpoint_builder, hooks.clone(), logging.clone())
+            .await;
+    }
+
+    Ok((endpoint_builder.build(), summary))
+}
+
+/// Prints the effective configuration as YAML.
+///
+/// The configuration is fully loaded first, so invalid configurations are rejected
+/// with the same errors reported when running the service.
+async fn render(config_file: &Path) -> anyhow::Result<()> {
+    let (config, report) = read_config(config_file)?;
+    let effective = config.clone().effective();
+    build_endpoint(config_file, config, report, None, &mut Analysis::default()).await?;
+
+    print!("{}", serde_yaml::to_string(&effective)?);
+
+    Ok(())
+}
+
+/// Lints the configuration, failing with the list of problems found if any. The warnings
+/// are printed but do not fail the lint.
+///
+/// The configuration is fully loaded first, so invalid configurations are rejected
+/// with the same errors reported when running the service.
+async fn lint(config_file: &Path) -> anyhow::Result<()> {
+    let mut analysis = Analysis::default();
+    load_endpoint(config_file, None, &mut analysis).await?;
+
+    let source = std::fs::read_to_string(config_file)
+        .with_context(|| format!("Failed to open config file {}", config_file.display()))?;
+    let mut report = Report::new(&source);
+    let mut warnings = Report::new(&source);
+    analysis.lint.finish(&mut report, &mut warnings);
+
+    for warning in warnings.problems() {
+        eprintln!("warning: {warning}");
+    }
+
+    report
+        .finish()
+        .with_context(|| format!("Lint of config file {} failed", config_file.display()))?;
+    println!("No problems found in {}", config_file.display());
+
+    Ok(())
+}
+
+/// Prints the call topology of the handlers of all the shards.
+async fn graph(config_file: &Path, args: &graph::GraphArgs) -> anyhow::Result<()> {
+    let mut analysis = Analysis::default();
+    load_endpoint(config_file, None, &mut analysis).await?;
+
+    print!("{}", analysis.graph.render(args.format));
+
+    Ok(())
+}
+
+/// Prints the OpenAPI description of the handlers of all the shards as JSON.
+async fn export(config_file: &Path) -> anyhow::Result<()> {
+    let (_, summary) = load_endpoint(config_file, None, &mut Analysis::default()).await?;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&export::openapi(&summary))?
+    );
+
+    Ok(())
+}
+
+/// Upgrades the configuration file to the current version of the format, printing the
+/// upgraded configuration or overwriting the file if `in_place` is set.
+///
+/// The comments of the file are not preserved.
+fn migrate(config_file: &Path, in_place: bool) -> anyhow::Result<()> {
+    let source = std::fs::read_to_string(config_file)
+        .with_context(|| format!("Failed to open config file {}", config_file.display()))?;
+
+    let mut document: serde_yaml::Mapping = serde_yaml::from_str(&source)
+        .with_context(|| format!("Failed to parse config file {}", config_file.display()))?;
+    let from = config::migrate(&mut document).map_err(|err| {
+        anyhow::anyhow!(
+            "Failed to migrate config file {}: {err}",
+            config_file.display()
+        )
+    })?;
+    let migrated = serde_yaml::to_string(&document)?;
+
+    if !in_place {
+        print!("{migrated}");
+    } else if from == config::VERSION {
+        println!(
+            "{} is already at version {}",
+            config_file.display(),
+            config::VERSION
+        );
+    } else {
+        std::fs::write(config_file, migrated)
+            .with_context(|| format!("Failed to write config file {}", config_file.display()))?;
+        println!(
+            "Migrated {} from version {from} to {}",
+            config_file.display(),
+            config::VERSION
+        );
+    }
+
+    Ok(())
+}
+
+/// Simulates a redeployment on every `SIGHUP`: the configuration files are reloaded, the
+/// endpoints are rebuilt, and the deployment is registered again with Restate if `--admin-url`
+/// is set.
+///
+/// A configuration that fails to load keeps its previous endpoint.
+#[cfg(unix)]
+async fn redeploy_on_signal(
+    args: &Args,
+    deployments: Vec<(PathBuf, watch::Sender<Endpoint>)>,
+) -> anyhow::Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    let client = reqwest::Client::new();
+
+    while hangup.recv().await.is_some() {
+        tracing::info!("Redeploying");
+        // the endpoints loaded below share the barriers they define, but not with the
+        // previous ones
+        mock::reset_barriers();
+
+        for (config_file, sender) in &deployments {
+            match load_endpoint(config_file, args.shard, &mut Analysis::default()).await {
+                Ok((endpoint, summary)) => {
+                    println!("{summary}");
+                    sender.send_replace(endpoint);
+                }
+                Err(err) => tracing::error!("Failed to redeploy, keeping the endpoint: {err:#}"),
+            }
+        }
+
+        if let Some(admin) = &args.admin_url {
+            match verify::register(&client, admin.trim_end_matches('/'), &args.deployment_url).await
+            {
+                Ok(()) => tracing::info!("Registered {} again", args.deployment_url),
+                Err(err) => tracing::error!("{err:#}"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// There is no `SIGHUP` to simulate a redeployment with outside of unix, the endpoints are
+/// never rebuilt.
+#[cfg(not(unix))]
+async fn redeploy_on_signal(
+    args: &Args,
+    _deployments: Vec<(PathBuf, watch::Sender<Endpoint>)>,
+) -> anyhow::Result<()> {
+    if args.admin_url.is_some() {
+        anyhow::bail!("Redeploying on SIGHUP (--admin-url) is only supported on unix platforms");
+    }
+
+    std::future::pending().await
+}
+
 #[restate_sdk::object]
 trait TestVO {
     async fn work() -> HandlerResult<()>;
@@ -54,65 +876,95 @@ async fn main() -> anyhow::Result<()> {
         .or_else(|_| EnvFilter::try_new(&args.log_level))
         .unwrap_or_else(|_| EnvFilter::new("info"));
 
-    let subscriber = FmtSubscriber::builder().with_env_filter(filter).finish();
+    let subscriber = FmtSubscriber::builder()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .finish();
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
-    tracing::debug!("Loading configuration from: {:?}", args.config_file);
-    let file = File::open(&args.config_file)
-        .with_context(|| format!("Failed to open config file {}", args.config_file.display()))?;
+    match &args.command {
+        Some(Command::Doctor(doctor_args)) => {
+            let endpoint = load_endpoint(&args.config_file, args.shard, &mut Analysis::default())
+                .await
+                .map(|(endpoint, _)| endpoint);
 
-    let reader = BufReader::new(file);
-    let config: Configuration =
-        serde_yaml::from_reader(reader).context("Failed to parse config")?;
-
-    let mut endpoint_builder = Endpoint::builder();
+            return doctor::run(doctor_args, &args.listen_address, endpoint).await;
+        }
+        Some(Command::Render) => return render(&args.config_file).await,
+        Some(Command::Lint) => return lint(&args.config_file).await,
+        Some(Command::Graph(graph_args)) => return graph(&args.config_file, graph_args).await,
+        Some(Command::Export) => return export(&args.config_file).await,
+        Some(Command::Import(import_args)) => {
+            return import::run(import_args, &args.config_file).await
+        }
+        Some(Command::Migrate { in_place }) => return migrate(&args.config_file, *in_place),
+        Some(Command::Verify(verify_args)) => {
+            let (endpoint, _) =
+                load_endpoint(&args.config_file, args.shard, &mut Analysis::default()).await?;
 
-    for (service, service_config) in config.services {
-        tracing::debug!(
-            "Setting up service '{service}' of type {:?}",
-            service_config.ty
-        );
+            return verify::run(verify_args, &args.listen_address, endpoint).await;
+        }
+        None => {}
+    }
 
-        let service_name = ServiceName::from_str(&service)
-            .with_context(|| format!("Invalid service name {service}"))?;
+    // a single `--deployment-url` can not register the additional endpoints again
+    if args.admin_url.is_some() && !args.endpoints.is_empty() {
+        anyhow::bail!("--admin-url can not be used together with --endpoint");
+    }
 
-        let mut mock_service = MockService::new(service_name, service_config.ty);
+    let mut configs = vec![(
+        args.config_file.clone(),
+        args.listen_address.parse::<SocketAddr>()?,
+    )];
+    configs.extend(
+        args.endpoints
+            .iter()
+            .map(|extra| (extra.config_file.clone(), extra.listen_address)),
+    );
 
-        for (handler_name, handler_config) in service_config.handlers {
-            tracing::info!("Adding handler '{handler_name}'to service '{service}'");
+    let mut endpoints = Vec::new();
+    for (config_file, address) in &configs {
+        let (endpoint, summary) =
+            load_endpoint(config_file, args.shard, &mut Analysis::default()).await?;
 
-            let mut steps: Vec<Box<dyn Step>> = Vec::new();
-            for (idx, step_cfg) in handler_config.steps.into_iter().enumerate() {
-                // The `?` operator will convert restate_sdk::Error into Box<dyn std::error::Error>
-                steps.push(
-                    step_from_config(service_config.ty, step_cfg).with_context(|| {
-                        format!("Failed to create step {idx} for handler {handler_name}")
-                    })?,
-                );
-            }
+        if configs.len() > 1 {
+            println!("Endpoint {address}:");
+        }
+        println!("{summary}");
 
-            let handler_name = HandlerName::from_str(&handler_name)
-                .with_context(|| format!("Invalid handler name {}", handler_name))?;
+        endpoints.push(endpoint);
+    }
 
-            mock_service.add_handler(
-                handler_name,
-                MockHandler {
-                    steps,
-                    ty: handler_config.ty,
-                },
-            );
-        }
+    // nobody can read the recent invocations without the stats server
+    STATS.keep_recent(if args.stats_address.is_some() {
+        args.recent_invocations
+    } else {
+        0
+    });
 
-        endpoint_builder = mock_service.bind(endpoint_builder).await;
+    if let Some(stats_address) = &args.stats_address {
+        let stats_address = stats_address.parse()?;
+        tokio::spawn(async move {
+            if let Err(err) = stats::serve(stats_address).await {
+                tracing::error!("Failed to serve stats: {err:#}");
+            }
+        });
     }
 
-    let endpoint = endpoint_builder.build();
+    let mut servers = tokio::task::JoinSet::new();
+    let mut deployments = Vec::new();
+    for ((config_file, address), endpoint) in configs.into_iter().zip(endpoints) {
+        let (sender, receiver) = watch::channel(endpoint);
+        servers.spawn(server::serve(address, receiver));
+        deployments.push((config_file, sender));
+    }
 
-    tracing::info!("Starting server on {}", args.listen_address);
-    HttpServer::new(endpoint)
-        .listen_and_serve(args.listen_address.parse()?)
-        .await;
+    tokio::select! {
+        Some(res) = servers.join_next() => res??,
+        res = redeploy_on_signal(&args, deployments) => res?,
+        _ = tokio::signal::ctrl_c() => tracing::info!("Shutting down"),
+    }
 
     Ok(())
 }
@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+
+use tokio::sync::watch;
+
+use super::clock::Clock;
+
+/// The barriers of the process, by name.
+static BARRIERS: LazyLock<Mutex<HashMap<String, Arc<Barrier>>>> = LazyLock::new(Default::default);
+
+/// Returns the barrier with the given name, creating it if needed.
+///
+/// Fails with the number of parties of the existing barrier if it waits for a different
+/// number of parties.
+pub fn barrier(name: &str, parties: usize) -> Result<Arc<Barrier>, usize> {
+    let mut barriers = BARRIERS.lock().expect("barriers lock");
+    let barrier = barriers
+        .entry(name.to_owned())
+        .or_insert_with(|| Arc::new(Barrier::new(parties)));
+
+    if barrier.parties != parties {
+        return Err(barrier.parties);
+    }
+
+    Ok(Arc::clone(barrier))
+}
+
+/// Forgets all the barriers, so the configurations loaded afterwards define them again
+/// (e.g. with a different number of parties). The steps created before keep their barrier.
+pub fn reset() {
+    BARRIERS.lock().expect("barriers lock").clear();
+}
+
+/// A reusable barrier releasing the waiting tasks once `parties` of them reached it.
+///
+/// Unlike [`tokio::sync::Barrier`], a waiting task that times out (or is cancelled) no
+/// longer counts as arrived, so the barrier can still be met by the following tasks.
+pub struct Barrier {
+    parties: usize,
+    /// The number of tasks waiting for the current generation to be released.
+    arrived: Mutex<usize>,
+    /// The generation of the barrier, bumped (under the `arrived` lock) on every release.
+    generation: watch::Sender<u64>,
+}
+
+impl Barrier {
+    fn new(parties: usize) -> Self {
+        Self {
+            parties,
+            arrived: Mutex::new(0),
+            generation: watch::Sender::new(0),
+        }
+    }
+
+    /// Waits until `parties` tasks reached the barrier, at most for `timeout` of the `clock`.
+    ///
+    /// Returns `false` if the barrier was not released in time.
+    pub async fn wait(&self, timeout: Duration, clock: &dyn Clock) -> bool {
+        let mut arrival = {
+            let mut arrived = self.arrived.lock().expect("barrier lock");
+            *arrived += 1;
+            if *arrived == self.parties {
+                *arrived = 0;
+                self.generation.send_modify(|generation| *generation += 1);
+                return true;
+            }
+
+            Arrival {
+                barrier: self,
+                generation: *self.generation.borrow(),
+                released: false,
+            }
+        };
+
+        let mut generation = self.generation.subscribe();
+        let released = generation.wait_for(|current| *current != arrival.generation);
+        arrival.released = tokio::select! {
+            biased;
+            _ = released => true,
+            () = clock.sleep(timeout) => false,
+        };
+        arrival.released
+    }
+}
+
+/// A task waiting at a barrier, withdrawn from it when dropped before the release.
+struct Arrival<'a> {
+    barrier: &'a Barrier,
+    generation: u64,
+    released: bool,
+}
+
+impl Drop for Arrival<'_> {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+
+        let mut arrived = self.barrier.arrived.lock().expect("barrier lock");
+        // the barrier may have been released since the wait timed out
+        if *self.barrier.generation.borrow() == self.generation {
+            *arrived -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::clock::TokioClock;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_barrier() {
+        let barrier = Arc::new(Barrier::new(2));
+
+        // a lone task times out and is withdrawn
+        assert!(!barrier.wait(Duration::from_secs(60), &TokioClock).await);
+        assert_eq!(*barrier.arrived.lock().unwrap(), 0);
+
+        let waiting = tokio::spawn({
+            let barrier = Arc::clone(&barrier);
+            async move { barrier.wait(Duration::from_secs(5), &TokioClock).await }
+        });
+        while *barrier.arrived.lock().unwrap() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(barrier.wait(Duration::from_secs(5), &TokioClock).await);
+        assert!(waiting.await.unwrap());
+
+        // the barrier is reusable
+        assert_eq!(*barrier.arrived.lock().unwrap(), 0);
+        assert_eq!(*barrier.generation.borrow(), 1);
+
+        assert!(super::barrier("test", 2).is_ok());
+        assert_eq!(super::barrier("test", 3).err(), Some(2));
+
+        // a reload can redefine the barrier
+        reset();
+        assert!(super::barrier("test", 3).is_ok());
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
+};
+
+/// The source of time of the steps waiting or measuring durations within the mock service.
+///
+/// Durable timers (e.g. the `sleep` step) are managed by the Restate runtime and do not go
+/// through the clock.
+#[async_trait::async_trait]
+pub trait Clock: Send + Sync + 'static {
+    /// Returns the current instant.
+    fn now(&self) -> Instant;
+
+    /// Waits for the given duration.
+    async fn sleep(&self, duration: Duration);
+}
+
+/// The clock of the tokio runtime, following its paused time in tests
+/// (`#[tokio::test(start_paused = true)]`).
+pub struct TokioClock;
+
+#[async_trait::async_trait]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+static SYSTEM: LazyLock<Arc<dyn Clock>> = LazyLock::new(|| Arc::new(TokioClock));
+
+/// Returns the clock used by the steps.
+pub fn system() -> Arc<dyn Clock> {
+    Arc::clone(&SYSTEM)
+}
+
+/// A clock for the tests whose sleeps return at once, only advancing its time, so the
+/// steps given the clock wait without slowing down the tests.
+#[cfg(test)]
+pub struct TestClock {
+    now: std::sync::Mutex<Instant>,
+}
+
+#[cfg(test)]
+impl Default for TestClock {
+    fn default() -> Self {
+        Self {
+            now: std::sync::Mutex::new(Instant::now()),
+        }
+    }
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl Clock for TestClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_paused_clock() {
+        let clock = system();
+        let started = clock.now();
+
+        clock.sleep(Duration::from_secs(3600)).await;
+        assert_eq!(clock.now() - started, Duration::from_secs(3600));
+    }
+
+    #[tokio::test]
+    async fn test_test_clock() {
+        let clock = TestClock::default();
+        let started = clock.now();
+
+        clock.sleep(Duration::from_secs(3600)).await;
+        assert_eq!(clock.now() - started, Duration::from_secs(3600));
+    }
+}
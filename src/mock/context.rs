@@ -1,26 +1,164 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
 
 use bytes::Bytes;
 
-use super::JsonValue;
+use super::{json::JsonPath, JsonValue};
 
+/// The variables and the return value of a handler execution.
+///
+/// Nested step lists run in a child scope (see [`ExecutionContext::scope`]): they can read
+/// the variables of their parent, while the variables they set shadow the parent ones and
+/// are dropped with the scope unless explicitly exported.
 #[derive(Default)]
 pub struct ExecutionContext {
+    invocation_id: String,
     ret: Option<JsonValue>,
+    /// The output built field by field, returned if no return value is set. It belongs to
+    /// the root context, the scopes share it rather than holding a copy.
+    output: Arc<Mutex<Option<JsonValue>>>,
+    finished: bool,
     variables: HashMap<String, Variable>,
+    parent: HashMap<String, Variable>,
+    /// The memory held until the end of the execution.
+    retained: Vec<Vec<u8>>,
+    /// The number of seeds drawn so far, shared by all the scopes of the execution.
+    draws: Arc<AtomicU64>,
 }
 
 impl ExecutionContext {
-    pub fn return_value(&mut self, value: impl Into<JsonValue>) {
-        if self.ret.is_some() {
-            panic!("return_value can only be called once");
+    /// Creates the context of the execution of the given invocation.
+    pub fn new(invocation_id: String) -> Self {
+        Self {
+            invocation_id,
+            ..Default::default()
         }
+    }
+
+    /// Returns the ID of the invocation being executed.
+    pub fn invocation_id(&self) -> &str {
+        &self.invocation_id
+    }
 
+    /// Creates a child scope seeing all the variables of this context.
+    pub fn scope(&self) -> ExecutionContext {
+        let mut parent = self.parent.clone();
+        parent.extend(
+            self.variables
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+
+        ExecutionContext {
+            invocation_id: self.invocation_id.clone(),
+            ret: None,
+            output: Arc::clone(&self.output),
+            finished: false,
+            variables: HashMap::new(),
+            parent,
+            retained: Vec::new(),
+            draws: Arc::clone(&self.draws),
+        }
+    }
+
+    /// Closes the child `scope`, copying the `exports` variables it set into this context.
+    ///
+    /// The return value set in the scope, if any, becomes the return value of this context,
+    /// and so does the end of the execution.
+    pub fn close(&mut self, scope: ExecutionContext, exports: &[String]) {
+        let ExecutionContext {
+            ret,
+            finished,
+            variables,
+            retained,
+            ..
+        } = scope;
+
+        self.retained.extend(retained);
+
+        if let Some(ret) = ret {
+            self.return_value(ret);
+        }
+        self.finished |= finished;
+
+        for (name, value) in variables {
+            if exports.contains(&name) {
+                self.variables.insert(name, value);
+            }
+        }
+    }
+
+    /// Sets the return value of the execution, replacing the one set before if any: the
+    /// last value set is returned.
+    pub fn return_value(&mut self, value: impl Into<JsonValue>) {
         self.ret = Some(value.into());
     }
 
-    pub fn ret(self) -> Option<JsonValue> {
+    /// Sets the element selected by `path` in the output built so far, creating the missing
+    /// objects and lists on the way. The built output is returned if no return value is set.
+    pub fn set_output_field(
+        &mut self,
+        path: &JsonPath,
+        value: impl Into<serde_json::Value>,
+    ) -> Result<(), OutputFieldError> {
+        let mut output = self.output.lock().expect("output lock");
+        let output = output.get_or_insert(JsonValue(serde_json::Value::Null));
+        let field = path
+            .select_or_insert(&mut output.0)
+            .ok_or_else(|| OutputFieldError(path.to_string()))?;
+        *field = value.into();
+
+        Ok(())
+    }
+
+    /// Returns a new seed derived from the `seed` of the invocation: every draw of the
+    /// execution gets a different seed, while a replayed execution draws the same ones.
+    pub fn draw_seed(&self, seed: u64) -> u64 {
+        seed.wrapping_add(self.draws.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Holds the memory until the end of the execution.
+    pub fn retain(&mut self, memory: Vec<u8>) {
+        self.retained.push(memory);
+    }
+
+    /// Ends the execution: the remaining steps of the handler are skipped.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Returns whether the execution was ended by a step.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Takes the return value (or else the built output) out of the context, leaving the
+    /// variables in place.
+    pub fn take_return(&mut self) -> Option<JsonValue> {
+        self.ret
+            .take()
+            .or_else(|| self.output.lock().expect("output lock").take())
+    }
+
+    /// Returns a copy of the return value (or else the output built) so far, if any.
+    pub fn pending_return(&self) -> Option<JsonValue> {
         self.ret
+            .clone()
+            .or_else(|| self.output.lock().expect("output lock").clone())
+    }
+
+    /// Returns all the variables visible in this context, sorted by name.
+    pub fn variables(&self) -> BTreeMap<&str, &Variable> {
+        self.parent
+            .iter()
+            .chain(self.variables.iter())
+            .map(|(name, value)| (name.as_str(), value))
+            .collect()
     }
 
     pub fn set(&mut self, name: &str, value: impl Into<Variable>) {
@@ -31,7 +169,7 @@ impl ExecutionContext {
     where
         T: TryFrom<Variable, Error = VariableError>,
     {
-        let value = self.variables.get(name).cloned()?;
+        let value = self.get_variable(name).cloned()?;
         if let Variable::Null = value {
             return None;
         }
@@ -40,7 +178,7 @@ impl ExecutionContext {
     }
 
     pub fn get_variable(&self, name: &str) -> Option<&Variable> {
-        self.variables.get(name)
+        self.variables.get(name).or_else(|| self.parent.get(name))
     }
 }
 
@@ -50,6 +188,10 @@ pub enum VariableError {
     InvalidType,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("can not set {0} in the output: an element on the way is not an object or a list")]
+pub struct OutputFieldError(String);
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum Variable {
     String(String),
@@ -57,6 +199,8 @@ pub enum Variable {
     Number(f64),
     Boolean(bool),
     Bytes(Bytes),
+    Array(Vec<Variable>),
+    Object(BTreeMap<String, Variable>),
     Null,
 }
 
@@ -112,12 +256,54 @@ impl From<Vec<u8>> for Variable {
     }
 }
 
+impl From<Vec<Variable>> for Variable {
+    fn from(value: Vec<Variable>) -> Self {
+        Self::Array(value)
+    }
+}
+
+impl From<BTreeMap<String, Variable>> for Variable {
+    fn from(value: BTreeMap<String, Variable>) -> Self {
+        Self::Object(value)
+    }
+}
+
 impl From<()> for Variable {
     fn from(_: ()) -> Self {
         Self::Null
     }
 }
 
+impl TryFrom<serde_json::Value> for Variable {
+    type Error = VariableError;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        let variable = match value {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(b) => Self::Boolean(b),
+            serde_json::Value::String(s) => Self::String(s),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Self::Integer(i as isize),
+                None => Self::Number(n.as_f64().ok_or(VariableError::InvalidType)?),
+            },
+            serde_json::Value::Array(items) => Self::Array(
+                items
+                    .into_iter()
+                    .map(Variable::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            serde_json::Value::Object(fields) => Self::Object(
+                fields
+                    .into_iter()
+                    .map(|(name, value)| Ok((name, Variable::try_from(value)?)))
+                    .collect::<Result<_, VariableError>>()?,
+            ),
+        };
+
+        Ok(variable)
+    }
+}
+
 impl TryFrom<Variable> for String {
     type Error = VariableError;
 
@@ -177,3 +363,154 @@ impl TryFrom<Variable> for Bytes {
         Ok(b)
     }
 }
+
+impl TryFrom<Variable> for Vec<Variable> {
+    type Error = VariableError;
+
+    fn try_from(value: Variable) -> Result<Self, Self::Error> {
+        let Variable::Array(items) = value else {
+            return Err(VariableError::InvalidType);
+        };
+
+        Ok(items)
+    }
+}
+
+impl TryFrom<Variable> for BTreeMap<String, Variable> {
+    type Error = VariableError;
+
+    fn try_from(value: Variable) -> Result<Self, Self::Error> {
+        let Variable::Object(fields) = value else {
+            return Err(VariableError::InvalidType);
+        };
+
+        Ok(fields)
+    }
+}
+
+impl From<Variable> for serde_json::Value {
+    /// Converts the variable to the plain JSON value it holds. Bytes are converted to a
+    /// string, replacing the invalid UTF-8 sequences.
+    fn from(value: Variable) -> Self {
+        match value {
+            Variable::String(s) => s.into(),
+            Variable::Integer(i) => i.into(),
+            Variable::Number(n) => n.into(),
+            Variable::Boolean(b) => b.into(),
+            Variable::Bytes(b) => String::from_utf8_lossy(&b).into(),
+            Variable::Array(items) => items.into_iter().map(serde_json::Value::from).collect(),
+            Variable::Object(fields) => fields
+                .into_iter()
+                .map(|(name, value)| (name, value.into()))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+            Variable::Null => serde_json::Value::Null,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_seed() {
+        let exec = ExecutionContext::default();
+        let first = exec.draw_seed(42);
+        // the scopes share the draws of the execution
+        let second = exec.scope().draw_seed(42);
+        assert_ne!(first, second);
+        assert_ne!(second, exec.draw_seed(42));
+
+        // a replay draws the same seeds
+        let replay = ExecutionContext::default();
+        assert_eq!(replay.draw_seed(42), first);
+        assert_eq!(replay.draw_seed(42), second);
+    }
+
+    #[test]
+    fn test_scope() {
+        let mut exec = ExecutionContext::default();
+        exec.set("a", 1);
+        exec.set("b", 1);
+
+        let mut scope = exec.scope();
+        assert_eq!(scope.get::<isize>("a").unwrap().unwrap(), 1);
+
+        scope.set("a", 2);
+        scope.set("b", 2);
+        scope.set("c", 2);
+        scope.retain(vec![0; 16]);
+        assert_eq!(scope.get::<isize>("a").unwrap().unwrap(), 2);
+        assert_eq!(exec.get::<isize>("a").unwrap().unwrap(), 1);
+
+        exec.close(scope, &["b".to_owned()]);
+        assert_eq!(exec.get::<isize>("a").unwrap().unwrap(), 1);
+        assert_eq!(exec.get::<isize>("b").unwrap().unwrap(), 2);
+        assert!(exec.get::<isize>("c").is_none());
+        // the memory retained by the scope is held until the end of the execution
+        assert_eq!(exec.retained.len(), 1);
+    }
+
+    #[test]
+    fn test_output_fields() {
+        let path = |path: &str| path.parse::<JsonPath>().unwrap();
+
+        let mut exec = ExecutionContext::default();
+        assert!(exec.pending_return().is_none());
+        exec.set_output_field(&path("$.id"), 1).unwrap();
+
+        let mut scope = exec.scope();
+        scope.set_output_field(&path("$.tags[0]"), "a").unwrap();
+        assert!(scope.set_output_field(&path("$.id.name"), "x").is_err());
+        // the scopes build the same output, even if they are not closed
+        let mut other = exec.scope();
+        other.set_output_field(&path("$.tags[1]"), "b").unwrap();
+        exec.close(scope, &[]);
+
+        assert_eq!(
+            exec.take_return().unwrap().0,
+            serde_json::json!({"id": 1, "tags": ["a", "b"]})
+        );
+
+        // a return value takes precedence over the built output
+        exec.set_output_field(&path("$.id"), 2).unwrap();
+        exec.return_value(serde_json::json!("done"));
+        assert_eq!(exec.take_return().unwrap().0, serde_json::json!("done"));
+    }
+
+    #[test]
+    fn test_last_return_wins() {
+        let mut exec = ExecutionContext::default();
+        exec.return_value(serde_json::json!(1));
+        exec.return_value(serde_json::json!(2));
+
+        // a scope returning again, e.g. every iteration of a loop, replaces the value too
+        let mut scope = exec.scope();
+        scope.return_value(serde_json::json!(3));
+        scope.return_value(serde_json::json!(4));
+        exec.close(scope, &[]);
+
+        assert_eq!(exec.take_return().unwrap().0, serde_json::json!(4));
+    }
+
+    #[test]
+    fn test_variable_json() {
+        let value = serde_json::json!({"id": 1, "tags": ["a", true, null], "price": 2.5});
+        let variable = Variable::try_from(value.clone()).unwrap();
+
+        let fields: BTreeMap<String, Variable> = variable.clone().try_into().unwrap();
+        let tags: Vec<Variable> = fields["tags"].clone().try_into().unwrap();
+        assert_eq!(tags.len(), 3);
+        assert!(matches!(fields["id"], Variable::Integer(1)));
+        assert!(String::try_from(fields["tags"].clone()).is_err());
+
+        assert_eq!(serde_json::Value::from(variable.clone()), value);
+
+        // the journaled form keeps the kind of the nested variables
+        let mut journaled = restate_sdk::serde::Serialize::serialize(&variable).unwrap();
+        let variable: Variable =
+            restate_sdk::serde::Deserialize::deserialize(&mut journaled).unwrap();
+        assert_eq!(serde_json::Value::from(variable), value);
+    }
+}
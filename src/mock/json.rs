@@ -0,0 +1,239 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde_json::Value;
+
+/// A single segment of a [`JsonPath`].
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+}
+
+/// A minimal JSONPath used to select a single element of a JSON document.
+///
+/// Supports the root `$`, fields (`.name` or `['name']`) and array indices (`[0]`),
+/// for example `$.items[0].id`.
+#[derive(Debug, Clone, PartialEq, serde_with::DeserializeFromStr)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid JSONPath at position {position}: {reason}")]
+pub struct JsonPathError {
+    position: usize,
+    reason: &'static str,
+}
+
+impl FromStr for JsonPath {
+    type Err = JsonPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |position, reason| JsonPathError { position, reason };
+
+        let rest = s
+            .strip_prefix('$')
+            .ok_or_else(|| error(0, "must start with `$`"))?;
+
+        let mut segments = Vec::new();
+        let mut chars = rest.char_indices().peekable();
+
+        while let Some((pos, c)) = chars.next() {
+            let pos = pos + 1;
+            match c {
+                '.' => {
+                    let mut name = String::new();
+                    while let Some((_, c)) = chars.next_if(|(_, c)| !matches!(c, '.' | '[')) {
+                        name.push(c);
+                    }
+
+                    if name.is_empty() {
+                        return Err(error(pos, "expected a field name"));
+                    }
+                    segments.push(Segment::Field(name));
+                }
+                '[' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, ']')) => break,
+                            Some((_, c)) => inner.push(c),
+                            None => return Err(error(pos, "missing `]`")),
+                        }
+                    }
+
+                    let field = inner
+                        .strip_prefix('\'')
+                        .and_then(|inner| inner.strip_suffix('\''));
+
+                    match field {
+                        Some(field) => segments.push(Segment::Field(field.to_owned())),
+                        None => segments.push(Segment::Index(
+                            inner
+                                .parse()
+                                .map_err(|_| error(pos, "expected an index or a quoted field"))?,
+                        )),
+                    }
+                }
+                _ => return Err(error(pos, "expected `.` or `[`")),
+            }
+        }
+
+        Ok(Self { segments })
+    }
+}
+
+impl Display for JsonPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "$")?;
+        for segment in &self.segments {
+            match segment {
+                Segment::Field(name) => write!(f, ".{name}")?,
+                Segment::Index(idx) => write!(f, "[{idx}]")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl JsonPath {
+    /// Returns the element selected by the path, or `None` if it does not exist.
+    pub fn select<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.segments
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                Segment::Field(name) => value.get(name),
+                Segment::Index(idx) => value.get(idx),
+            })
+    }
+
+    /// Returns the element selected by the path for writing, creating the missing elements
+    /// on the way: `null` elements become objects (or lists) and lists are extended with
+    /// `null` items. Returns `None` if an element on the way is of another kind.
+    pub fn select_or_insert<'a>(&self, value: &'a mut Value) -> Option<&'a mut Value> {
+        self.segments
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                Segment::Field(name) => {
+                    if value.is_null() {
+                        *value = Value::Object(Default::default());
+                    }
+                    let fields = value.as_object_mut()?;
+                    Some(fields.entry(name.clone()).or_insert(Value::Null))
+                }
+                Segment::Index(idx) => {
+                    if value.is_null() {
+                        *value = Value::Array(Vec::new());
+                    }
+                    let items = value.as_array_mut()?;
+                    if items.len() <= *idx {
+                        items.resize(idx + 1, Value::Null);
+                    }
+                    Some(&mut items[*idx])
+                }
+            })
+    }
+}
+
+/// Compares `actual` against `expected`, returning one line per difference
+/// prefixed with the path of the differing element. An empty result means both are equal.
+pub fn diff(path: &str, expected: &Value, actual: &Value) -> Vec<String> {
+    let mut differences = Vec::new();
+    diff_into(path, expected, actual, &mut differences);
+    differences
+}
+
+fn diff_into(path: &str, expected: &Value, actual: &Value, differences: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, expected) in expected {
+                match actual.get(key) {
+                    Some(actual) => {
+                        diff_into(&format!("{path}.{key}"), expected, actual, differences)
+                    }
+                    None => differences.push(format!("{path}.{key}: missing, expected {expected}")),
+                }
+            }
+
+            for (key, actual) in actual {
+                if !expected.contains_key(key) {
+                    differences.push(format!("{path}.{key}: unexpected {actual}"));
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => {
+            for (idx, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                diff_into(&format!("{path}[{idx}]"), expected, actual, differences);
+            }
+        }
+        (expected, actual) if expected != actual => {
+            differences.push(format!("{path}: expected {expected}, got {actual}"));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_json_path() {
+        let value = json!({"items": [{"id": 1}, {"id": 2, "odd key": true}]});
+
+        let path: JsonPath = "$.items[1].id".parse().unwrap();
+        assert_eq!(path.to_string(), "$.items[1].id");
+        assert_eq!(path.select(&value), Some(&json!(2)));
+
+        let path: JsonPath = "$['items'][1]['odd key']".parse().unwrap();
+        assert_eq!(path.select(&value), Some(&json!(true)));
+
+        let path: JsonPath = "$".parse().unwrap();
+        assert_eq!(path.select(&value), Some(&value));
+
+        let path: JsonPath = "$.items[5]".parse().unwrap();
+        assert_eq!(path.select(&value), None);
+
+        assert!("items".parse::<JsonPath>().is_err());
+        assert!("$.items[".parse::<JsonPath>().is_err());
+        assert!("$..items".parse::<JsonPath>().is_err());
+    }
+
+    #[test]
+    fn test_json_path_insert() {
+        let mut value = Value::Null;
+
+        let path: JsonPath = "$.order.items[1].id".parse().unwrap();
+        *path.select_or_insert(&mut value).unwrap() = json!(7);
+        let path: JsonPath = "$.order.id".parse().unwrap();
+        *path.select_or_insert(&mut value).unwrap() = json!("o-1");
+        assert_eq!(
+            value,
+            json!({"order": {"items": [null, {"id": 7}], "id": "o-1"}})
+        );
+
+        let path: JsonPath = "$.order.id.name".parse().unwrap();
+        assert!(path.select_or_insert(&mut value).is_none());
+        let path: JsonPath = "$.order[0]".parse().unwrap();
+        assert!(path.select_or_insert(&mut value).is_none());
+    }
+
+    #[test]
+    fn test_diff() {
+        let expected = json!({"a": 1, "b": [1, 2], "c": "x"});
+
+        assert!(diff("$", &expected, &expected).is_empty());
+        assert_eq!(
+            diff("$", &expected, &json!({"a": 2, "b": [1, 3], "d": null})),
+            vec![
+                "$.a: expected 1, got 2",
+                "$.b[1]: expected 2, got 3",
+                "$.c: missing, expected \"x\"",
+                "$.d: unexpected null",
+            ]
+        );
+    }
+}
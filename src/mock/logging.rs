@@ -0,0 +1,98 @@
+use regex::Regex;
+use serde_json::Value;
+
+/// The text replacing the values of the redacted fields.
+const REDACTED: &str = "[REDACTED]";
+
+/// Renders the handler inputs and outputs for the logs, redacting the sensitive fields
+/// and truncating the long payloads.
+#[derive(Debug, Default)]
+pub struct PayloadLogging {
+    payloads: bool,
+    max_length: usize,
+    redact: Vec<Regex>,
+}
+
+impl PayloadLogging {
+    /// Creates the payload logging, the values of the fields whose name matches one of the
+    /// `redact` patterns are replaced wherever payloads are reported.
+    pub fn new(payloads: bool, max_length: usize, redact: Vec<Regex>) -> Self {
+        Self {
+            payloads,
+            max_length,
+            redact,
+        }
+    }
+
+    /// Returns `true` if the inputs and outputs are logged.
+    pub fn payloads(&self) -> bool {
+        self.payloads
+    }
+
+    /// Returns a copy of the payload with the values of the sensitive fields replaced.
+    pub fn redact(&self, payload: &Value) -> Value {
+        let mut payload = payload.clone();
+        self.redact_fields(&mut payload);
+        payload
+    }
+
+    /// Returns the JSON text of the payload as logged.
+    pub fn render(&self, payload: &Value) -> String {
+        let mut text = self.redact(payload).to_string();
+        if text.len() > self.max_length {
+            let mut end = self.max_length;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            let total = text.len();
+            text.truncate(end);
+            text.push_str(&format!("... ({total} bytes)"));
+        }
+
+        text
+    }
+
+    fn redact_fields(&self, value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                for (name, field) in fields {
+                    if self.redact.iter().any(|pattern| pattern.is_match(name)) {
+                        *field = Value::String(REDACTED.to_owned());
+                    } else {
+                        self.redact_fields(field);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_fields(item)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let logging =
+            PayloadLogging::new(true, 64, vec![Regex::new("(?i)^password$|token").unwrap()]);
+
+        assert_eq!(
+            logging.render(&json!({
+                "user": "mock",
+                "Password": "secret",
+                "auth": [{"access_token": "abc", "scope": "all"}],
+            })),
+            r#"{"Password":"[REDACTED]","auth":[{"access_token":"[REDACTED]","s... (92 bytes)"#
+        );
+
+        assert_eq!(
+            logging.render(&json!({"passwords": 1})),
+            r#"{"passwords":1}"#
+        );
+    }
+}
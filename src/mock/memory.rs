@@ -0,0 +1,25 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use super::context::Variable;
+
+/// The in-memory key-value store of the process, shared by all the services of the endpoint.
+///
+/// Unlike the Restate state, the values are neither durable nor scoped to a key, and they
+/// are lost when the mock service restarts.
+static MEMORY: LazyLock<Mutex<HashMap<String, Variable>>> = LazyLock::new(Default::default);
+
+/// Stores the value under the key, replacing the previous one.
+pub fn set(key: &str, value: Variable) {
+    MEMORY
+        .lock()
+        .expect("memory lock")
+        .insert(key.to_owned(), value);
+}
+
+/// Returns the value stored under the key, if any.
+pub fn get(key: &str) -> Option<Variable> {
+    MEMORY.lock().expect("memory lock").get(key).cloned()
+}
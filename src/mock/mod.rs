@@ -1,20 +1,54 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
+pub use barrier::reset as reset_barriers;
 use bytes::Bytes;
-use context::ExecutionContext;
+use context::{ExecutionContext, Variable};
+pub use logging::PayloadLogging;
 use restate_sdk::{
     discovery::{self, Handler, HandlerName, HandlerType, ServiceName, ServiceType},
     endpoint::Builder,
-    errors::HandlerError,
+    errors::{HandlerError, TerminalError},
     prelude::WorkflowContext,
     serde::{Deserialize, Serialize},
     service::{Discoverable, Service, ServiceBoxFuture},
 };
-pub use steps::STEPS;
-use tracing::debug;
+pub use schema::{ParamType, Schema};
+pub use steps::{
+    create_step, DETECT_DUPLICATES, GET_DUPLICATES, GET_HISTORY, MIRROR, NOT_IMPLEMENTED,
+    RECORD_HISTORY, REQUIRE_HEADER, VERIFY_ADD, VERIFY_TOTALS,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    config::HandlerContent,
+    stats::{Invocation, Outcome, STATS},
+};
 
+mod barrier;
+mod clock;
 mod context;
+mod json;
+mod logging;
+mod memory;
+mod registry;
+mod schema;
+mod size;
 mod steps;
+mod template;
+
+/// The handler name used in the configuration to define a catch-all handler.
+pub const CATCH_ALL_HANDLER: &str = "*";
+
+/// Returned by [`Step::inputs`] for steps reading all the variables.
+pub const ALL_VARIABLES: &str = "*";
+
+/// The variable holding the input of the handlers with a `raw` content.
+pub const RAW_INPUT_VARIABLE: &str = "input";
 
 tokio::task_local! {
     static DISCOVERY_METADATA: discovery::Service;
@@ -44,12 +78,76 @@ impl Serialize for JsonValue {
     }
 }
 
+impl JsonValue {
+    /// Returns the size of the JSON encoded value, in bytes.
+    pub fn size(&self) -> usize {
+        serde_json::to_vec(&self.0).map_or(0, |bytes| bytes.len())
+    }
+}
+
 impl From<serde_json::Value> for JsonValue {
     fn from(value: serde_json::Value) -> Self {
         JsonValue(value)
     }
 }
 
+/// The response of a handler, encoded according to the content of the handler.
+enum Response {
+    Json(JsonValue),
+    Raw(Bytes),
+}
+
+impl Response {
+    fn new(content: HandlerContent, output: JsonValue) -> Self {
+        match content {
+            HandlerContent::Json => Response::Json(output),
+            HandlerContent::Raw => Response::Raw(raw_output(output)),
+        }
+    }
+
+    /// Returns the size of the encoded response, in bytes.
+    fn size(&self) -> usize {
+        match self {
+            Response::Json(output) => output.size(),
+            Response::Raw(output) => output.len(),
+        }
+    }
+
+    /// Returns the text of the response as logged.
+    fn render(&self, logging: &PayloadLogging) -> String {
+        match self {
+            Response::Json(output) => logging.render(&output.0),
+            Response::Raw(output) => format!("{} bytes", output.len()),
+        }
+    }
+}
+
+impl Serialize for Response {
+    type Error = serde_json::Error;
+
+    fn serialize(&self) -> Result<Bytes, Self::Error> {
+        match self {
+            Response::Json(output) => output.serialize(),
+            Response::Raw(output) => Ok(output.clone()),
+        }
+    }
+}
+
+/// Converts the value returned by the steps of a raw handler to bytes: strings and returned
+/// variables are written as is (bytes, or the text of the other values), `null` as an empty
+/// body and any other value as its JSON text.
+fn raw_output(output: JsonValue) -> Bytes {
+    match output.0 {
+        serde_json::Value::Null => Bytes::new(),
+        serde_json::Value::String(text) => Bytes::from(text),
+        value => match serde_json::from_value::<Variable>(value.clone()) {
+            Ok(Variable::Bytes(bytes)) => bytes,
+            Ok(variable) => Bytes::from(template::text_of(&variable)),
+            Err(_) => Bytes::from(value.to_string()),
+        },
+    }
+}
+
 /// Represents a configurable mock service that can handle requests based on predefined steps.
 ///
 /// A `MockService` is defined by its name, type (e.g., `SERVICE` or `VIRTUAL_OBJECT`), and a collection of handlers.
@@ -58,6 +156,7 @@ pub struct MockService {
     name: ServiceName,
     ty: ServiceType,
     handlers: HashMap<String, MockHandler>,
+    catch_all: Option<MockHandler>,
 }
 
 impl MockService {
@@ -67,6 +166,7 @@ impl MockService {
             name,
             ty,
             handlers: HashMap::new(),
+            catch_all: None,
         }
     }
 
@@ -80,6 +180,13 @@ impl MockService {
         self.handlers.insert(name.to_string(), handler);
     }
 
+    /// Sets the handler that runs for any handler name not explicitly added to the service.
+    ///
+    /// The catch-all handler is not part of the service discovery.
+    pub fn set_catch_all_handler(&mut self, handler: MockHandler) {
+        self.catch_all = Some(handler);
+    }
+
     /// Generates the service discovery information for this mock service.
     fn service_discovery(&self) -> discovery::Service {
         discovery::Service {
@@ -101,11 +208,27 @@ impl MockService {
     /// Binds this mock service to the Restate endpoint builder.
     ///
     /// This method sets up the service with Restate, making its handlers discoverable and callable.
-    pub async fn bind(self, endpoint: Builder) -> Builder {
+    /// The global `hooks` are executed around every handler of the service, and the inputs and
+    /// outputs are reported according to `logging`.
+    pub async fn bind(
+        self,
+        endpoint: Builder,
+        hooks: Arc<Hooks>,
+        logging: Arc<PayloadLogging>,
+    ) -> Builder {
         let discovery = self.service_discovery();
 
+        for (name, handler) in &self.handlers {
+            STATS.register(&self.name, name, handler.slo);
+        }
+        if let Some(handler) = &self.catch_all {
+            STATS.register(&self.name, CATCH_ALL_HANDLER, handler.slo);
+        }
+
         let wrapper = MockServiceWrapper {
             inner: Arc::new(self),
+            hooks,
+            logging,
         };
 
         DISCOVERY_METADATA
@@ -119,6 +242,8 @@ impl MockService {
 #[derive(Clone)]
 struct MockServiceWrapper {
     inner: Arc<MockService>,
+    hooks: Arc<Hooks>,
+    logging: Arc<PayloadLogging>,
 }
 
 impl Service for MockServiceWrapper {
@@ -127,7 +252,17 @@ impl Service for MockServiceWrapper {
     fn handle(&self, ctx: restate_sdk::endpoint::ContextInternal) -> Self::Future {
         let service_clone = self.clone();
         Box::pin(async move {
-            let Some(handler) = service_clone.inner.handlers.get(ctx.handler_name()) else {
+            let (handler_name, handler) = match service_clone
+                .inner
+                .handlers
+                .get_key_value(ctx.handler_name())
+            {
+                Some((name, handler)) => (name.as_str(), Some(handler)),
+                None => (CATCH_ALL_HANDLER, service_clone.inner.catch_all.as_ref()),
+            };
+
+            let Some(handler) = handler else {
+                STATS.record_unknown_handler(ctx.service_name(), ctx.handler_name());
                 return Err(::restate_sdk::endpoint::Error::unknown_handler(
                     ctx.service_name(),
                     ctx.handler_name(),
@@ -140,11 +275,95 @@ impl Service for MockServiceWrapper {
                 ctx.handler_name()
             );
 
-            let (input, metadata) = ctx.input::<JsonValue>().await;
+            let (input, raw_input, metadata) = match handler.content {
+                HandlerContent::Json => {
+                    let (input, metadata) = ctx.input::<JsonValue>().await;
+                    let input = match &handler.default_input {
+                        Some(default) if input.0.is_null() => default.clone(),
+                        _ => input,
+                    };
+                    (input, None, metadata)
+                }
+                HandlerContent::Raw => {
+                    let (input, metadata) = ctx.input::<Bytes>().await;
+                    (JsonValue(serde_json::Value::Null), Some(input), metadata)
+                }
+            };
+            let input_size = raw_input.as_ref().map_or_else(|| input.size(), Bytes::len);
+            let logging = &service_clone.logging;
+
+            if logging.payloads() {
+                let rendered = match &raw_input {
+                    Some(raw) => format!("{} bytes", raw.len()),
+                    None => logging.render(&input.0),
+                };
+                tracing::info!(
+                    "Input of {}/{}: {}",
+                    ctx.service_name(),
+                    ctx.handler_name(),
+                    rendered
+                );
+            }
+
+            // the redacted copy of the input is only made if the invocation is kept
+            let invocation = STATS.keeps_recent().then(|| Invocation {
+                started_at: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+                invocation_id: metadata.invocation_id.clone(),
+                service: ctx.service_name().to_owned(),
+                handler: ctx.handler_name().to_owned(),
+                key: Some(metadata.key.clone()).filter(|key| !key.is_empty()),
+                input: logging.redact(&input.0),
+                duration_ms: 0,
+                outcome: "",
+            });
+
+            let mut exec_ctx = ExecutionContext::new(metadata.invocation_id.clone());
+            if let Some(raw) = raw_input {
+                exec_ctx.set(RAW_INPUT_VARIABLE, raw);
+            }
+            let workflow_ctx: WorkflowContext = (&ctx, metadata).into();
+            let clock = clock::system();
+            let started = clock.now();
+            let res = handler
+                .run(&workflow_ctx, &mut exec_ctx, &service_clone.hooks, &input)
+                .await
+                .map(|output| Response::new(handler.content, output));
+            let elapsed = clock.now() - started;
+            let outcome = match &res {
+                Ok(output) => Outcome::Success(output.size()),
+                Err(err) if err.is_terminal() => Outcome::TerminalError,
+                Err(_) => Outcome::RetryableError,
+            };
+            STATS.record(
+                ctx.service_name(),
+                handler_name,
+                elapsed,
+                input_size,
+                outcome,
+            );
 
-            let res = handler.run((&ctx, metadata).into(), &input).await;
+            if let Some(mut invocation) = invocation {
+                invocation.duration_ms = elapsed.as_millis() as u64;
+                invocation.outcome = outcome.as_str();
+                STATS.record_invocation(invocation);
+            }
 
-            ctx.handle_handler_result(res);
+            if let (true, Ok(output)) = (logging.payloads(), &res) {
+                tracing::info!(
+                    "Output of {}/{}: {}",
+                    ctx.service_name(),
+                    ctx.handler_name(),
+                    output.render(logging)
+                );
+            }
+
+            let succeeded = res.is_ok();
+            ctx.handle_handler_result(res.map_err(HandlerError::from));
+            if succeeded {
+                handler
+                    .run_after_return(&workflow_ctx, &mut exec_ctx, &input)
+                    .await;
+            }
             ctx.end();
             Ok(())
         })
@@ -163,12 +382,103 @@ pub enum StepError {
     /// Error indicating that a step is not valid for the given service type.
     #[error("Invalid service type: {}", .0.to_string())]
     InvalidServiceType(ServiceType),
+    /// Error indicating that a step is not valid for the given handler type.
+    #[error("Invalid handler type: {}", .0.to_string())]
+    InvalidHandlerType(HandlerType),
     /// Error indicating invalid parameters were provided for a step.
     #[error("Invalid step parameters: {0}")]
     InvalidStepParameters(#[from] serde_yaml::Error),
+    /// Error indicating that a step parameter does not match the step's schema.
+    #[error("Invalid parameter `{field}`: {reason}")]
+    InvalidParameter { field: String, reason: String },
+    /// Error indicating that a resource referenced by a step parameter (e.g. a fixture
+    /// file) could not be loaded.
+    #[error("Failed to load `{field}`: {source:#}")]
+    Load {
+        field: String,
+        source: anyhow::Error,
+    },
+    /// Error indicating an invalid `flaky` configuration of a step.
+    #[error("Invalid flaky configuration: {0}")]
+    InvalidFlaky(String),
     /// Error indicating that a step type is not known.
     #[error("Unknown step type: {0}")]
     UnknownStepType(String),
+    /// Error of a step nested in the parameters of the step (e.g. in the `steps` of a
+    /// loop), at the given path relative to the parameters.
+    #[error("{source}")]
+    Nested {
+        path: String,
+        source: Box<StepError>,
+    },
+}
+
+/// The failure of a step.
+///
+/// The SDK keeps the kind of a `HandlerError` private, so steps report their failures in
+/// this type instead, which tells the terminal errors apart from the retryable ones (e.g.
+/// for the `retry` step). It is converted to a `HandlerError` when the handler ends.
+#[derive(Debug)]
+pub enum StepFailure {
+    /// A terminal error, failing the invocation without retrying it.
+    Terminal(TerminalError),
+    /// Any other error, the invocation is retried.
+    Retryable(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl StepFailure {
+    /// Returns `true` if the failure is terminal.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, StepFailure::Terminal(_))
+    }
+}
+
+impl fmt::Display for StepFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepFailure::Terminal(err) => write!(f, "{err}"),
+            StepFailure::Retryable(err) => write!(f, "Retryable error: {err}"),
+        }
+    }
+}
+
+impl From<TerminalError> for StepFailure {
+    fn from(err: TerminalError) -> Self {
+        StepFailure::Terminal(err)
+    }
+}
+
+impl From<String> for StepFailure {
+    fn from(message: String) -> Self {
+        StepFailure::Retryable(message.into())
+    }
+}
+
+impl From<&str> for StepFailure {
+    fn from(message: &str) -> Self {
+        StepFailure::Retryable(message.into())
+    }
+}
+
+impl From<serde_json::Error> for StepFailure {
+    fn from(err: serde_json::Error) -> Self {
+        StepFailure::Retryable(err.into())
+    }
+}
+
+impl From<anyhow::Error> for StepFailure {
+    fn from(err: anyhow::Error) -> Self {
+        StepFailure::Retryable(err.into())
+    }
+}
+
+impl From<StepFailure> for HandlerError {
+    fn from(failure: StepFailure) -> Self {
+        match failure {
+            StepFailure::Terminal(err) => err.into(),
+            StepFailure::Retryable(err) => err.into(),
+        }
+    }
 }
 
 /// Trait defining the contract for a step in a mock handler's execution flow.
@@ -176,16 +486,67 @@ pub enum StepError {
 /// Each step must be able to validate itself against a service type and execute its logic.
 #[async_trait::async_trait]
 pub trait Step: Send + Sync + 'static {
-    /// Validates if the step is appropriate for the given `ServiceType`.
+    /// Validates if the step is appropriate for the given `ServiceType` and `HandlerType`.
     ///
     /// # Arguments
     ///
     /// * `service_type` - The type of the service this step belongs to.
+    /// * `handler_type` - The effective type of the handler this step belongs to,
+    ///   `None` for handlers of a `SERVICE`.
     ///
     /// # Returns
     ///
     /// `Ok(())` if the step is valid, otherwise a `StepError`.
-    fn validate(&self, service_type: ServiceType) -> Result<(), StepError>;
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError>;
+
+    /// Returns the names of the variables the step stores its results in.
+    ///
+    /// Used to detect steps of the same handler overwriting each other's output.
+    fn outputs(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// Returns the names of the variables the step reads, or [`ALL_VARIABLES`] if the step
+    /// reads all of them.
+    ///
+    /// Used by the linter to detect variables that are never used.
+    fn inputs(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// Returns the handlers the step invokes.
+    fn targets(&self) -> Vec<Target<'_>> {
+        Vec::new()
+    }
+
+    /// Returns `true` if the step always ends the handler execution, so the steps
+    /// following it never run.
+    fn ends_handler(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if the step neither waits for other handlers nor changes the state,
+    /// so it can run after the response of the handler is finalized.
+    fn fire_and_forget(&self) -> bool {
+        false
+    }
+
+    /// Returns `false` if the step can fail differently when the execution is replayed
+    /// (e.g. randomly or on a timeout), since its failures are not journaled.
+    ///
+    /// Used to reject such steps within a `retry` step, whose attempts must replay the same way.
+    fn deterministic(&self) -> bool {
+        true
+    }
+
+    /// Returns the nested step lists of the step.
+    fn branches(&self) -> Vec<Branch<'_>> {
+        Vec::new()
+    }
 
     /// Executes the step's logic.
     ///
@@ -197,13 +558,38 @@ pub trait Step: Send + Sync + 'static {
     ///
     /// # Returns
     ///
-    /// `Ok(())` if execution is successful, otherwise a `HandlerError`.
+    /// `Ok(())` if execution is successful, otherwise a `StepFailure`.
     async fn run(
         &self,
         ctx: &WorkflowContext<'_>,
         step: &mut ExecutionContext,
         input: &JsonValue,
-    ) -> Result<(), HandlerError>;
+    ) -> Result<(), StepFailure>;
+}
+
+/// How a step invokes a handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InvocationKind {
+    /// The step waits for the result of the handler.
+    Call,
+    /// The step does not wait for the handler (fire-and-forget).
+    Send,
+}
+
+/// A handler invoked by a step.
+pub struct Target<'a> {
+    pub service: &'a str,
+    pub handler: &'a str,
+    pub kind: InvocationKind,
+}
+
+/// A list of steps nested in a step, e.g. the body of a loop.
+pub struct Branch<'a> {
+    /// The path of the list relative to the step, e.g. `params.steps`.
+    pub path: String,
+    pub steps: &'a [BoxStep],
+    /// Whether the steps can run at all, `false` for example for a loop with a count of 0.
+    pub reachable: bool,
 }
 
 /// A type alias for a boxed `Step` trait object.
@@ -218,6 +604,28 @@ where
     }
 }
 
+/// Steps executed around every handler of every `MockService`.
+///
+/// The `before` steps run before the handler steps and the `after` steps run after them,
+/// all sharing the same `ExecutionContext`.
+#[derive(Default)]
+pub struct Hooks {
+    /// Steps executed before the handler steps, along with their configuration path.
+    pub before: Vec<(String, BoxStep)>,
+    /// Steps executed after the handler steps, along with their configuration path.
+    pub after: Vec<(String, BoxStep)>,
+}
+
+impl Hooks {
+    /// Returns all the hook steps along with their configuration path.
+    pub fn steps(&self) -> impl Iterator<Item = (String, &BoxStep)> {
+        self.before
+            .iter()
+            .chain(self.after.iter())
+            .map(|(path, step)| (path.clone(), step))
+    }
+}
+
 /// Represents a handler within a `MockService`.
 ///
 /// A `MockHandler` contains a sequence of `Step`s that are executed in order when the handler is called.
@@ -227,6 +635,14 @@ pub struct MockHandler {
     pub steps: Vec<BoxStep>,
     /// The type of the handler (e.g., workflow, unary). If `None`, Restate's default is used.
     pub ty: Option<HandlerType>,
+    /// The latency SLO of the handler, reported in the stats.
+    pub slo: Option<Duration>,
+    /// The input of the invocations without a body (or with a `null` one).
+    pub default_input: Option<JsonValue>,
+    /// How the input and the output of the handler are encoded.
+    pub content: HandlerContent,
+    /// The fire-and-forget steps executed after the response of the handler is finalized.
+    pub after_return: Vec<BoxStep>,
 }
 
 impl MockHandler {
@@ -235,32 +651,80 @@ impl MockHandler {
     /// # Arguments
     ///
     /// * `ctx` - The `WorkflowContext` for the current invocation.
+    /// * `exec_ctx` - The `ExecutionContext` of the invocation, kept for the `after_return` steps.
+    /// * `hooks` - The global `Hooks` executed around the handler steps.
     /// * `input` - The input `JsonValue` passed to the handler.
     ///
     /// # Returns
     ///
     /// A `Result` containing the `JsonValue` returned by the handler's execution (often from a `ReturnStep`),
-    /// or a `HandlerError` if any step fails.
+    /// or a `StepFailure` if any step fails.
     async fn run(
         &self,
-        ctx: WorkflowContext<'_>,
+        ctx: &WorkflowContext<'_>,
+        exec_ctx: &mut ExecutionContext,
+        hooks: &Hooks,
         input: &JsonValue,
-    ) -> Result<JsonValue, HandlerError> {
-        let mut exec_ctx = ExecutionContext::default();
-        for step in self.steps.iter() {
-            step.run(&ctx, &mut exec_ctx, input).await?;
+    ) -> Result<JsonValue, StepFailure> {
+        let steps = hooks
+            .before
+            .iter()
+            .map(|(_, step)| step)
+            .chain(self.steps.iter());
+
+        for step in steps {
+            step.run(ctx, exec_ctx, input).await?;
+
+            if exec_ctx.is_finished() {
+                break;
+            }
+        }
+
+        // the `after` hooks run even if the handler ended early
+        for (_, step) in &hooks.after {
+            step.run(ctx, exec_ctx, input).await?;
         }
 
-        Ok(exec_ctx.ret().unwrap_or(JsonValue(serde_json::Value::Null)))
+        Ok(exec_ctx
+            .take_return()
+            .unwrap_or(JsonValue(serde_json::Value::Null)))
+    }
+
+    /// Runs the `after_return` steps, once the response of a successful execution is
+    /// written. The response can not change anymore, so a failing step is only logged
+    /// and skips the remaining ones.
+    async fn run_after_return(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec_ctx: &mut ExecutionContext,
+        input: &JsonValue,
+    ) {
+        for step in &self.after_return {
+            if let Err(err) = step.run(ctx, exec_ctx, input).await {
+                warn!("Step after the return failed: {err}");
+                break;
+            }
+        }
     }
 }
 
 /// Trait for a factory that can create instances of a specific `Step`.
 ///
 /// Each step type (e.g., `Echo`, `Sleep`) will have an associated factory.
+#[async_trait::async_trait]
 pub trait StepFactory: Send + Sync + 'static {
+    /// Returns the schema of the parameters accepted by the step.
+    ///
+    /// The `params` of a step configuration are validated against this schema
+    /// before [`StepFactory::create`] is called.
+    fn schema(&self) -> Schema;
+
     /// Creates a new `BoxStep` instance from YAML configuration parameters.
     ///
+    /// Steps are created once, when the configuration is loaded: the factory is the place
+    /// for the expensive preparations (reading fixture files, compiling patterns) that would
+    /// otherwise slow down every invocation.
+    ///
     /// # Arguments
     ///
     /// * `params` - The YAML value containing the parameters for configuring the step.
@@ -268,6 +732,46 @@ pub trait StepFactory: Send + Sync + 'static {
     /// # Returns
     ///
     /// A `Result` containing the created `BoxStep` or a `StepError` if creation fails
-    /// (e.g., due to invalid parameters).
-    fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError>;
+    /// (e.g., due to invalid parameters or a missing fixture file).
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_terminal() {
+        assert!(StepFailure::from(TerminalError::new("boom")).is_terminal());
+        assert!(!StepFailure::from("boom".to_owned()).is_terminal());
+        // the kind does not depend on the message
+        assert!(!StepFailure::from("Terminal error [500]: boom").is_terminal());
+        assert!(StepFailure::from(TerminalError::new("Retryable error: boom")).is_terminal());
+
+        assert_eq!(
+            StepFailure::from(TerminalError::new("boom")).to_string(),
+            "Terminal error [500]: boom"
+        );
+        assert_eq!(
+            StepFailure::from("boom").to_string(),
+            "Retryable error: boom"
+        );
+    }
+
+    #[test]
+    fn test_raw_output() {
+        let raw = |value: serde_json::Value| raw_output(JsonValue(value));
+
+        assert!(raw(serde_json::Value::Null).is_empty());
+        assert_eq!(raw(serde_json::json!("text")), "text");
+        assert_eq!(raw(serde_json::json!({"a": 1})), r#"{"a":1}"#);
+
+        let returned = |variable: Variable| raw(serde_json::to_value(variable).unwrap());
+        assert_eq!(
+            returned(Variable::Bytes(Bytes::from_static(b"\x00\x01"))),
+            b"\x00\x01"[..]
+        );
+        assert_eq!(returned(Variable::String("text".to_owned())), "text");
+        assert_eq!(returned(Variable::Integer(42)), "42");
+    }
 }
@@ -0,0 +1,165 @@
+use std::collections::{HashMap, HashSet};
+
+use super::StepFactory;
+
+/// The namespace of the built-in steps.
+pub const CORE: &str = "core";
+
+/// Defines errors that can occur while registering step types.
+#[derive(Debug, thiserror::Error)]
+pub enum RegistryError {
+    /// Error indicating that a step type is registered more than once.
+    #[error("Step type {0} is already registered")]
+    Collision(String),
+    /// Error indicating an invalid namespace or step name.
+    #[error(
+        "Invalid step type {0}: namespaces and names must be non-empty and can not contain `.`"
+    )]
+    InvalidName(String),
+}
+
+/// A set of step types provided together, registered under a common namespace.
+pub struct StepPack {
+    pub namespace: &'static str,
+    pub steps: Vec<(&'static str, Box<dyn StepFactory>)>,
+}
+
+/// The registry of all the step types, keyed by their namespaced type `<namespace>.<name>`.
+///
+/// Step types are referenced by their namespaced type, e.g. `acme.billing-check`. The types
+/// without a namespace resolve to the [`CORE`] namespace, so steps of other packs can never
+/// shadow the built-in steps.
+#[derive(Default)]
+pub struct StepRegistry {
+    factories: HashMap<String, Box<dyn StepFactory>>,
+}
+
+impl StepRegistry {
+    /// Registers all the steps of the pack, failing if any of them is already registered.
+    ///
+    /// All the names are checked before registering any step, so a failing pack leaves the
+    /// registry unchanged.
+    pub fn register(&mut self, pack: StepPack) -> Result<(), RegistryError> {
+        let mut types = HashSet::new();
+        for (name, _) in &pack.steps {
+            let ty = format!("{}.{name}", pack.namespace);
+
+            if [pack.namespace, name]
+                .iter()
+                .any(|part| part.is_empty() || part.contains('.'))
+            {
+                return Err(RegistryError::InvalidName(ty));
+            }
+
+            if self.factories.contains_key(&ty) || !types.insert(ty.clone()) {
+                return Err(RegistryError::Collision(ty));
+            }
+        }
+
+        for (name, factory) in pack.steps {
+            self.factories
+                .insert(format!("{}.{name}", pack.namespace), factory);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the factory of the step type, along with its canonical name: the name alone
+    /// for the built-in steps, the namespaced type otherwise.
+    pub fn get<'a>(&self, ty: &'a str) -> Option<(&'a str, &dyn StepFactory)> {
+        let (namespace, name) = ty.split_once('.').unwrap_or((CORE, ty));
+        let factory = self.factories.get(&format!("{namespace}.{name}"))?;
+
+        let canonical = if namespace == CORE { name } else { ty };
+        Some((canonical, factory.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use restate_sdk::{
+        discovery::{HandlerType, ServiceType},
+        prelude::*,
+    };
+
+    use super::*;
+    use crate::mock::{BoxStep, ExecutionContext, JsonValue, Schema, Step, StepError, StepFailure};
+
+    struct Noop;
+
+    #[async_trait::async_trait]
+    impl Step for Noop {
+        fn validate(
+            &self,
+            _service_type: ServiceType,
+            _handler_type: Option<HandlerType>,
+        ) -> Result<(), StepError> {
+            Ok(())
+        }
+
+        async fn run(
+            &self,
+            _ctx: &WorkflowContext<'_>,
+            _exec: &mut ExecutionContext,
+            _input: &JsonValue,
+        ) -> Result<(), StepFailure> {
+            Ok(())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl StepFactory for Noop {
+        fn schema(&self) -> Schema {
+            Schema::new()
+        }
+
+        async fn create(&self, _params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+            Ok(Box::new(Noop))
+        }
+    }
+
+    fn pack(namespace: &'static str, names: &[&'static str]) -> StepPack {
+        StepPack {
+            namespace,
+            steps: names
+                .iter()
+                .map(|name| (*name, Box::new(Noop) as Box<dyn StepFactory>))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = StepRegistry::default();
+        registry.register(pack(CORE, &["sleep"])).unwrap();
+        registry
+            .register(pack("acme", &["sleep", "billing-check"]))
+            .unwrap();
+
+        assert_eq!(registry.get("sleep").unwrap().0, "sleep");
+        assert_eq!(registry.get("core.sleep").unwrap().0, "sleep");
+        assert_eq!(registry.get("acme.sleep").unwrap().0, "acme.sleep");
+        assert!(registry.get("billing-check").is_none());
+        assert!(registry.get("other.sleep").is_none());
+
+        assert!(matches!(
+            registry.register(pack("acme", &["billing-check"])),
+            Err(RegistryError::Collision(ty)) if ty == "acme.billing-check"
+        ));
+        assert!(matches!(
+            registry.register(pack("acme.v2", &["check"])),
+            Err(RegistryError::InvalidName(_))
+        ));
+
+        // a failing pack registers none of its steps
+        assert!(matches!(
+            registry.register(pack("other", &["check", "billing.check"])),
+            Err(RegistryError::InvalidName(_))
+        ));
+        assert!(matches!(
+            registry.register(pack("other", &["check", "check"])),
+            Err(RegistryError::Collision(ty)) if ty == "other.check"
+        ));
+        assert!(registry.get("other.check").is_none());
+    }
+}
@@ -0,0 +1,277 @@
+use serde_yaml::Value;
+
+use super::{json::JsonPath, size::Size, StepError};
+
+/// The expected type of a step parameter.
+#[derive(Debug, Clone)]
+pub enum ParamType {
+    /// A string.
+    String,
+    /// An integer number.
+    Integer,
+    /// Any number, integer or float.
+    Number,
+    /// A boolean.
+    Boolean,
+    /// A human-readable duration string (e.g. `500ms`, `2s`).
+    Duration,
+    /// A number of bytes or a human-readable size string (e.g. `512KB`, `5MB`).
+    Size,
+    /// One of the given strings.
+    Enum(&'static [&'static str]),
+    /// A literal value (string, number, boolean, null, or a list or an object of literals).
+    Literal,
+    /// Any value, including lists and objects.
+    Any,
+    /// A JSONPath selecting a single element (e.g. `$.items[0].id`).
+    JsonPath,
+    /// A regular expression.
+    Regex,
+    /// A list where every entry has the given type.
+    List(Box<ParamType>),
+    /// A nested object validated against its own schema.
+    Object(Schema),
+    /// A list of nested step configurations.
+    Steps,
+}
+
+impl ParamType {
+    /// Shorthand for a [`ParamType::List`] of the given type.
+    pub fn list(ty: ParamType) -> Self {
+        Self::List(Box::new(ty))
+    }
+
+    fn check(&self, field: &str, value: &Value) -> Result<(), StepError> {
+        let invalid = |reason: &str| StepError::InvalidParameter {
+            field: field.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        match self {
+            Self::String if !value.is_string() => Err(invalid("expected a string")),
+            Self::Integer if !(value.is_i64() || value.is_u64()) => {
+                Err(invalid("expected an integer"))
+            }
+            Self::Number if !value.is_number() => Err(invalid("expected a number")),
+            Self::Boolean if !value.is_bool() => Err(invalid("expected a boolean")),
+            Self::Duration => {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| invalid("expected a duration (e.g. 500ms, 2s)"))?;
+
+                humantime::parse_duration(value)
+                    .map(|_| ())
+                    .map_err(|err| invalid(&format!("invalid duration: {err}")))
+            }
+            Self::Size if value.is_u64() => Ok(()),
+            Self::Size => {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| invalid("expected a size (e.g. 512, 64KB, 5MB)"))?;
+
+                value
+                    .parse::<Size>()
+                    .map(|_| ())
+                    .map_err(|err| invalid(&err.to_string()))
+            }
+            Self::Enum(variants) => match value.as_str() {
+                Some(value) if variants.contains(&value) => Ok(()),
+                _ => Err(invalid(&format!("expected one of {}", variants.join(", ")))),
+            },
+            Self::Literal if matches!(value, Value::Tagged(_)) => Err(invalid(
+                "expected a literal (string, number, boolean, null, list or object)",
+            )),
+            Self::JsonPath => {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| invalid("expected a JSONPath (e.g. $.items[0].id)"))?;
+
+                value
+                    .parse::<JsonPath>()
+                    .map(|_| ())
+                    .map_err(|err| invalid(&err.to_string()))
+            }
+            Self::Regex => {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| invalid("expected a regular expression"))?;
+
+                regex::Regex::new(value)
+                    .map(|_| ())
+                    .map_err(|err| invalid(&format!("invalid regular expression: {err}")))
+            }
+            Self::List(ty) => {
+                let entries = value
+                    .as_sequence()
+                    .ok_or_else(|| invalid("expected a list"))?;
+
+                for (idx, entry) in entries.iter().enumerate() {
+                    ty.check(&format!("{field}[{idx}]"), entry)?;
+                }
+
+                Ok(())
+            }
+            Self::Object(schema) => schema.check(Some(field), value),
+            Self::Steps => {
+                let steps = value
+                    .as_sequence()
+                    .ok_or_else(|| invalid("expected a list of steps"))?;
+
+                for (idx, step) in steps.iter().enumerate() {
+                    if !step.get("type").is_some_and(Value::is_string) {
+                        return Err(StepError::InvalidParameter {
+                            field: format!("{field}[{idx}].type"),
+                            reason: "expected a step type".to_owned(),
+                        });
+                    }
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A single parameter of a [`Schema`].
+#[derive(Debug, Clone)]
+struct Param {
+    name: &'static str,
+    ty: ParamType,
+    required: bool,
+}
+
+/// The parameters accepted by a step, used to validate the `params` of a step
+/// configuration before the step is created.
+///
+/// Parameters that are not part of the schema are rejected.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    params: Vec<Param>,
+}
+
+impl Schema {
+    /// Creates a schema that accepts no parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a required parameter.
+    pub fn required(mut self, name: &'static str, ty: ParamType) -> Self {
+        self.params.push(Param {
+            name,
+            ty,
+            required: true,
+        });
+        self
+    }
+
+    /// Adds an optional parameter.
+    pub fn optional(mut self, name: &'static str, ty: ParamType) -> Self {
+        self.params.push(Param {
+            name,
+            ty,
+            required: false,
+        });
+        self
+    }
+
+    /// Validates the step `params` against the schema.
+    pub fn validate(&self, params: &Value) -> Result<(), StepError> {
+        self.check(None, params)
+    }
+
+    fn check(&self, prefix: Option<&str>, value: &Value) -> Result<(), StepError> {
+        let field = |name: &str| match prefix {
+            Some(prefix) => format!("{prefix}.{name}"),
+            None => name.to_owned(),
+        };
+
+        let empty = serde_yaml::Mapping::new();
+        let mapping = match value {
+            Value::Null => &empty,
+            Value::Mapping(mapping) => mapping,
+            _ => {
+                return Err(StepError::InvalidParameter {
+                    field: prefix.unwrap_or("params").to_owned(),
+                    reason: "expected an object".to_owned(),
+                })
+            }
+        };
+
+        for key in mapping.keys() {
+            let known = key
+                .as_str()
+                .is_some_and(|key| self.params.iter().any(|param| param.name == key));
+
+            if !known {
+                let name = key
+                    .as_str()
+                    .map(str::to_owned)
+                    .unwrap_or(format!("{key:?}"));
+                return Err(StepError::InvalidParameter {
+                    field: field(&name),
+                    reason: "unknown parameter".to_owned(),
+                });
+            }
+        }
+
+        for param in &self.params {
+            match mapping.get(param.name) {
+                Some(value) => param.ty.check(&field(param.name), value)?,
+                None if param.required => {
+                    return Err(StepError::InvalidParameter {
+                        field: field(param.name),
+                        reason: "missing required parameter".to_owned(),
+                    })
+                }
+                None => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(schema: &Schema, params: &str) -> Result<(), String> {
+        let params: Value = serde_yaml::from_str(params).unwrap();
+        schema.validate(&params).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_schema_validation() {
+        let schema = Schema::new()
+            .required("duration", ParamType::Duration)
+            .optional("jitter", ParamType::Number)
+            .optional(
+                "keys",
+                ParamType::list(ParamType::Object(
+                    Schema::new().required("key", ParamType::String),
+                )),
+            );
+
+        assert_eq!(check(&schema, "duration: 2s"), Ok(()));
+        assert_eq!(
+            check(&schema, "jitter: 0.1"),
+            Err("Invalid parameter `duration`: missing required parameter".to_owned())
+        );
+        assert_eq!(
+            check(&schema, "{duration: 2s, jitter: high}"),
+            Err("Invalid parameter `jitter`: expected a number".to_owned())
+        );
+        assert_eq!(
+            check(&schema, "{duration: 2s, jiter: 0.1}"),
+            Err("Invalid parameter `jiter`: unknown parameter".to_owned())
+        );
+        assert_eq!(
+            check(&schema, "{duration: 2s, keys: [{key: a}, {output: b}]}"),
+            Err("Invalid parameter `keys[1].output`: unknown parameter".to_owned())
+        );
+        assert!(check(&schema, "duration: forever").is_err());
+        assert!(check(&Schema::new(), "").is_ok());
+    }
+}
@@ -0,0 +1,98 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer};
+
+/// The units accepted by [`Size`], from the largest. Units are binary, so `1KB` is 1024
+/// bytes.
+const UNITS: &[(&str, u64)] = &[
+    ("GIB", 1 << 30),
+    ("GB", 1 << 30),
+    ("G", 1 << 30),
+    ("MIB", 1 << 20),
+    ("MB", 1 << 20),
+    ("M", 1 << 20),
+    ("KIB", 1 << 10),
+    ("KB", 1 << 10),
+    ("K", 1 << 10),
+    ("B", 1),
+];
+
+/// A number of bytes, given either as an integer or as a human-readable string
+/// (e.g. `512KB`, `5MB`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Size(pub u64);
+
+impl Size {
+    /// Returns the number of bytes.
+    pub fn bytes(self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid size `{0}` (expected e.g. 512, 64KB, 5MB)")]
+pub struct ParseSizeError(String);
+
+impl FromStr for Size {
+    type Err = ParseSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseSizeError(s.to_owned());
+
+        let upper = s.trim().to_ascii_uppercase();
+        let (number, unit) = UNITS
+            .iter()
+            .find_map(|(suffix, unit)| upper.strip_suffix(suffix).map(|number| (number, *unit)))
+            .unwrap_or((upper.as_str(), 1));
+
+        let number: u64 = number.trim().parse().map_err(|_| invalid())?;
+        number.checked_mul(unit).map(Size).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}B", self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Size {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bytes(u64),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Bytes(bytes) => Ok(Size(bytes)),
+            Raw::Text(text) => text.parse().map_err(de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!("512".parse::<Size>().unwrap(), Size(512));
+        assert_eq!("10B".parse::<Size>().unwrap(), Size(10));
+        assert_eq!("64KB".parse::<Size>().unwrap(), Size(64 << 10));
+        assert_eq!("5 MB".parse::<Size>().unwrap(), Size(5 << 20));
+        assert_eq!("2mib".parse::<Size>().unwrap(), Size(2 << 20));
+        assert_eq!("1G".parse::<Size>().unwrap(), Size(1 << 30));
+
+        assert!("".parse::<Size>().is_err());
+        assert!("MB".parse::<Size>().is_err());
+        assert!("1.5MB".parse::<Size>().is_err());
+        assert!("5 parsecs".parse::<Size>().is_err());
+
+        let size: Size = serde_yaml::from_str("1024").unwrap();
+        assert_eq!(size, Size(1024));
+        let size: Size = serde_yaml::from_str("1KB").unwrap();
+        assert_eq!(size, Size(1024));
+    }
+}
@@ -1,40 +1,375 @@
-use std::{collections::HashMap, sync::LazyLock, time::Duration};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, TryReserveError},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering as AtomicOrdering},
+        Arc, LazyLock, Mutex,
+    },
+    time::Duration,
+};
 
 use anyhow::Context;
-use rand::Rng;
-use restate_sdk::{context::RequestTarget, discovery::ServiceType, prelude::*};
+use rand::{distr::weighted::WeightedIndex, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
+use restate_sdk::{
+    context::{macro_support::SealedDurableFuture, HeaderMap, RequestTarget},
+    discovery::{HandlerType, ServiceType},
+    prelude::*,
+};
 use serde::Deserialize;
 use serde_with::serde_as;
 
-use crate::config;
+use crate::{
+    config,
+    stats::{StepTimer, STATS},
+};
 
 use super::{
-    context::Variable, BoxStep, ExecutionContext, JsonValue, Step, StepError, StepFactory,
+    barrier::{self, Barrier},
+    clock::{self, Clock},
+    context::Variable,
+    json::{self, JsonPath},
+    memory,
+    registry::{StepPack, StepRegistry, CORE},
+    size::Size,
+    template, BoxStep, Branch, ExecutionContext, InvocationKind, JsonValue, ParamType, Schema,
+    Step, StepError, StepFactory, StepFailure, Target, ALL_VARIABLES,
 };
 
-pub static STEPS: LazyLock<HashMap<String, Box<dyn StepFactory>>> = LazyLock::new(|| {
-    let mut steps: HashMap<String, Box<dyn StepFactory>> = HashMap::default();
-
-    steps.insert("echo".to_owned(), Box::new(Echo));
-    steps.insert("sleep".to_owned(), Box::new(Sleep));
-    steps.insert("busy".to_owned(), Box::new(Busy));
-    steps.insert("set".to_owned(), Box::new(Set));
-    steps.insert("get".to_owned(), Box::new(Get));
-    steps.insert("random".to_owned(), Box::new(Random));
-    steps.insert("increment".to_owned(), Box::new(Increment));
-    steps.insert("call".to_owned(), Box::new(Call));
-    steps.insert("send".to_owned(), Box::new(Send));
-    steps.insert("loop".to_owned(), Box::new(Loop));
-    steps.insert("return".to_owned(), Box::new(Return));
-
-    steps
+/// The type of the step used by stub handlers.
+pub const NOT_IMPLEMENTED: &str = "not-implemented";
+
+/// The type of the step recording the invocation history of a service.
+pub const RECORD_HISTORY: &str = "record-history";
+
+/// The type of the step used by the invocation history handler.
+pub const GET_HISTORY: &str = "get-history";
+
+/// The type of the step detecting duplicate deliveries.
+pub const DETECT_DUPLICATES: &str = "detect-duplicates";
+
+/// The type of the step used by the duplicate statistics handler.
+pub const GET_DUPLICATES: &str = "get-duplicates";
+
+/// The type of the step mirroring the input of a handler.
+pub const MIRROR: &str = "mirror";
+
+/// The type of the step simulating header based authentication.
+pub const REQUIRE_HEADER: &str = "require-header";
+
+/// The type of the step counting the increments of the exactly-once verification.
+pub const VERIFY_ADD: &str = "verify-add";
+
+/// The type of the step returning the totals of the exactly-once verification.
+pub const VERIFY_TOTALS: &str = "verify-totals";
+
+/// The registry of all the step types.
+///
+/// Third-party step packs are registered after the built-in steps, under their own namespace.
+pub static STEPS: LazyLock<StepRegistry> = LazyLock::new(|| {
+    let mut registry = StepRegistry::default();
+
+    for pack in [core_steps()] {
+        if let Err(err) = registry.register(pack) {
+            panic!("{err}");
+        }
+    }
+
+    registry
 });
 
+/// Returns the built-in steps.
+fn core_steps() -> StepPack {
+    StepPack {
+        namespace: CORE,
+        steps: vec![
+            ("echo", Box::new(Echo)),
+            ("sleep", Box::new(Sleep)),
+            ("sleep-until", Box::new(SleepUntil)),
+            ("busy", Box::new(Busy)),
+            ("cpu", Box::new(Cpu)),
+            ("memory", Box::new(Memory)),
+            ("crash", Box::new(Crash)),
+            ("hang", Box::new(Hang)),
+            ("run", Box::new(RunFactory)),
+            ("barrier", Box::new(BarrierFactory)),
+            ("set", Box::new(Set)),
+            ("get", Box::new(Get)),
+            ("get-or-init", Box::new(GetOrInit)),
+            ("get-many", Box::new(GetMany)),
+            ("mem-set", Box::new(MemSet)),
+            ("mem-get", Box::new(MemGet)),
+            ("persist-request", Box::new(PersistRequest)),
+            (RECORD_HISTORY, Box::new(RecordHistory)),
+            (GET_HISTORY, Box::new(GetHistory)),
+            (DETECT_DUPLICATES, Box::new(DetectDuplicates)),
+            (GET_DUPLICATES, Box::new(GetDuplicates)),
+            ("fill-state", Box::new(FillState)),
+            ("state-size", Box::new(StateSize)),
+            ("random", Box::new(Random)),
+            ("random-number", Box::new(RandomNumber)),
+            ("choice", Box::new(ChoiceFactory)),
+            ("uuid", Box::new(UuidFactory)),
+            ("now", Box::new(Now)),
+            ("key", Box::new(Key)),
+            ("headers", Box::new(Headers)),
+            ("invocation-id", Box::new(InvocationId)),
+            ("const", Box::new(Const)),
+            ("payload", Box::new(Payload)),
+            ("template", Box::new(Template)),
+            ("extract", Box::new(Extract)),
+            ("increment", Box::new(Increment)),
+            ("sequence", Box::new(Sequence)),
+            ("counter", Box::new(Counter)),
+            ("call", Box::new(Call)),
+            ("send", Box::new(Send)),
+            ("send-batch", Box::new(SendBatch)),
+            (MIRROR, Box::new(Mirror)),
+            ("await-workflow", Box::new(AwaitWorkflow)),
+            ("attach", Box::new(Attach)),
+            ("http", Box::new(Http)),
+            ("exec", Box::new(Exec)),
+            ("promise-await", Box::new(PromiseAwait)),
+            ("promise-peek", Box::new(PromisePeek)),
+            ("promise-resolve", Box::new(PromiseResolve)),
+            ("loop", Box::new(Loop)),
+            ("repeat", Box::new(Loop)),
+            ("parallel", Box::new(Parallel)),
+            ("race", Box::new(Race)),
+            ("retry", Box::new(Retry)),
+            ("condition", Box::new(Condition)),
+            ("state-if", Box::new(StateIf)),
+            ("switch", Box::new(Switch)),
+            ("assert", Box::new(Assert)),
+            ("expect-input", Box::new(ExpectInput)),
+            ("validate-input", Box::new(ValidateInput)),
+            ("build-output", Box::new(BuildOutput)),
+            ("return", Box::new(Return)),
+            ("dump", Box::new(Dump)),
+            ("log", Box::new(Log)),
+            ("verify-produce", Box::new(VerifyProduce)),
+            (VERIFY_ADD, Box::new(VerifyAdd)),
+            (VERIFY_TOTALS, Box::new(VerifyTotals)),
+            (REQUIRE_HEADER, Box::new(RequireHeader)),
+            (NOT_IMPLEMENTED, Box::new(NotImplemented)),
+            ("fail", Box::new(Fail)),
+            ("fail-if", Box::new(FailIf)),
+            ("retryable-error", Box::new(RetryableError)),
+            ("maybe-fail", Box::new(MaybeFail)),
+            ("fail-times", Box::new(FailTimes)),
+        ],
+    }
+}
+
+/// The accepted values of a `target_type` parameter.
+const SERVICE_TYPES: &[&str] = &["SERVICE", "VIRTUAL_OBJECT", "WORKFLOW"];
+
+/// Validates that a step writing state runs in an exclusive handler of a virtual object
+/// or workflow.
+fn validate_state_write(
+    service_type: ServiceType,
+    handler_type: Option<HandlerType>,
+) -> Result<(), StepError> {
+    if service_type == ServiceType::Service {
+        return Err(StepError::InvalidServiceType(service_type));
+    }
+
+    if handler_type == Some(HandlerType::Shared) {
+        return Err(StepError::InvalidHandlerType(HandlerType::Shared));
+    }
+
+    Ok(())
+}
+
+/// Creates a step from its configuration, validating the step parameters
+/// against the schema of its factory first.
+pub async fn create_step(config: config::StepConfig) -> Result<BoxStep, StepError> {
+    let (ty, factory) = STEPS
+        .get(&config.ty)
+        .ok_or_else(|| StepError::UnknownStepType(config.ty.clone()))?;
+
+    factory.schema().validate(&config.params)?;
+
+    let step = factory.create(config.params).await?;
+
+    let step = match config.flaky {
+        Some(flaky) => {
+            if !(0.0..=1.0).contains(&flaky.probability) {
+                return Err(StepError::InvalidFlaky(
+                    "probability must be between 0.0 and 1.0".to_owned(),
+                ));
+            }
+
+            Box::new(FlakyStep { inner: step, flaky })
+        }
+        None => step,
+    };
+
+    Ok(Box::new(TimedStep {
+        inner: step,
+        timer: STATS.step_timer(ty, config.name.as_deref()),
+        clock: clock::system(),
+    }))
+}
+
+/// Creates the steps nested in the parameter `field` of a step (e.g. the `steps` of a loop),
+/// the errors pointing at the failing nested step.
+async fn create_steps(
+    field: &str,
+    configs: Vec<config::StepConfig>,
+) -> Result<Vec<BoxStep>, StepError> {
+    let mut steps = Vec::new();
+    for (idx, config) in configs.into_iter().enumerate() {
+        let step = create_step(config).await.map_err(|err| StepError::Nested {
+            path: format!("{field}[{idx}]"),
+            source: Box::new(err),
+        })?;
+        steps.push(step);
+    }
+
+    Ok(steps)
+}
+
+/// A wrapper recording the execution duration of the wrapped step in the stats.
+struct TimedStep {
+    inner: BoxStep,
+    timer: StepTimer,
+    clock: Arc<dyn Clock>,
+}
+
+#[async_trait::async_trait]
+impl Step for TimedStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        self.inner.validate(service_type, handler_type)
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.inner.outputs()
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        self.inner.inputs()
+    }
+
+    fn targets(&self) -> Vec<Target<'_>> {
+        self.inner.targets()
+    }
+
+    fn ends_handler(&self) -> bool {
+        self.inner.ends_handler()
+    }
+
+    fn fire_and_forget(&self) -> bool {
+        self.inner.fire_and_forget()
+    }
+
+    fn deterministic(&self) -> bool {
+        self.inner.deterministic()
+    }
+
+    fn branches(&self) -> Vec<Branch<'_>> {
+        self.inner.branches()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let started = self.clock.now();
+        let res = self.inner.run(ctx, exec, input).await;
+        self.timer.record(self.clock.now() - started);
+
+        res
+    }
+}
+
+/// A wrapper failing randomly before running the wrapped step, to exercise the retry
+/// behavior of the SDK and the runtime at the granularity of a single step.
+struct FlakyStep {
+    inner: BoxStep,
+    flaky: config::FlakyConfig,
+}
+
+#[async_trait::async_trait]
+impl Step for FlakyStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        self.inner.validate(service_type, handler_type)
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.inner.outputs()
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        self.inner.inputs()
+    }
+
+    fn targets(&self) -> Vec<Target<'_>> {
+        self.inner.targets()
+    }
+
+    fn ends_handler(&self) -> bool {
+        self.inner.ends_handler()
+    }
+
+    fn fire_and_forget(&self) -> bool {
+        self.inner.fire_and_forget()
+    }
+
+    fn deterministic(&self) -> bool {
+        false
+    }
+
+    fn branches(&self) -> Vec<Branch<'_>> {
+        self.inner.branches()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        if rand::random_bool(self.flaky.probability) {
+            return Err(injected_error(
+                self.flaky.error,
+                self.flaky.code.unwrap_or(config::DEFAULT_ERROR_CODE),
+                "injected flaky step failure".to_owned(),
+            ));
+        }
+
+        self.inner.run(ctx, exec, input).await
+    }
+}
+
+/// Returns an injected error of the given kind, `code` being the code of a terminal error.
+fn injected_error(kind: config::ErrorKind, code: u16, message: String) -> StepFailure {
+    match kind {
+        config::ErrorKind::Retryable => StepFailure::from(message),
+        config::ErrorKind::Terminal => TerminalError::new_with_code(code, message).into(),
+    }
+}
+
 /// Factory for creating `EchoStep` instances.
 struct Echo;
 
+#[async_trait::async_trait]
 impl StepFactory for Echo {
-    fn create(&self, _params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+    fn schema(&self) -> Schema {
+        Schema::new()
+    }
+
+    async fn create(&self, _params: serde_yaml::Value) -> Result<BoxStep, StepError> {
         Ok(Box::new(EchoStep))
     }
 }
@@ -44,7 +379,11 @@ struct EchoStep;
 
 #[async_trait::async_trait]
 impl Step for EchoStep {
-    fn validate(&self, _service_type: ServiceType) -> Result<(), StepError> {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
         Ok(())
     }
 
@@ -53,7 +392,7 @@ impl Step for EchoStep {
         _ctx: &WorkflowContext<'_>,
         exec: &mut ExecutionContext,
         input: &JsonValue,
-    ) -> Result<(), HandlerError> {
+    ) -> Result<(), StepFailure> {
         exec.return_value(input.clone());
         Ok(())
     }
@@ -62,32 +401,267 @@ impl Step for EchoStep {
 /// Factory for creating `SleepStep` instances.
 struct Sleep;
 
+#[async_trait::async_trait]
 impl StepFactory for Sleep {
-    fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+    fn schema(&self) -> Schema {
+        Latency::schema()
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
         let step: SleepStep = serde_yaml::from_value(params)?;
+        step.latency.check()?;
         Ok(Box::new(step))
     }
 }
 
-/// A step that pauses execution for a specified duration. This step utilizes the Restate SDK's
-/// `ctx.sleep()` method, meaning the sleep is managed by the Restate runtime and is durable.
-/// It can be useful for simulating delays that should persist across retries or service restarts.
+/// A single entry of a weighted latency profile.
 #[serde_as]
 #[derive(Debug, Deserialize)]
-pub struct SleepStep {
-    /// The base duration for which to sleep. Parsed from a human-readable string like "2s" or "500ms".
+struct WeightedDuration {
+    /// The duration, parsed from a human-readable string like "2s" or "500ms".
     #[serde_as(as = "serde_with::DisplayFromStr")]
     duration: humantime::Duration,
-    /// Optional: A factor (0.0 to 1.0) to add random jitter to the sleep duration.
+    /// The relative weight of this entry. Defaults to `1`.
+    #[serde(default = "default_weight")]
+    weight: f64,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+/// The longest time a step waits on a timer (one year). The SDK adds the delay of a timer to
+/// the current time without checking for overflows, so longer delays are capped.
+const MAX_DELAY: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// The duration of a [`SleepStep`] or [`BusyStep`].
+///
+/// Exactly one of a single `duration`, a weighted profile of `durations` (one is picked per
+/// invocation) or a random `distribution` must be set. Jitter is applied on top of the
+/// picked duration, then the result is clamped between `min` and `max`.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct Latency {
+    /// The base duration. Parsed from a human-readable string like "2s" or "500ms".
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    duration: Option<humantime::Duration>,
+    /// A weighted list of durations, one of them is picked randomly on every invocation.
+    /// Useful to model bimodal latencies (e.g. fast cache hit vs slow miss).
+    #[serde(default)]
+    durations: Vec<WeightedDuration>,
+    /// A distribution the duration is sampled from on every invocation, to match latency
+    /// profiles measured in production.
+    distribution: Option<LatencyDistribution>,
+    /// Optional: A factor (0.0 to 1.0) to add random jitter to the duration.
     /// The actual jitter duration will be a random value between 0 and `jitter * duration`.
     /// For example, if `duration` is `10s` and `jitter` is `0.1`, an additional random delay
     /// between `0s` and `1s` will be added.
     jitter: Option<f32>,
+    /// Optional: The minimum duration.
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    min: Option<humantime::Duration>,
+    /// Optional: The maximum duration, useful to cut the tail of a distribution.
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    max: Option<humantime::Duration>,
+}
+
+/// The accepted values of the `type` of a latency distribution.
+const DISTRIBUTIONS: &[&str] = &["normal", "exponential", "log-normal", "pareto"];
+
+/// A distribution of durations. Negative samples are treated as 0.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum LatencyDistribution {
+    /// A normal distribution.
+    Normal {
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        mean: humantime::Duration,
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        stddev: humantime::Duration,
+    },
+    /// An exponential distribution, e.g. the service times of a queue.
+    Exponential {
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        mean: humantime::Duration,
+    },
+    /// A log-normal distribution, given by its own mean and standard deviation, with a
+    /// long tail typical of request latencies.
+    LogNormal {
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        mean: humantime::Duration,
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        stddev: humantime::Duration,
+    },
+    /// A Pareto distribution, with `scale` the minimum duration and `shape` the tail index
+    /// (the lower, the heavier the tail).
+    Pareto {
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        scale: humantime::Duration,
+        shape: f64,
+    },
+}
+
+impl LatencyDistribution {
+    /// Samples a duration, `None` if the parameters are invalid.
+    fn sample(&self, rng: &mut impl Rng) -> Option<Duration> {
+        let secs = |duration: &humantime::Duration| Duration::from(*duration).as_secs_f64();
+
+        let sample = match self {
+            LatencyDistribution::Normal { mean, stddev } => {
+                rand_distr::Normal::new(secs(mean), secs(stddev))
+                    .ok()?
+                    .sample(rng)
+            }
+            LatencyDistribution::Exponential { mean } => {
+                rand_distr::Exp::new(1.0 / secs(mean)).ok()?.sample(rng)
+            }
+            LatencyDistribution::LogNormal { mean, stddev } => {
+                rand_distr::LogNormal::from_mean_cv(secs(mean), secs(stddev) / secs(mean))
+                    .ok()?
+                    .sample(rng)
+            }
+            LatencyDistribution::Pareto { scale, shape } => {
+                rand_distr::Pareto::new(secs(scale), *shape)
+                    .ok()?
+                    .sample(rng)
+            }
+        };
+
+        Some(Duration::try_from_secs_f64(sample.max(0.0)).map_or(MAX_DELAY, |d| d.min(MAX_DELAY)))
+    }
+}
+
+impl Latency {
+    fn schema() -> Schema {
+        Schema::new()
+            .optional("duration", ParamType::Duration)
+            .optional(
+                "durations",
+                ParamType::list(ParamType::Object(
+                    Schema::new()
+                        .required("duration", ParamType::Duration)
+                        .optional("weight", ParamType::Number),
+                )),
+            )
+            .optional(
+                "distribution",
+                ParamType::Object(
+                    Schema::new()
+                        .required("type", ParamType::Enum(DISTRIBUTIONS))
+                        .optional("mean", ParamType::Duration)
+                        .optional("stddev", ParamType::Duration)
+                        .optional("scale", ParamType::Duration)
+                        .optional("shape", ParamType::Number),
+                ),
+            )
+            .optional("jitter", ParamType::Number)
+            .optional("min", ParamType::Duration)
+            .optional("max", ParamType::Duration)
+    }
+
+    /// Checks that exactly one of `duration`, `durations` and `distribution` is set, with
+    /// valid weights, distribution parameters and jitter.
+    fn check(&self) -> Result<(), StepError> {
+        let invalid = |field: &str, reason: &str| StepError::InvalidParameter {
+            field: field.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        if let Some(jitter) = self.jitter {
+            if !(0.0..=1.0).contains(&jitter) {
+                return Err(invalid("jitter", "must be a factor between 0.0 and 1.0"));
+            }
+        }
+
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if Duration::from(min) > Duration::from(max) {
+                return Err(invalid("min", "must not be greater than `max`"));
+            }
+        }
+
+        let set = [
+            self.duration.is_some(),
+            !self.durations.is_empty(),
+            self.distribution.is_some(),
+        ];
+        if set.iter().filter(|set| **set).count() != 1 {
+            return Err(invalid(
+                "duration",
+                "exactly one of `duration`, `durations` or `distribution` must be set",
+            ));
+        }
+
+        if let Some(distribution) = &self.distribution {
+            return distribution
+                .sample(&mut rand::rng())
+                .map(|_| ())
+                .ok_or_else(|| invalid("distribution", "invalid distribution parameters"));
+        }
+
+        if self.durations.is_empty() {
+            return Ok(());
+        }
+
+        WeightedIndex::new(self.durations.iter().map(|d| d.weight))
+            .map(|_| ())
+            .map_err(|err| invalid("durations", &format!("invalid weights: {err}")))
+    }
+
+    /// Picks the duration for the current invocation, including jitter and clamping. The
+    /// duration never exceeds [`MAX_DELAY`], so heavy tails can not overflow the timers.
+    fn sample(&self, rng: &mut impl Rng) -> Duration {
+        let base: Duration = match (self.duration, &self.distribution) {
+            (Some(duration), _) => duration.into(),
+            (None, Some(distribution)) => {
+                // parameters are checked on creation
+                distribution.sample(rng).unwrap_or_default()
+            }
+            (None, None) => {
+                // weights are checked on creation
+                let index = WeightedIndex::new(self.durations.iter().map(|d| d.weight))
+                    .expect("valid weights");
+                self.durations[index.sample(rng)].duration.into()
+            }
+        };
+
+        let base = base.min(MAX_DELAY);
+        let jitter = self.jitter.map(|j| {
+            let factor = f64::from(rng.random_range(0.0..=j));
+            Duration::try_from_secs_f64(factor * base.as_secs_f64()).unwrap_or(MAX_DELAY)
+        });
+
+        let mut duration = base.saturating_add(jitter.unwrap_or_default());
+        if let Some(min) = self.min {
+            duration = duration.max(min.into());
+        }
+        if let Some(max) = self.max {
+            duration = duration.min(max.into());
+        }
+
+        duration.min(MAX_DELAY)
+    }
+}
+
+/// A step that pauses execution for a specified duration. This step utilizes the Restate SDK's
+/// `ctx.sleep()` method, meaning the sleep is managed by the Restate runtime and is durable.
+/// It can be useful for simulating delays that should persist across retries or service restarts.
+#[derive(Debug, Deserialize)]
+pub struct SleepStep {
+    #[serde(flatten)]
+    latency: Latency,
 }
 
 #[async_trait::async_trait]
 impl Step for SleepStep {
-    fn validate(&self, _service_type: ServiceType) -> Result<(), StepError> {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
         Ok(())
     }
 
@@ -96,15 +670,108 @@ impl Step for SleepStep {
         ctx: &WorkflowContext<'_>,
         _step: &mut ExecutionContext,
         _input: &JsonValue,
-    ) -> Result<(), HandlerError> {
-        let jitter = self
-            .jitter
-            .map(|j| rand::random_range(0.0..=j) * self.duration.as_secs_f32())
-            .map(Duration::from_secs_f32);
+    ) -> Result<(), StepFailure> {
+        let duration = self.latency.sample(&mut rand::rng());
+        ctx.sleep(duration).await?;
+
+        Ok(())
+    }
+}
 
-        let duration = Duration::from(self.duration) + jitter.unwrap_or_default();
+/// Factory for creating `SleepUntilStep` instances.
+struct SleepUntil;
 
-        ctx.sleep(duration).await?;
+#[async_trait::async_trait]
+impl StepFactory for SleepUntil {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("variable", ParamType::String)
+            .optional("path", ParamType::JsonPath)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: SleepUntilConfig = serde_yaml::from_value(params)?;
+        Ok(Box::new(SleepUntilStep {
+            timestamp: Operand::new(config.variable, config.path)?,
+        }))
+    }
+}
+
+/// Configuration for a `SleepUntilStep` as defined in the YAML.
+#[derive(Debug, Deserialize)]
+struct SleepUntilConfig {
+    /// Optional: The name of the variable holding the timestamp.
+    variable: Option<String>,
+    /// Optional: A JSONPath selecting the timestamp in the handler input.
+    path: Option<JsonPath>,
+}
+
+/// A step that sleeps on a durable timer until an absolute timestamp, given in epoch
+/// milliseconds or as an RFC3339 time. A timestamp in the past does not sleep.
+struct SleepUntilStep {
+    timestamp: Operand,
+}
+
+impl SleepUntilStep {
+    /// Parses the timestamp as milliseconds since the epoch.
+    fn parse(value: &Variable) -> Option<u64> {
+        match value {
+            Variable::Integer(millis) => u64::try_from(*millis).ok(),
+            Variable::String(text) => text.trim().parse().ok().or_else(|| {
+                humantime::parse_rfc3339_weak(text.trim())
+                    .ok()?
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .ok()
+                    .map(|since| since.as_millis() as u64)
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for SleepUntilStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        self.timestamp.input().into_iter().collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let value = self.timestamp.resolve(ctx, exec, input).await?;
+        let until = SleepUntilStep::parse(&value).ok_or_else(|| {
+            TerminalError::new(format!(
+                "invalid timestamp {}: expected epoch millis or an RFC3339 time",
+                template::text_of(&value)
+            ))
+        })?;
+
+        // the duration of the timer is derived from the journaled time, so it is stable
+        // across replays
+        let now: u64 = ctx
+            .run(|| async {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                Ok(now.as_millis() as u64)
+            })
+            .name("now")
+            .await?;
+
+        if until > now {
+            ctx.sleep(Duration::from_millis(until - now)).await?;
+        }
 
         Ok(())
     }
@@ -113,8 +780,15 @@ impl Step for SleepStep {
 /// Factory for creating `SetStep` instances.
 struct Set;
 
+#[async_trait::async_trait]
 impl StepFactory for Set {
-    fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("key", ParamType::String)
+            .required("input", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
         let step: SetStep = serde_yaml::from_value(params)?;
         Ok(Box::new(step))
     }
@@ -132,12 +806,16 @@ struct SetStep {
 
 #[async_trait::async_trait]
 impl Step for SetStep {
-    fn validate(&self, service_type: ServiceType) -> Result<(), StepError> {
-        if service_type == ServiceType::Service {
-            return Err(StepError::InvalidServiceType(service_type));
-        }
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_state_write(service_type, handler_type)
+    }
 
-        Ok(())
+    fn inputs(&self) -> Vec<&str> {
+        vec![self.input.as_str()]
     }
 
     async fn run(
@@ -145,7 +823,7 @@ impl Step for SetStep {
         ctx: &WorkflowContext<'_>,
         exec: &mut ExecutionContext,
         _input: &JsonValue,
-    ) -> Result<(), HandlerError> {
+    ) -> Result<(), StepFailure> {
         ctx.set(
             &self.key,
             exec.get_variable(&self.input)
@@ -160,8 +838,15 @@ impl Step for SetStep {
 /// Factory for creating `GetStep` instances.
 struct Get;
 
+#[async_trait::async_trait]
 impl StepFactory for Get {
-    fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("key", ParamType::String)
+            .required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
         let step: GetStep = serde_yaml::from_value(params)?;
         Ok(Box::new(step))
     }
@@ -181,7 +866,11 @@ struct GetStep {
 
 #[async_trait::async_trait]
 impl Step for GetStep {
-    fn validate(&self, service_type: ServiceType) -> Result<(), StepError> {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
         if service_type == ServiceType::Service {
             return Err(StepError::InvalidServiceType(service_type));
         }
@@ -189,12 +878,16 @@ impl Step for GetStep {
         Ok(())
     }
 
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
     async fn run(
         &self,
         ctx: &WorkflowContext<'_>,
         exec: &mut ExecutionContext,
         _input: &JsonValue,
-    ) -> Result<(), HandlerError> {
+    ) -> Result<(), StepFailure> {
         let value: Variable = ctx.get(&self.key).await?.unwrap_or(Variable::Null);
 
         exec.set(&self.output, value);
@@ -203,391 +896,534 @@ impl Step for GetStep {
     }
 }
 
-/// Factory for creating `RandomStep` instances.
-struct Random;
+/// Factory for creating `MemSetStep` instances.
+struct MemSet;
+
+#[async_trait::async_trait]
+impl StepFactory for MemSet {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("key", ParamType::String)
+            .optional("input", ParamType::String)
+            .optional("value", ParamType::Literal)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: MemSetStep = serde_yaml::from_value(params)?;
+        if step.input.is_some() == step.value.is_some() {
+            return Err(StepError::InvalidParameter {
+                field: "input".to_owned(),
+                reason: "exactly one of `input` or `value` must be set".to_owned(),
+            });
+        }
 
-impl StepFactory for Random {
-    fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
-        let step: RandomStep = serde_yaml::from_value(params)?;
         Ok(Box::new(step))
     }
 }
 
-/// A step that generates a specified number of random bytes and stores them in a variable.
+/// A step that stores a value in the in-memory key-value store of the process, shared by
+/// all the invocations of all the services. The value is not durable, it is meant to
+/// coordinate the behavior of the mocks, e.g. to flip a flag read by another handler.
+#[serde_as]
 #[derive(Debug, Deserialize)]
-struct RandomStep {
-    /// The number of random bytes to generate.
-    size: u16,
-    /// The name of the variable in the execution context where the byte array will be stored.
-    output: String,
+struct MemSetStep {
+    /// The string key to store the value under.
+    key: String,
+    /// The name of the variable in the execution context whose value will be stored.
+    input: Option<String>,
+    /// The literal value to store instead of a variable.
+    #[serde_as(as = "Option<serde_with::TryFromInto<serde_json::Value>>")]
+    #[serde(default)]
+    value: Option<Variable>,
 }
 
 #[async_trait::async_trait]
-impl Step for RandomStep {
-    fn validate(&self, _service_type: ServiceType) -> Result<(), StepError> {
+impl Step for MemSetStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
         Ok(())
     }
 
+    fn inputs(&self) -> Vec<&str> {
+        self.input.as_deref().into_iter().collect()
+    }
+
     async fn run(
         &self,
         _ctx: &WorkflowContext<'_>,
         exec: &mut ExecutionContext,
         _input: &JsonValue,
-    ) -> Result<(), HandlerError> {
-        let mut rng = rand::rng();
-        let bytes: Vec<u8> = (0..self.size).map(|_| rng.random()).collect();
+    ) -> Result<(), StepFailure> {
+        let value = match (&self.input, &self.value) {
+            (Some(input), _) => exec
+                .get_variable(input)
+                .ok_or_else(|| TerminalError::new(format!("unknown variable {input}")))?
+                .clone(),
+            (None, value) => value.clone().unwrap_or(Variable::Null),
+        };
 
-        exec.set(&self.output, bytes);
+        memory::set(&self.key, value);
 
         Ok(())
     }
 }
 
-/// Factory for creating `ReturnStep` instances.
-struct Return;
+/// Factory for creating `MemGetStep` instances.
+struct MemGet;
 
-impl StepFactory for Return {
-    fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
-        let step: ReturnStep = serde_yaml::from_value(params)?;
+#[async_trait::async_trait]
+impl StepFactory for MemGet {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("key", ParamType::String)
+            .required("output", ParamType::String)
+            .optional("default", ParamType::Literal)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: MemGetStep = serde_yaml::from_value(params)?;
         Ok(Box::new(step))
     }
 }
 
-/// A step that ends the handler execution and returns the value of a specified variable.
+/// A step that retrieves a value from the in-memory key-value store of the process and
+/// stores it in a variable. The read is journaled.
+#[serde_as]
 #[derive(Debug, Deserialize)]
-struct ReturnStep {
-    /// The name of the variable in the execution context whose value will be returned
-    /// as the result of the handler.
+struct MemGetStep {
+    /// The string key of the value to retrieve.
+    key: String,
+    /// The name of the variable in the execution context where the value will be stored.
     output: String,
+    /// The literal value used if the key is not found. Defaults to `null`.
+    #[serde_as(as = "Option<serde_with::TryFromInto<serde_json::Value>>")]
+    #[serde(default)]
+    default: Option<Variable>,
 }
 
 #[async_trait::async_trait]
-impl Step for ReturnStep {
-    fn validate(&self, _service_type: ServiceType) -> Result<(), StepError> {
+impl Step for MemGetStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
         Ok(())
     }
 
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
     async fn run(
         &self,
-        _ctx: &WorkflowContext<'_>,
+        ctx: &WorkflowContext<'_>,
         exec: &mut ExecutionContext,
         _input: &JsonValue,
-    ) -> Result<(), HandlerError> {
-        let variable = exec
-            .get_variable(&self.output)
-            .ok_or_else(|| TerminalError::new(format!("unkown variable {}", self.output)))?;
+    ) -> Result<(), StepFailure> {
+        // journaled, so a replayed invocation sees the value read the first time
+        let value: Variable = ctx
+            .run(|| async {
+                Ok(memory::get(&self.key)
+                    .or_else(|| self.default.clone())
+                    .unwrap_or(Variable::Null))
+            })
+            .name("mem-get")
+            .await?;
 
-        exec.return_value(serde_json::to_value(variable)?);
+        exec.set(&self.output, value);
 
         Ok(())
     }
 }
 
-/// Factory for creating `IncrementStep` instances.
-struct Increment;
+/// Factory for creating `GetOrInitStep` instances.
+struct GetOrInit;
 
-impl StepFactory for Increment {
-    fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
-        let step: IncrementStep = serde_yaml::from_value(params)?;
+#[async_trait::async_trait]
+impl StepFactory for GetOrInit {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("key", ParamType::String)
+            .required("default", ParamType::Literal)
+            .required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: GetOrInitStep = serde_yaml::from_value(params)?;
         Ok(Box::new(step))
     }
 }
 
-/// A step that increments a numerical value stored in a variable.
-/// If the variable doesn't exist or is not a number, it defaults to 0 before incrementing.
+/// A step that retrieves a value from the Restate state for the current virtual object and,
+/// if the key is not set, initializes it with a configured default value.
+/// The resulting value is stored in a variable.
+/// This step is only valid for services of type `VIRTUAL_OBJECT`.
+#[serde_as]
 #[derive(Debug, Deserialize)]
-struct IncrementStep {
-    /// The name of the variable in the execution context holding the numerical value to increment.
-    /// The result is stored back in the same variable.
-    input: String,
-    /// The integer amount to increment by. Defaults to `1`.
-    #[serde(default = "default_steps")]
-    steps: isize,
-}
-
-fn default_steps() -> isize {
-    1
+struct GetOrInitStep {
+    /// The string key of the value to retrieve or initialize.
+    key: String,
+    /// The literal value written to the state if the key is not found.
+    #[serde_as(as = "serde_with::TryFromInto<serde_json::Value>")]
+    default: Variable,
+    /// The name of the variable in the execution context where the value will be stored.
+    output: String,
 }
 
 #[async_trait::async_trait]
-impl Step for IncrementStep {
-    fn validate(&self, _service_type: ServiceType) -> Result<(), StepError> {
-        Ok(())
+impl Step for GetOrInitStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_state_write(service_type, handler_type)
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
     }
 
     async fn run(
         &self,
-        _ctx: &WorkflowContext<'_>,
+        ctx: &WorkflowContext<'_>,
         exec: &mut ExecutionContext,
         _input: &JsonValue,
-    ) -> Result<(), HandlerError> {
-        let mut value = exec
-            .get::<isize>(&self.input)
-            .unwrap_or(Ok(0))
-            .context("Failed to get variable")?;
-
-        value += self.steps;
+    ) -> Result<(), StepFailure> {
+        let value: Variable = match ctx.get(&self.key).await? {
+            Some(value) => value,
+            None => {
+                ctx.set(&self.key, self.default.clone());
+                self.default.clone()
+            }
+        };
 
-        exec.set(&self.input, value);
+        exec.set(&self.output, value);
 
         Ok(())
     }
 }
 
-struct Call;
+/// Factory for creating `GetManyStep` instances.
+struct GetMany;
 
-impl StepFactory for Call {
-    fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
-        let step: CallStep = serde_yaml::from_value(params)?;
+#[async_trait::async_trait]
+impl StepFactory for GetMany {
+    fn schema(&self) -> Schema {
+        Schema::new().required(
+            "keys",
+            ParamType::list(ParamType::Object(
+                Schema::new()
+                    .required("key", ParamType::String)
+                    .optional("output", ParamType::String)
+                    .optional("default", ParamType::Literal),
+            )),
+        )
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: GetManyStep = serde_yaml::from_value(params)?;
         Ok(Box::new(step))
     }
 }
 
-/// A step that makes a call to another handler, which can be part of any service,
-/// virtual object, or workflow defined within the mock service configuration.
-///
-/// This step allows for complex interactions and chaining of logic across different
-/// components of the mock setup.
+/// A single state key read by a [`GetManyStep`].
+#[serde_as]
 #[derive(Debug, Deserialize)]
-struct CallStep {
-    /// Specifies the type of the target handler to be called (SERVICE, VIRTUAL_OBJECT, or WORKFLOW).
-    target_type: ServiceType,
-    /// The string name of the target service, virtual object, or workflow.
-    service: String,
-    /// The string name of the target handler to invoke on the specified service.
-    handler: String,
-    /// The string key to use when `target_type` is `VIRTUAL_OBJECT` or `WORKFLOW`.
-    /// If the current service (caller) is a `VIRTUAL_OBJECT` or `WORKFLOW` and `key` is `None`,
-    /// the key of the current service instance is used.
-    /// Required if `target_type` is `VIRTUAL_OBJECT`/`WORKFLOW` and the caller is `SERVICE`,
-    /// or if a specific key different from the caller's key is needed.
-    key: Option<String>,
-    /// Optional: The name of a variable in the execution context whose value will be sent as input.
-    /// If `None` or the variable doesn't exist, `null` is sent.
-    input: Option<String>,
-    /// Optional: The name of a variable in the execution context to store the call's result.
-    /// If `None`, the result is discarded.
+struct GetManyEntry {
+    /// The string key of the value to retrieve.
+    key: String,
+    /// The name of the variable where the retrieved value will be stored.
+    /// Defaults to the state key itself.
     output: Option<String>,
+    /// Optional: A literal value stored in the variable if the key is not found.
+    /// If `None`, `null` will be stored.
+    #[serde_as(as = "Option<serde_with::TryFromInto<serde_json::Value>>")]
+    #[serde(default)]
+    default: Option<Variable>,
+}
+
+/// A step that retrieves several values from the Restate state for the current virtual object
+/// and stores each of them in a variable.
+/// This is a configuration shorthand for a sequence of `get` steps: each key is still read
+/// with its own state call, so it journals one entry per key.
+/// This step is only valid for services of type `VIRTUAL_OBJECT`.
+#[derive(Debug, Deserialize)]
+struct GetManyStep {
+    /// The state keys to retrieve.
+    keys: Vec<GetManyEntry>,
 }
 
 #[async_trait::async_trait]
-impl Step for CallStep {
-    fn validate(&self, _service_type: ServiceType) -> Result<(), StepError> {
+impl Step for GetManyStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        if service_type == ServiceType::Service {
+            return Err(StepError::InvalidServiceType(service_type));
+        }
+
         Ok(())
     }
 
+    fn outputs(&self) -> Vec<&str> {
+        self.keys
+            .iter()
+            .map(|entry| entry.output.as_deref().unwrap_or(&entry.key))
+            .collect()
+    }
+
     async fn run(
         &self,
         ctx: &WorkflowContext<'_>,
         exec: &mut ExecutionContext,
         _input: &JsonValue,
-    ) -> Result<(), HandlerError> {
-        let request_target = match self.target_type {
-            ServiceType::Service => RequestTarget::Service {
-                name: self.service.clone(),
-                handler: self.handler.clone(),
-            },
-            ServiceType::VirtualObject => RequestTarget::Object {
-                name: self.service.clone(),
-                key: self.key.clone().unwrap_or_else(|| ctx.key().to_string()),
-                handler: self.handler.clone(),
-            },
-            ServiceType::Workflow => RequestTarget::Workflow {
-                name: self.service.clone(),
-                key: self.key.clone().unwrap_or_else(|| ctx.key().to_string()),
-                handler: self.handler.clone(),
-            },
-        };
-
-        let req = self
-            .input
-            .as_ref()
-            .and_then(|input| exec.get_variable(input))
-            .cloned()
-            .unwrap_or(Variable::Null);
+    ) -> Result<(), StepFailure> {
+        for entry in &self.keys {
+            let value: Variable = ctx
+                .get(&entry.key)
+                .await?
+                .or_else(|| entry.default.clone())
+                .unwrap_or(Variable::Null);
 
-        let res: Variable = ctx.request(request_target, req).call().await?;
-        if let Some(output) = self.output.as_ref() {
-            exec.set(output, res);
+            exec.set(entry.output.as_deref().unwrap_or(&entry.key), value);
         }
 
         Ok(())
     }
 }
 
-struct Send;
+/// Factory for creating `PersistRequestStep` instances.
+struct PersistRequest;
+
+#[async_trait::async_trait]
+impl StepFactory for PersistRequest {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("key", ParamType::String)
+            .optional("fields", ParamType::list(ParamType::String))
+            .optional("append", ParamType::Boolean)
+            .optional("max", ParamType::Integer)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: PersistRequestStep = serde_yaml::from_value(params)?;
+
+        if step.max.is_some() && !step.append {
+            return Err(StepError::InvalidParameter {
+                field: "max".to_owned(),
+                reason: "can only be used with `append`".to_owned(),
+            });
+        }
 
-impl StepFactory for Send {
-    fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
-        let step: SendStep = serde_yaml::from_value(params)?;
         Ok(Box::new(step))
     }
 }
 
-/// A step that sends a call to another handler, which can be part of any service,
-/// virtual object, or workflow defined within the mock service configuration.
-///
-/// This step allows for complex interactions and chaining of logic across different
-/// components of the mock setup.
-///
-/// Similar to [`CallStep`] but does not wait for output
+/// A step that stores the handler input (or selected fields of it) in the Restate state,
+/// so it can be inspected later.
+/// This step is only valid for services of type `VIRTUAL_OBJECT` or `WORKFLOW`.
 #[derive(Debug, Deserialize)]
-struct SendStep {
-    /// Specifies the type of the target handler to be called (SERVICE, VIRTUAL_OBJECT, or WORKFLOW).
-    target_type: ServiceType,
-    /// The string name of the target service, virtual object, or workflow.
-    service: String,
-    /// The string name of the target handler to invoke on the specified service.
-    handler: String,
-    /// The string key to use when `target_type` is `VIRTUAL_OBJECT` or `WORKFLOW`.
-    /// If the current service (caller) is a `VIRTUAL_OBJECT` or `WORKFLOW` and `key` is `None`,
-    /// the key of the current service instance is used.
-    /// Required if `target_type` is `VIRTUAL_OBJECT`/`WORKFLOW` and the caller is `SERVICE`,
-    /// or if a specific key different from the caller's key is needed.
-    key: Option<String>,
-    /// Optional: The name of a variable in the execution context whose value will be sent as input.
-    /// If `None` or the variable doesn't exist, `null` is sent.
-    input: Option<String>,
+struct PersistRequestStep {
+    /// The string key to store the input under.
+    key: String,
+    /// Optional: The top-level fields of the input to store. Defaults to the whole input.
+    fields: Option<Vec<String>>,
+    /// If `true`, the input is appended to a list stored under `key` instead of replacing it.
+    #[serde(default)]
+    append: bool,
+    /// Optional: The maximum number of entries kept when appending, the oldest entries are dropped.
+    max: Option<usize>,
+}
+
+impl PersistRequestStep {
+    fn select(&self, input: &serde_json::Value) -> serde_json::Value {
+        let Some(fields) = &self.fields else {
+            return input.clone();
+        };
+
+        fields
+            .iter()
+            .filter_map(|field| Some((field.clone(), input.get(field)?.clone())))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
 }
 
 #[async_trait::async_trait]
-impl Step for SendStep {
-    fn validate(&self, _service_type: ServiceType) -> Result<(), StepError> {
-        Ok(())
+impl Step for PersistRequestStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_state_write(service_type, handler_type)
     }
 
     async fn run(
         &self,
         ctx: &WorkflowContext<'_>,
-        exec: &mut ExecutionContext,
-        _input: &JsonValue,
-    ) -> Result<(), HandlerError> {
-        let request_target = match self.target_type {
-            ServiceType::Service => RequestTarget::Service {
-                name: self.service.clone(),
-                handler: self.handler.clone(),
-            },
-            ServiceType::VirtualObject => RequestTarget::Object {
-                name: self.service.clone(),
-                key: self.key.clone().unwrap_or_else(|| ctx.key().to_string()),
-                handler: self.handler.clone(),
-            },
-            ServiceType::Workflow => RequestTarget::Workflow {
-                name: self.service.clone(),
-                key: self.key.clone().unwrap_or_else(|| ctx.key().to_string()),
-                handler: self.handler.clone(),
-            },
-        };
+        _exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let value = self.select(&input.0);
 
-        let req = self
-            .input
-            .as_ref()
-            .and_then(|input| exec.get_variable(input))
-            .cloned()
-            .unwrap_or(Variable::Null);
+        if !self.append {
+            ctx.set(&self.key, JsonValue(value));
+            return Ok(());
+        }
 
-        ctx.request::<_, ()>(request_target, req).send();
+        append_to_state(ctx, &self.key, value, self.max).await
+    }
+}
 
-        Ok(())
+/// Appends `value` to the list stored under the state `key`, keeping at most `max` entries
+/// (the oldest are dropped first).
+async fn append_to_state(
+    ctx: &WorkflowContext<'_>,
+    key: &str,
+    value: serde_json::Value,
+    max: Option<usize>,
+) -> Result<(), StepFailure> {
+    let stored = ctx.get::<JsonValue>(key).await?;
+    ctx.set(key, JsonValue(appended(key, stored, value, max)?));
+
+    Ok(())
+}
+
+/// Returns the list `stored` under the state `key` with `value` appended, keeping at most
+/// `max` entries.
+fn appended(
+    key: &str,
+    stored: Option<JsonValue>,
+    value: serde_json::Value,
+    max: Option<usize>,
+) -> Result<serde_json::Value, TerminalError> {
+    let mut entries = match stored {
+        Some(JsonValue(serde_json::Value::Array(entries))) => entries,
+        Some(_) => {
+            return Err(TerminalError::new(format!(
+                "state key {key} does not hold a list"
+            )))
+        }
+        None => Vec::new(),
+    };
+
+    entries.push(value);
+    if let Some(max) = max {
+        let excess = entries.len().saturating_sub(max);
+        entries.drain(..excess);
     }
+
+    Ok(entries.into())
 }
 
-struct Busy;
+/// Factory for creating `RecordHistoryStep` instances.
+struct RecordHistory;
 
-impl StepFactory for Busy {
-    fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
-        let step: BusyStep = serde_yaml::from_value(params)?;
+#[async_trait::async_trait]
+impl StepFactory for RecordHistory {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("key", ParamType::String)
+            .required("handler", ParamType::String)
+            .optional("max", ParamType::Integer)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: RecordHistoryStep = serde_yaml::from_value(params)?;
         Ok(Box::new(step))
     }
 }
 
-/// A step that simulates a busy handler by causing the current thread to sleep for a specified duration.
-/// Unlike [`SleepStep`], this sleep is handled directly within the mock service using `tokio::time::sleep()`
-/// and is not managed by the Restate runtime. This is useful for simulating CPU-bound work or
-/// other synchronous delays within the handler itself, without involving durable timers.
-#[serde_as]
+/// A step that appends the received invocation (handler name and input) to a bounded
+/// history list in the Restate state.
+/// This step is only valid for services of type `VIRTUAL_OBJECT` or `WORKFLOW`.
 #[derive(Debug, Deserialize)]
-struct BusyStep {
-    /// The base duration for which the handler will simulate being busy.
-    /// Parsed from a human-readable string (e.g., "100ms", "1s").
-    #[serde_as(as = "serde_with::DisplayFromStr")]
-    duration: humantime::Duration,
-    /// Optional: A factor (0.0 to 1.0) to add random jitter to the busy duration.
-    /// The actual jitter duration will be a random value between 0 and `jitter * duration`.
-    jitter: Option<f32>,
+struct RecordHistoryStep {
+    /// The state key holding the history.
+    key: String,
+    /// The name of the handler recorded in the history entries.
+    handler: String,
+    /// Optional: The maximum number of entries kept, the oldest entries are dropped.
+    max: Option<usize>,
+}
+
+impl RecordHistoryStep {
+    /// Returns the history entry recording the invocation with the given `input`.
+    fn entry(&self, input: &JsonValue) -> serde_json::Value {
+        serde_json::json!({
+            "handler": self.handler,
+            "input": input.0,
+        })
+    }
 }
 
 #[async_trait::async_trait]
-impl Step for BusyStep {
-    fn validate(&self, _service_type: ServiceType) -> Result<(), StepError> {
-        Ok(())
+impl Step for RecordHistoryStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_state_write(service_type, handler_type)
     }
 
     async fn run(
         &self,
-        _ctx: &WorkflowContext<'_>,
+        ctx: &WorkflowContext<'_>,
         _exec: &mut ExecutionContext,
-        _input: &JsonValue,
-    ) -> Result<(), HandlerError> {
-        let jitter = self
-            .jitter
-            .map(|j| rand::random_range(0.0..=j) * self.duration.as_secs_f32())
-            .map(Duration::from_secs_f32);
-
-        let duration = Duration::from(self.duration) + jitter.unwrap_or_default();
-
-        tokio::time::sleep(duration).await;
-
-        Ok(())
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        append_to_state(ctx, &self.key, self.entry(input), self.max).await
     }
 }
 
-/// Factory for creating `LoopStep` instances.
-struct Loop;
-
-impl StepFactory for Loop {
-    fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
-        let step: LoopStepConfig = serde_yaml::from_value(params)?;
-
-        let mut steps = Vec::new();
-
-        for config in step.steps {
-            let step = STEPS
-                .get(&config.ty)
-                .ok_or(StepError::UnknownStepType(config.ty))?;
+/// Factory for creating `GetHistoryStep` instances.
+struct GetHistory;
 
-            //todo: validate steps against service type
-            steps.push(step.create(config.params)?);
-        }
+#[async_trait::async_trait]
+impl StepFactory for GetHistory {
+    fn schema(&self) -> Schema {
+        Schema::new().required("key", ParamType::String)
+    }
 
-        Ok(Box::new(LoopStep {
-            count: step.count,
-            steps,
-        }))
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: GetHistoryStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
     }
 }
 
-/// Configuration for a `LoopStep` as defined in the YAML.
+/// A step that returns the history recorded by [`RecordHistoryStep`], oldest entry first.
+/// This step is only valid for services of type `VIRTUAL_OBJECT` or `WORKFLOW`.
 #[derive(Debug, Deserialize)]
-struct LoopStepConfig {
-    /// Optional: The number of times to execute the nested steps.
-    /// If `None` or not provided, the loop will run indefinitely (or until `usize::MAX` iterations).
-    count: Option<usize>,
-    /// A list of step configurations that will be executed in each iteration of the loop.
-    steps: Vec<config::StepConfig>,
+struct GetHistoryStep {
+    /// The state key holding the history.
+    key: String,
 }
 
-/// A step that executes a sequence of nested steps repeatedly.
-struct LoopStep {
-    /// The number of times the loop will iterate. `None` means effectively infinite.
-    count: Option<usize>,
-    /// The actual `Step` trait objects for the nested steps to be executed in each iteration.
-    steps: Vec<BoxStep>,
+/// Returns the recorded history, empty if nothing was recorded yet.
+fn history_or_empty(history: Option<JsonValue>) -> JsonValue {
+    history.unwrap_or(JsonValue(serde_json::json!([])))
 }
 
 #[async_trait::async_trait]
-impl Step for LoopStep {
-    fn validate(&self, _service_type: ServiceType) -> Result<(), StepError> {
+impl Step for GetHistoryStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        if service_type == ServiceType::Service {
+            return Err(StepError::InvalidServiceType(service_type));
+        }
+
         Ok(())
     }
 
@@ -595,14 +1431,8100 @@ impl Step for LoopStep {
         &self,
         ctx: &WorkflowContext<'_>,
         exec: &mut ExecutionContext,
-        input: &JsonValue,
-    ) -> Result<(), HandlerError> {
-        for _ in 0..self.count.unwrap_or(usize::MAX) {
-            for step in &self.steps {
-                step.run(ctx, exec, input).await?;
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let history = ctx.get::<JsonValue>(&self.key).await?;
+        exec.return_value(history_or_empty(history));
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `DetectDuplicatesStep` instances.
+struct DetectDuplicates;
+
+#[async_trait::async_trait]
+impl StepFactory for DetectDuplicates {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("key", ParamType::String)
+            .optional("field", ParamType::JsonPath)
+            .optional("header", ParamType::String)
+            .optional("fail", ParamType::Boolean)
+            .optional("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: DetectDuplicatesStep = serde_yaml::from_value(params)?;
+
+        if step.field.is_some() && step.header.is_some() {
+            return Err(StepError::InvalidParameter {
+                field: "header".to_owned(),
+                reason: "can not be used together with `field`".to_owned(),
+            });
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// The duplicate delivery statistics maintained by [`DetectDuplicatesStep`].
+#[derive(Debug, Default, serde::Serialize, Deserialize)]
+struct DuplicateStats {
+    /// The number of received requests.
+    requests: usize,
+    /// The number of requests without a request ID.
+    missing_id: usize,
+    /// The number of requests whose ID was already seen.
+    duplicates: usize,
+    /// The number of times each request ID was received.
+    ids: std::collections::BTreeMap<String, usize>,
+}
+
+impl DuplicateStats {
+    /// Records a request with the given ID, returning whether the ID was already seen.
+    fn record(&mut self, id: Option<&str>) -> bool {
+        self.requests += 1;
+
+        let Some(id) = id else {
+            self.missing_id += 1;
+            return false;
+        };
+
+        let count = self.ids.entry(id.to_owned()).or_default();
+        *count += 1;
+        if *count > 1 {
+            self.duplicates += 1;
+        }
+
+        *count > 1
+    }
+
+    /// Returns the report of the statistics, listing the duplicated request IDs.
+    fn report(&self) -> serde_json::Value {
+        let duplicated: serde_json::Map<_, _> = self
+            .ids
+            .iter()
+            .filter(|(_, count)| **count > 1)
+            .map(|(id, count)| (id.clone(), (*count).into()))
+            .collect();
+
+        serde_json::json!({
+            "requests": self.requests,
+            "unique": self.ids.len(),
+            "missing_id": self.missing_id,
+            "duplicates": self.duplicates,
+            "duplicated_ids": duplicated,
+        })
+    }
+}
+
+/// A step that records the request ID of every invocation in the Restate state and counts
+/// the requests delivered more than once.
+/// This step is only valid for services of type `VIRTUAL_OBJECT` or `WORKFLOW`.
+#[derive(Debug, Deserialize)]
+struct DetectDuplicatesStep {
+    /// The state key holding the statistics.
+    key: String,
+    /// Optional: A JSONPath selecting the request ID in the input.
+    field: Option<JsonPath>,
+    /// Optional: The request header holding the request ID. Defaults to `idempotency-key`
+    /// if `field` is not set.
+    header: Option<String>,
+    /// If `true`, duplicates fail the handler with a terminal error.
+    #[serde(default)]
+    fail: bool,
+    /// Optional: The name of the variable where a boolean (`true` for duplicates) is stored.
+    output: Option<String>,
+}
+
+impl DetectDuplicatesStep {
+    fn request_id(&self, headers: &HeaderMap, input: &JsonValue) -> Option<String> {
+        if let Some(field) = &self.field {
+            return match field.select(&input.0)? {
+                serde_json::Value::String(id) => Some(id.clone()),
+                serde_json::Value::Null => None,
+                id => Some(id.to_string()),
+            };
+        }
+
+        headers
+            .get(self.header.as_deref().unwrap_or("idempotency-key"))
+            .cloned()
+    }
+
+    /// Returns the error failing the handler for a duplicate request, if configured so.
+    fn check(&self, duplicate: bool, id: Option<String>) -> Result<(), TerminalError> {
+        if duplicate && self.fail {
+            return Err(TerminalError::new_with_code(
+                409,
+                format!("duplicate request {}", id.unwrap_or_default()),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for DetectDuplicatesStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_state_write(service_type, handler_type)
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.output.as_deref().into_iter().collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let mut stats: DuplicateStats = match ctx.get::<JsonValue>(&self.key).await? {
+            Some(JsonValue(stats)) => serde_json::from_value(stats)?,
+            None => DuplicateStats::default(),
+        };
+
+        let id = self.request_id(ctx.headers(), input);
+        let duplicate = stats.record(id.as_deref());
+
+        ctx.set(&self.key, JsonValue(serde_json::to_value(&stats)?));
+
+        if let Some(output) = &self.output {
+            exec.set(output, duplicate);
+        }
+
+        Ok(self.check(duplicate, id)?)
+    }
+}
+
+/// Factory for creating `GetDuplicatesStep` instances.
+struct GetDuplicates;
+
+#[async_trait::async_trait]
+impl StepFactory for GetDuplicates {
+    fn schema(&self) -> Schema {
+        Schema::new().required("key", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: GetDuplicatesStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that returns the statistics recorded by [`DetectDuplicatesStep`], along with
+/// the list of the duplicated request IDs.
+/// This step is only valid for services of type `VIRTUAL_OBJECT` or `WORKFLOW`.
+#[derive(Debug, Deserialize)]
+struct GetDuplicatesStep {
+    /// The state key holding the statistics.
+    key: String,
+}
+
+#[async_trait::async_trait]
+impl Step for GetDuplicatesStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        if service_type == ServiceType::Service {
+            return Err(StepError::InvalidServiceType(service_type));
+        }
+
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let stats: DuplicateStats = match ctx.get::<JsonValue>(&self.key).await? {
+            Some(JsonValue(stats)) => serde_json::from_value(stats)?,
+            None => DuplicateStats::default(),
+        };
+
+        exec.return_value(stats.report());
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `FillStateStep` instances.
+struct FillState;
+
+#[async_trait::async_trait]
+impl StepFactory for FillState {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("count", ParamType::Integer)
+            .required("size", ParamType::Integer)
+            .optional("pattern", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: FillStateStep = serde_yaml::from_value(params)?;
+
+        let total = step.count.checked_mul(step.size);
+        if total.is_none_or(|total| total > MAX_FILL_STATE_BYTES) {
+            return Err(StepError::InvalidParameter {
+                field: "size".to_owned(),
+                reason: format!(
+                    "the state written (`count` times `size`) must be at most {MAX_FILL_STATE_BYTES} bytes"
+                ),
+            });
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// The maximum number of bytes a [`FillStateStep`] writes, over all the keys (1 GiB).
+const MAX_FILL_STATE_BYTES: usize = 1 << 30;
+
+/// A step that writes a number of state keys, each holding a fixed amount of random bytes,
+/// to the current virtual object. Useful to exercise Restate with large per-key state.
+/// This step is only valid for services of type `VIRTUAL_OBJECT`.
+#[derive(Debug, Deserialize)]
+struct FillStateStep {
+    /// The number of state keys to write.
+    count: usize,
+    /// The number of random bytes stored under each key.
+    size: usize,
+    /// The pattern used to name the keys. Every `{}` is replaced with the key index
+    /// (starting at 0). Defaults to `key-{}`.
+    #[serde(default = "default_fill_state_pattern")]
+    pattern: String,
+}
+
+fn default_fill_state_pattern() -> String {
+    "key-{}".to_owned()
+}
+
+impl FillStateStep {
+    /// Generates the keys and their bytes, passing them to `write`. The bytes are derived
+    /// from the seed, so a replay writes the same state, as Restate expects.
+    fn fill(
+        &self,
+        seed: u64,
+        mut write: impl FnMut(String, bytes::Bytes),
+    ) -> Result<(), StepFailure> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for idx in 0..self.count {
+            let key = self.pattern.replace("{}", &idx.to_string());
+            let mut bytes = Vec::new();
+            bytes.try_reserve_exact(self.size).map_err(|err| {
+                StepFailure::from(format!("failed to allocate {} bytes: {err}", self.size))
+            })?;
+            bytes.extend((0..self.size).map(|_| rng.random::<u8>()));
+
+            write(key, bytes::Bytes::from(bytes));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for FillStateStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_state_write(service_type, handler_type)
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        // raw bytes, so every key holds exactly `size` bytes of state
+        self.fill(exec.draw_seed(ctx.random_seed()), |key, bytes| {
+            ctx.set(&key, bytes)
+        })
+    }
+}
+
+/// Factory for creating `StateSizeStep` instances.
+struct StateSize;
+
+#[async_trait::async_trait]
+impl StepFactory for StateSize {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("output", ParamType::String)
+            .optional("keys", ParamType::String)
+            .optional("return", ParamType::Boolean)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: StateSizeStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that enumerates the state keys of the current virtual object and sums the size
+/// of their serialized values.
+/// This step is only valid for services of type `VIRTUAL_OBJECT`.
+#[derive(Debug, Deserialize)]
+struct StateSizeStep {
+    /// Optional: The name of the variable where the total size in bytes will be stored.
+    output: Option<String>,
+    /// Optional: The name of the variable where the number of state keys will be stored.
+    keys: Option<String>,
+    /// If `true`, the handler returns a report with the number of keys, the total size
+    /// and the size of each key. Defaults to `false`.
+    #[serde(default, rename = "return")]
+    ret: bool,
+}
+
+#[async_trait::async_trait]
+impl Step for StateSizeStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        if service_type == ServiceType::Service {
+            return Err(StepError::InvalidServiceType(service_type));
+        }
+
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.output
+            .as_deref()
+            .into_iter()
+            .chain(self.keys.as_deref())
+            .collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let keys = ctx.get_keys().await?;
+
+        let mut total = 0;
+        let mut sizes = serde_json::Map::new();
+        for key in keys.iter() {
+            let value: Option<bytes::Bytes> = ctx.get(key).await?;
+            let size = value.map(|v| v.len()).unwrap_or_default();
+
+            total += size;
+            sizes.insert(key.clone(), size.into());
+        }
+
+        if let Some(output) = self.output.as_ref() {
+            exec.set(output, total as isize);
+        }
+
+        if let Some(output) = self.keys.as_ref() {
+            exec.set(output, keys.len() as isize);
+        }
+
+        if self.ret {
+            exec.return_value(serde_json::json!({
+                "keys": keys.len(),
+                "total_size": total,
+                "sizes": sizes,
+            }));
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `RandomStep` instances.
+struct Random;
+
+#[async_trait::async_trait]
+impl StepFactory for Random {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("size", ParamType::Integer)
+            .required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: RandomStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that generates a specified number of random bytes and stores them in a variable.
+#[derive(Debug, Deserialize)]
+struct RandomStep {
+    /// The number of random bytes to generate.
+    size: u16,
+    /// The name of the variable in the execution context where the byte array will be stored.
+    output: String,
+}
+
+#[async_trait::async_trait]
+impl Step for RandomStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let mut rng = rand::rng();
+        let bytes: Vec<u8> = (0..self.size).map(|_| rng.random()).collect();
+
+        exec.set(&self.output, bytes);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `RandomNumberStep` instances.
+struct RandomNumber;
+
+#[async_trait::async_trait]
+impl StepFactory for RandomNumber {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("min", ParamType::Number)
+            .required("max", ParamType::Number)
+            .required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: RandomNumberConfig = serde_yaml::from_value(params)?;
+
+        let range = match (config.min.as_i64(), config.max.as_i64()) {
+            (Some(min), Some(max)) => NumberRange::Integer(min as isize, max as isize),
+            _ => NumberRange::Number(
+                config.min.as_f64().unwrap_or_default(),
+                config.max.as_f64().unwrap_or_default(),
+            ),
+        };
+
+        if range.is_empty() {
+            return Err(StepError::InvalidParameter {
+                field: "max".to_owned(),
+                reason: "must be greater than or equal to `min`".to_owned(),
+            });
+        }
+
+        Ok(Box::new(RandomNumberStep {
+            range,
+            output: config.output,
+        }))
+    }
+}
+
+/// The configuration of a [`RandomNumberStep`].
+#[derive(Debug, Deserialize)]
+struct RandomNumberConfig {
+    min: serde_json::Number,
+    max: serde_json::Number,
+    output: String,
+}
+
+/// The inclusive range of a [`RandomNumberStep`].
+#[derive(Debug)]
+enum NumberRange {
+    Integer(isize, isize),
+    Number(f64, f64),
+}
+
+impl NumberRange {
+    fn is_empty(&self) -> bool {
+        match *self {
+            Self::Integer(min, max) => min > max,
+            Self::Number(min, max) => min > max,
+        }
+    }
+}
+
+/// A step that generates a random number in the `[min, max]` range and stores it in a
+/// variable: an integer if both bounds are integers, a float otherwise.
+///
+/// The number is generated in a `ctx.run()`, so that a replayed invocation gets the same
+/// number and takes the same decisions.
+#[derive(Debug)]
+struct RandomNumberStep {
+    range: NumberRange,
+    /// The name of the variable in the execution context where the number will be stored.
+    output: String,
+}
+
+#[async_trait::async_trait]
+impl Step for RandomNumberStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let number = ctx
+            .run(|| async {
+                Ok(match self.range {
+                    NumberRange::Integer(min, max) => {
+                        Variable::Integer(rand::random_range(min as i64..=max as i64) as isize)
+                    }
+                    NumberRange::Number(min, max) => {
+                        Variable::Number(rand::random_range(min..=max))
+                    }
+                })
+            })
+            .name("random-number")
+            .await?;
+
+        exec.set(&self.output, number);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `ChoiceStep` instances.
+struct ChoiceFactory;
+
+#[async_trait::async_trait]
+impl StepFactory for ChoiceFactory {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required(
+                "choices",
+                ParamType::list(ParamType::Object(
+                    Schema::new()
+                        .required("value", ParamType::Literal)
+                        .optional("weight", ParamType::Number),
+                )),
+            )
+            .required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: ChoiceConfig = serde_yaml::from_value(params)?;
+
+        let weights = WeightedIndex::new(config.choices.iter().map(|choice| choice.weight))
+            .map_err(|err| StepError::InvalidParameter {
+                field: "choices".to_owned(),
+                reason: format!("invalid weights: {err}"),
+            })?;
+
+        Ok(Box::new(ChoiceStep {
+            values: config
+                .choices
+                .into_iter()
+                .map(|choice| choice.value)
+                .collect(),
+            weights,
+            output: config.output,
+        }))
+    }
+}
+
+/// A single entry of a [`ChoiceConfig`].
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct Choice {
+    /// The literal value stored if the entry is picked.
+    #[serde_as(as = "serde_with::TryFromInto<serde_json::Value>")]
+    value: Variable,
+    /// The relative weight of this entry. Defaults to `1`.
+    #[serde(default = "default_weight")]
+    weight: f64,
+}
+
+/// The configuration of a [`ChoiceStep`].
+#[derive(Debug, Deserialize)]
+struct ChoiceConfig {
+    choices: Vec<Choice>,
+    output: String,
+}
+
+/// A step that picks one of the configured literal values, at random according to their
+/// weights, and stores it in a variable.
+struct ChoiceStep {
+    /// The values to pick from.
+    values: Vec<Variable>,
+    /// The distribution of the indexes of `values`.
+    weights: WeightedIndex<f64>,
+    /// The name of the variable in the execution context where the picked value will be stored.
+    output: String,
+}
+
+#[async_trait::async_trait]
+impl Step for ChoiceStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        // the picked index is journaled so a replayed invocation picks the same value
+        let idx: u64 = ctx
+            .run(|| async { Ok(self.weights.sample(&mut rand::rng()) as u64) })
+            .name("choice")
+            .await?;
+
+        // a journal written by a previous configuration may hold an index out of range
+        let value = self.values.get(idx as usize).cloned().ok_or_else(|| {
+            TerminalError::new(format!(
+                "journaled choice {idx} is out of range of {} values",
+                self.values.len()
+            ))
+        })?;
+        exec.set(&self.output, value);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `UuidStep` instances.
+struct UuidFactory;
+
+#[async_trait::async_trait]
+impl StepFactory for UuidFactory {
+    fn schema(&self) -> Schema {
+        Schema::new().required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: UuidStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that generates a random (version 4) UUID and stores its text in a variable.
+///
+/// The UUID is derived from the random seed of the invocation, so that a replayed invocation
+/// gets the same UUID without journaling it and can use it as the key of its calls.
+#[derive(Debug, Deserialize)]
+struct UuidStep {
+    /// The name of the variable in the execution context where the UUID will be stored.
+    output: String,
+}
+
+impl UuidStep {
+    /// Generates the UUID derived from the seed.
+    fn generate(seed: u64) -> String {
+        uuid::Builder::from_random_bytes(StdRng::seed_from_u64(seed).random())
+            .into_uuid()
+            .to_string()
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for UuidStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let uuid = UuidStep::generate(exec.draw_seed(ctx.random_seed()));
+        exec.set(&self.output, uuid);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `NowStep` instances.
+struct Now;
+
+#[async_trait::async_trait]
+impl StepFactory for Now {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("output", ParamType::String)
+            .optional("format", ParamType::Enum(&["millis", "rfc3339"]))
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: NowStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// The format of the time stored by a `now` step.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TimeFormat {
+    /// An integer number of milliseconds since the Unix epoch.
+    #[default]
+    Millis,
+    /// An RFC 3339 string, e.g. `2025-06-01T12:00:00.000Z`.
+    Rfc3339,
+}
+
+/// A step that stores the current time in a variable.
+///
+/// The time is read once in a `ctx.run()`, so that a replayed invocation gets the time of
+/// its first execution back.
+#[derive(Debug, Deserialize)]
+struct NowStep {
+    /// The name of the variable in the execution context where the time will be stored.
+    output: String,
+    /// The format of the time. Defaults to `millis`.
+    #[serde(default)]
+    format: TimeFormat,
+}
+
+#[async_trait::async_trait]
+impl Step for NowStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let millis: u64 = ctx
+            .run(|| async {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                Ok(now.as_millis() as u64)
+            })
+            .name("now")
+            .await?;
+
+        match self.format {
+            TimeFormat::Millis => exec.set(&self.output, millis as isize),
+            TimeFormat::Rfc3339 => {
+                let time = std::time::UNIX_EPOCH + Duration::from_millis(millis);
+                exec.set(
+                    &self.output,
+                    humantime::format_rfc3339_millis(time).to_string(),
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `KeyStep` instances.
+struct Key;
+
+#[async_trait::async_trait]
+impl StepFactory for Key {
+    fn schema(&self) -> Schema {
+        Schema::new().required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: KeyStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that stores the key of the current virtual object or workflow in a variable.
+/// This step is only valid for services of type `VIRTUAL_OBJECT` or `WORKFLOW`.
+#[derive(Debug, Deserialize)]
+struct KeyStep {
+    /// The name of the variable in the execution context where the key will be stored.
+    output: String,
+}
+
+#[async_trait::async_trait]
+impl Step for KeyStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        if service_type == ServiceType::Service {
+            return Err(StepError::InvalidServiceType(service_type));
+        }
+
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        exec.set(&self.output, ctx.key().to_owned());
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `HeadersStep` instances.
+struct Headers;
+
+#[async_trait::async_trait]
+impl StepFactory for Headers {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("name", ParamType::String)
+            .required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: HeadersStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that stores the headers of the request in a variable.
+#[derive(Debug, Deserialize)]
+struct HeadersStep {
+    /// Optional: The name of the header stored, `null` if the request does not have it.
+    /// If `None`, an object holding all the headers by name is stored.
+    name: Option<String>,
+    /// The name of the variable in the execution context where the headers will be stored.
+    output: String,
+}
+
+impl HeadersStep {
+    /// Returns the value stored for the `headers` of the request.
+    fn headers(&self, headers: &HeaderMap) -> Variable {
+        // the repeated headers are joined as they would be on a single line
+        let value = |name| {
+            headers
+                .get_all(name)
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match &self.name {
+            Some(name) if headers.contains_key(name.as_str()) => value(name.as_str()).into(),
+            Some(_) => Variable::Null,
+            None => headers
+                .keys()
+                .map(|name| (name.to_string(), value(name.as_str()).into()))
+                .collect::<std::collections::BTreeMap<_, _>>()
+                .into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for HeadersStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        exec.set(&self.output, self.headers(ctx.headers()));
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `InvocationIdStep` instances.
+struct InvocationId;
+
+#[async_trait::async_trait]
+impl StepFactory for InvocationId {
+    fn schema(&self) -> Schema {
+        Schema::new().required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: InvocationIdStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that stores the ID of the current invocation in a variable.
+#[derive(Debug, Deserialize)]
+struct InvocationIdStep {
+    /// The name of the variable in the execution context where the ID will be stored.
+    output: String,
+}
+
+#[async_trait::async_trait]
+impl Step for InvocationIdStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let id = exec.invocation_id().to_owned();
+        exec.set(&self.output, id);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `ReturnStep` instances.
+struct Return;
+
+#[async_trait::async_trait]
+impl StepFactory for Return {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("output", ParamType::String)
+            .optional("value", ParamType::Any)
+            .optional("file", ParamType::String)
+            .optional("merge", ParamType::list(ParamType::String))
+            .optional("early", ParamType::Boolean)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let mut step: ReturnStep = serde_yaml::from_value(params)?;
+
+        let set = [
+            step.output.is_some(),
+            step.value.is_some(),
+            step.file.is_some(),
+            !step.merge.is_empty(),
+        ];
+        if set.iter().filter(|set| **set).count() != 1 {
+            return Err(StepError::InvalidParameter {
+                field: "output".to_owned(),
+                reason: "exactly one of `output`, `value`, `file` or `merge` must be set"
+                    .to_owned(),
+            });
+        }
+
+        if let Some(file) = &step.file {
+            let value = load_fixture(file).await.map_err(|source| StepError::Load {
+                field: "file".to_owned(),
+                source,
+            })?;
+            step.value = Some(value);
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// Returns the JSON returned for a variable: lists and objects as plain JSON, while the
+/// other values keep the tagged form they have always been returned in (e.g. `{"Integer":3}`),
+/// so the bytes returned by a raw handler are written as is.
+fn returned_value(variable: &Variable) -> Result<serde_json::Value, TerminalError> {
+    match variable {
+        Variable::Array(_) | Variable::Object(_) => Ok(variable.clone().into()),
+        variable => {
+            serde_json::to_value(variable).map_err(|err| TerminalError::new(err.to_string()))
+        }
+    }
+}
+
+/// A step that ends the handler execution and returns a value, either the value of a
+/// variable, an inline value template or several variables merged into an object.
+#[derive(Debug, Deserialize)]
+struct ReturnStep {
+    /// Optional: The name of the variable in the execution context whose value will be returned
+    /// as the result of the handler.
+    output: Option<String>,
+    /// Optional: A value template returned after replacing its `${name}` placeholders
+    /// with the values of the variables.
+    value: Option<serde_json::Value>,
+    /// Optional: A JSON or YAML fixture file holding the value template, read once when
+    /// the step is created.
+    file: Option<PathBuf>,
+    /// Optional: The names of the variables returned as the fields of an object.
+    #[serde(default)]
+    merge: Vec<String>,
+    /// Whether the remaining steps of the handler are skipped. Defaults to `false`.
+    #[serde(default)]
+    early: bool,
+}
+
+impl ReturnStep {
+    /// Returns the value to return.
+    fn value(&self, exec: &ExecutionContext) -> Result<serde_json::Value, TerminalError> {
+        let variable = |name: &str| {
+            exec.get_variable(name)
+                .ok_or_else(|| TerminalError::new(format!("unknown variable {name}")))
+        };
+
+        if let Some(output) = &self.output {
+            return returned_value(variable(output)?);
+        }
+
+        if let Some(value) = &self.value {
+            return template::render(value, exec)
+                .map_err(|err| TerminalError::new(err.to_string()));
+        }
+
+        let mut fields = serde_json::Map::new();
+        for name in &self.merge {
+            fields.insert(name.clone(), returned_value(variable(name)?)?);
+        }
+
+        Ok(serde_json::Value::Object(fields))
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for ReturnStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        let mut inputs: Vec<&str> = self.output.as_deref().into_iter().collect();
+        if let Some(value) = &self.value {
+            inputs.extend(template::variables(value));
+        }
+        inputs.extend(self.merge.iter().map(String::as_str));
+
+        inputs
+    }
+
+    fn ends_handler(&self) -> bool {
+        self.early
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let value = self.value(exec)?;
+
+        exec.return_value(value);
+        if self.early {
+            exec.finish();
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a JSON or YAML fixture file.
+async fn load_fixture(path: &Path) -> anyhow::Result<serde_json::Value> {
+    let source = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("failed to read {}", path.display()))?;
+
+    serde_yaml::from_str(&source).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Factory for creating `BuildOutputStep` instances.
+struct BuildOutput;
+
+#[async_trait::async_trait]
+impl StepFactory for BuildOutput {
+    fn schema(&self) -> Schema {
+        Schema::new().required(
+            "fields",
+            ParamType::list(ParamType::Object(
+                Schema::new()
+                    .required("path", ParamType::JsonPath)
+                    .optional("variable", ParamType::String)
+                    .optional("value", ParamType::Any),
+            )),
+        )
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: BuildOutputStep = serde_yaml::from_value(params)?;
+
+        for (idx, field) in step.fields.iter().enumerate() {
+            if field.variable.is_some() == field.value.is_some() {
+                return Err(StepError::InvalidParameter {
+                    field: format!("fields[{idx}].variable"),
+                    reason: "exactly one of `variable` or `value` must be set".to_owned(),
+                });
+            }
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// A single field set by a [`BuildOutputStep`].
+#[derive(Debug, Deserialize)]
+struct OutputField {
+    /// The path of the field in the output.
+    path: JsonPath,
+    /// The variable whose value is set.
+    variable: Option<String>,
+    /// The value set, with `${name}` placeholders replaced by the variables.
+    value: Option<serde_json::Value>,
+}
+
+/// A step that sets fields of the handler output, so the output can be assembled across
+/// several steps. The built output is returned if no `return` step sets a value.
+#[derive(Debug, Deserialize)]
+struct BuildOutputStep {
+    /// The fields to set, in order.
+    fields: Vec<OutputField>,
+}
+
+#[async_trait::async_trait]
+impl Step for BuildOutputStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        self.fields
+            .iter()
+            .flat_map(|field| {
+                let mut inputs: Vec<&str> = field.variable.as_deref().into_iter().collect();
+                if let Some(value) = &field.value {
+                    inputs.extend(template::variables(value));
+                }
+                inputs
+            })
+            .collect()
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        Ok(self.build(exec)?)
+    }
+}
+
+impl BuildOutputStep {
+    /// Sets the fields in the output, their values as plain JSON.
+    fn build(&self, exec: &mut ExecutionContext) -> Result<(), TerminalError> {
+        for field in &self.fields {
+            let value = match (&field.variable, &field.value) {
+                (Some(name), _) => exec
+                    .get_variable(name)
+                    .cloned()
+                    .map(serde_json::Value::from)
+                    .ok_or_else(|| TerminalError::new(format!("unknown variable {name}")))?,
+                (None, Some(value)) => template::render(value, exec)
+                    .map_err(|err| TerminalError::new(err.to_string()))?,
+                (None, None) => unreachable!("checked when the step is created"),
+            };
+
+            exec.set_output_field(&field.path, value)
+                .map_err(|err| TerminalError::new(err.to_string()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `DumpStep` instances.
+struct Dump;
+
+#[async_trait::async_trait]
+impl StepFactory for Dump {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("output", ParamType::String)
+            .optional("log", ParamType::Boolean)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: DumpStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A debugging step serializing all the variables and the pending return value
+/// into a JSON object.
+#[derive(Debug, Deserialize)]
+struct DumpStep {
+    /// Optional: The name of the variable where the JSON text of the dump is stored.
+    output: Option<String>,
+    /// Whether the dump is logged. Defaults to `true`.
+    #[serde(default = "default_dump_log")]
+    log: bool,
+}
+
+fn default_dump_log() -> bool {
+    true
+}
+
+#[async_trait::async_trait]
+impl Step for DumpStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.output.as_deref().into_iter().collect()
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        vec![ALL_VARIABLES]
+    }
+
+    fn fire_and_forget(&self) -> bool {
+        true
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let dump = serde_json::json!({
+            "variables": exec.variables(),
+            "return": exec.pending_return().map(|value| value.0),
+        })
+        .to_string();
+
+        if self.log {
+            tracing::info!(key = ctx.key(), "Execution context: {dump}");
+        }
+
+        if let Some(output) = &self.output {
+            exec.set(output, dump);
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `LogStep` instances.
+struct Log;
+
+/// The accepted values of the `level` parameter of a `log` step.
+const LOG_LEVELS: &[&str] = &["trace", "debug", "info", "warn", "error"];
+
+#[async_trait::async_trait]
+impl StepFactory for Log {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("message", ParamType::String)
+            .optional("level", ParamType::Enum(LOG_LEVELS))
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: LogStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// The level of a `log` step.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LogLevel {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+/// A step emitting a tracing event, to mark points of the execution in the logs of the
+/// mock service. The event is emitted again when the invocation is replayed.
+#[derive(Debug, Deserialize)]
+struct LogStep {
+    /// The message of the event, with `${name}` placeholders replaced by the text of the
+    /// variables.
+    message: String,
+    /// The level of the event. Defaults to `info`.
+    #[serde(default)]
+    level: LogLevel,
+}
+
+#[async_trait::async_trait]
+impl Step for LogStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        template::placeholders(&self.message)
+    }
+
+    fn fire_and_forget(&self) -> bool {
+        true
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let message = template::interpolate(&self.message, exec)
+            .map_err(|err| TerminalError::new(err.to_string()))?;
+
+        let key = ctx.key();
+        match self.level {
+            LogLevel::Trace => tracing::trace!(key, "{message}"),
+            LogLevel::Debug => tracing::debug!(key, "{message}"),
+            LogLevel::Info => tracing::info!(key, "{message}"),
+            LogLevel::Warn => tracing::warn!(key, "{message}"),
+            LogLevel::Error => tracing::error!(key, "{message}"),
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `ConstStep` instances.
+struct Const;
+
+#[async_trait::async_trait]
+impl StepFactory for Const {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("value", ParamType::Literal)
+            .required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: ConstStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that stores a literal value from the configuration in a variable.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct ConstStep {
+    /// The literal value to store.
+    #[serde_as(as = "serde_with::TryFromInto<serde_json::Value>")]
+    value: Variable,
+    /// The name of the variable in the execution context where the value will be stored.
+    output: String,
+}
+
+#[async_trait::async_trait]
+impl Step for ConstStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        exec.set(&self.output, self.value.clone());
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `PayloadStep` instances.
+struct Payload;
+
+/// The contents a [`PayloadStep`] can generate.
+const PAYLOAD_PATTERNS: &[&str] = &["random", "zeros", "repeat"];
+
+#[async_trait::async_trait]
+impl StepFactory for Payload {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("size", ParamType::Size)
+            .optional("pattern", ParamType::Enum(PAYLOAD_PATTERNS))
+            .optional("text", ParamType::String)
+            .required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: PayloadStep = serde_yaml::from_value(params)?;
+
+        // the quotes of the JSON string
+        if !(2..=MAX_PAYLOAD_SIZE.bytes()).contains(&step.size.bytes()) {
+            return Err(StepError::InvalidParameter {
+                field: "size".to_owned(),
+                reason: format!("must be between 2 bytes and {MAX_PAYLOAD_SIZE}"),
+            });
+        }
+
+        match (step.pattern, &step.text) {
+            (PayloadPattern::Repeat, None) => Err(StepError::InvalidParameter {
+                field: "text".to_owned(),
+                reason: "required by the `repeat` pattern".to_owned(),
+            }),
+            (PayloadPattern::Repeat, Some(text))
+                if text.is_empty()
+                    || !text.is_ascii()
+                    || serde_json::to_string(text).unwrap_or_default().len() != text.len() + 2 =>
+            {
+                Err(StepError::InvalidParameter {
+                    field: "text".to_owned(),
+                    reason: "must be non-empty ASCII text without characters escaped in JSON"
+                        .to_owned(),
+                })
+            }
+            (PayloadPattern::Repeat, Some(_)) => Ok(Box::new(step)),
+            (_, Some(_)) => Err(StepError::InvalidParameter {
+                field: "text".to_owned(),
+                reason: "only used by the `repeat` pattern".to_owned(),
+            }),
+            (_, None) => Ok(Box::new(step)),
+        }
+    }
+}
+
+/// The maximum size of the payload generated by a [`PayloadStep`] (1 GiB).
+const MAX_PAYLOAD_SIZE: Size = Size(1 << 30);
+
+/// The content of the payload generated by a [`PayloadStep`].
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PayloadPattern {
+    /// Random alphanumeric characters.
+    #[default]
+    Random,
+    /// Only `0` characters.
+    Zeros,
+    /// The configured text, repeated.
+    Repeat,
+}
+
+/// A step that generates a string of a given serialized size, to produce return values and
+/// call inputs of arbitrary size.
+#[derive(Debug, Deserialize)]
+struct PayloadStep {
+    /// The size of the JSON string, including its quotes.
+    size: Size,
+    #[serde(default)]
+    pattern: PayloadPattern,
+    /// The text repeated by [`PayloadPattern::Repeat`].
+    text: Option<String>,
+    /// The variable the payload is stored in.
+    output: String,
+}
+
+impl PayloadStep {
+    /// Generates the payload, failing if it can not be allocated. Random payloads are
+    /// derived from the seed, so a replay generates the same payload without journaling it.
+    fn generate(&self, seed: u64) -> Result<String, TryReserveError> {
+        let len = usize::try_from(self.size.bytes().saturating_sub(2)).unwrap_or(usize::MAX);
+
+        let mut payload = String::new();
+        payload.try_reserve_exact(len)?;
+        match self.pattern {
+            PayloadPattern::Random => payload.extend(
+                StdRng::seed_from_u64(seed)
+                    .sample_iter(rand::distr::Alphanumeric)
+                    .take(len)
+                    .map(char::from),
+            ),
+            PayloadPattern::Zeros => payload.extend(std::iter::repeat_n('0', len)),
+            PayloadPattern::Repeat => payload.extend(
+                self.text
+                    .as_deref()
+                    .unwrap_or_default()
+                    .chars()
+                    .cycle()
+                    .take(len),
+            ),
+        }
+
+        Ok(payload)
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for PayloadStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let payload = self
+            .generate(exec.draw_seed(ctx.random_seed()))
+            .map_err(|err| StepFailure::from(format!("failed to allocate {}: {err}", self.size)))?;
+        exec.set(&self.output, payload);
+        Ok(())
+    }
+}
+
+/// Factory for creating `TemplateStep` instances.
+struct Template;
+
+#[async_trait::async_trait]
+impl StepFactory for Template {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("template", ParamType::String)
+            .required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: TemplateConfig = serde_yaml::from_value(params)?;
+
+        let paths = template::placeholders(&config.template)
+            .into_iter()
+            .filter(|name| name.starts_with('$'))
+            .map(|name| {
+                let path = name.parse().map_err(|err| StepError::InvalidParameter {
+                    field: "template".to_owned(),
+                    reason: format!("invalid input path `{name}`: {err}"),
+                })?;
+                Ok((name.to_owned(), path))
+            })
+            .collect::<Result<_, StepError>>()?;
+
+        Ok(Box::new(TemplateStep {
+            template: config.template,
+            paths,
+            output: config.output,
+        }))
+    }
+}
+
+/// The configuration of a [`TemplateStep`].
+#[derive(Debug, Deserialize)]
+struct TemplateConfig {
+    template: String,
+    output: String,
+}
+
+/// A step that renders a string template and stores the result in a variable.
+///
+/// The `${name}` placeholders are replaced by the text of the variables, while the
+/// placeholders starting with `$` (e.g. `${$.user.id}`) are JSONPaths replaced by the
+/// selected element of the handler input.
+struct TemplateStep {
+    template: String,
+    /// The input placeholders of the template with their parsed path.
+    paths: Vec<(String, JsonPath)>,
+    /// The name of the variable in the execution context where the text will be stored.
+    output: String,
+}
+
+#[async_trait::async_trait]
+impl Step for TemplateStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        template::placeholders(&self.template)
+            .into_iter()
+            .filter(|name| !name.starts_with('$'))
+            .collect()
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        // the input elements are exposed to the template as variables named after their path
+        let mut scope = exec.scope();
+        for (name, path) in &self.paths {
+            let value = path
+                .select(&input.0)
+                .ok_or_else(|| TerminalError::new(format!("input {path} not found")))?;
+            let value = match value {
+                serde_json::Value::String(text) => Variable::String(text.clone()),
+                value => Variable::String(value.to_string()),
+            };
+            scope.set(name, value);
+        }
+
+        let text = template::interpolate(&self.template, &scope)
+            .map_err(|err| TerminalError::new(err.to_string()))?;
+        exec.set(&self.output, text);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `ExtractStep` instances.
+struct Extract;
+
+#[async_trait::async_trait]
+impl StepFactory for Extract {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("path", ParamType::JsonPath)
+            .optional("variable", ParamType::String)
+            .optional("default", ParamType::Literal)
+            .required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: ExtractStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that selects a literal value in the handler input, or in the JSON document held
+/// by a variable, and stores it in a variable.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct ExtractStep {
+    /// The JSONPath selecting the value.
+    path: JsonPath,
+    /// Optional: The name of the variable holding the JSON document (as text, bytes, a list
+    /// or an object) the value is selected in. If `None`, the value is selected in the handler input.
+    variable: Option<String>,
+    /// Optional: The literal value stored if the path selects nothing. If `None`, `null`
+    /// is stored.
+    #[serde_as(as = "Option<serde_with::TryFromInto<serde_json::Value>>")]
+    #[serde(default)]
+    default: Option<Variable>,
+    /// The name of the variable in the execution context where the value will be stored.
+    output: String,
+}
+
+#[async_trait::async_trait]
+impl Step for ExtractStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        self.variable.as_deref().into_iter().collect()
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let document = match &self.variable {
+            None => None,
+            Some(name) => {
+                let parsed = match exec.get_variable(name) {
+                    Some(Variable::String(text)) => serde_json::from_str(text),
+                    Some(Variable::Bytes(bytes)) => serde_json::from_slice(bytes),
+                    Some(variable @ (Variable::Array(_) | Variable::Object(_))) => {
+                        Ok(variable.clone().into())
+                    }
+                    _ => {
+                        return Err(TerminalError::new(format!(
+                            "variable {name} does not hold a JSON document"
+                        ))
+                        .into())
+                    }
+                };
+                Some(parsed.map_err(|err| {
+                    TerminalError::new(format!("variable {name} is not valid JSON: {err}"))
+                })?)
+            }
+        };
+
+        let value = match self.path.select(document.as_ref().unwrap_or(&input.0)) {
+            Some(value) => Variable::try_from(value.clone())
+                .map_err(|_| TerminalError::new(format!("{} is not a literal value", self.path)))?,
+            None => self.default.clone().unwrap_or(Variable::Null),
+        };
+        exec.set(&self.output, value);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `IncrementStep` instances.
+struct Increment;
+
+#[async_trait::async_trait]
+impl StepFactory for Increment {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("input", ParamType::String)
+            .optional("steps", ParamType::Integer)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: IncrementStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that increments a numerical value stored in a variable.
+/// If the variable doesn't exist or is not a number, it defaults to 0 before incrementing.
+#[derive(Debug, Deserialize)]
+struct IncrementStep {
+    /// The name of the variable in the execution context holding the numerical value to increment.
+    /// The result is stored back in the same variable.
+    input: String,
+    /// The integer amount to increment by. Defaults to `1`.
+    #[serde(default = "default_steps")]
+    steps: isize,
+}
+
+fn default_steps() -> isize {
+    1
+}
+
+#[async_trait::async_trait]
+impl Step for IncrementStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let mut value = exec
+            .get::<isize>(&self.input)
+            .unwrap_or(Ok(0))
+            .context("Failed to get variable")?;
+
+        value += self.steps;
+
+        exec.set(&self.input, value);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `SequenceStep` instances.
+struct Sequence;
+
+#[async_trait::async_trait]
+impl StepFactory for Sequence {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("key", ParamType::String)
+            .required("output", ParamType::String)
+            .optional("start", ParamType::Integer)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: SequenceStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that issues the next number of a monotonically increasing sequence persisted
+/// in the Restate state of the current key, and stores it in a variable.
+/// This step is only valid for services of type `VIRTUAL_OBJECT` or `WORKFLOW`.
+#[derive(Debug, Deserialize)]
+struct SequenceStep {
+    /// The state key holding the last issued number.
+    key: String,
+    /// The name of the variable where the issued number will be stored.
+    output: String,
+    /// The first number of the sequence. Defaults to `1`.
+    #[serde(default = "default_sequence_start")]
+    start: isize,
+}
+
+fn default_sequence_start() -> isize {
+    1
+}
+
+impl SequenceStep {
+    /// Returns the number following the `last` number stored in the state, or the start
+    /// of the sequence if none is stored yet.
+    fn next(&self, last: Option<Variable>) -> Result<isize, TerminalError> {
+        match last {
+            Some(Variable::Integer(last)) => last.checked_add(1).ok_or_else(|| {
+                TerminalError::new(format!("the sequence {} is exhausted", self.key))
+            }),
+            Some(_) => Err(TerminalError::new(format!(
+                "state key {} does not hold an integer",
+                self.key
+            ))),
+            None => Ok(self.start),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for SequenceStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_state_write(service_type, handler_type)
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let next = self.next(ctx.get::<Variable>(&self.key).await?)?;
+
+        ctx.set(&self.key, Variable::Integer(next));
+        exec.set(&self.output, next);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `CounterStep` instances.
+struct Counter;
+
+#[async_trait::async_trait]
+impl StepFactory for Counter {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("key", ParamType::String)
+            .optional("amount", ParamType::Number)
+            .optional("amount_variable", ParamType::String)
+            .optional("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: CounterStep = serde_yaml::from_value(params)?;
+
+        if step.amount.is_some() && step.amount_variable.is_some() {
+            return Err(StepError::InvalidParameter {
+                field: "amount_variable".to_owned(),
+                reason: "can not be used together with `amount`".to_owned(),
+            });
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that adds an amount to a numeric counter persisted in the Restate state of the
+/// current key. A missing counter starts at 0.
+/// This step is only valid for services of type `VIRTUAL_OBJECT` or `WORKFLOW`.
+#[derive(Debug, Deserialize)]
+struct CounterStep {
+    /// The state key holding the counter.
+    key: String,
+    /// Optional: The amount added to the counter, integer or float. Defaults to `1`.
+    amount: Option<serde_json::Number>,
+    /// Optional: The name of the variable holding the amount, instead of `amount`.
+    amount_variable: Option<String>,
+    /// Optional: The name of the variable where the new value will be stored.
+    output: Option<String>,
+}
+
+impl CounterStep {
+    /// Adds the amount to the counter, `None` if either is not a number or on overflow.
+    fn add(counter: &Variable, amount: &Variable) -> Option<Variable> {
+        match (counter, amount) {
+            (Variable::Integer(counter), Variable::Integer(amount)) => {
+                counter.checked_add(*amount).map(Variable::Integer)
+            }
+            (Variable::Integer(counter), Variable::Number(amount)) => {
+                Some(Variable::Number(*counter as f64 + amount))
+            }
+            (Variable::Number(counter), Variable::Integer(amount)) => {
+                Some(Variable::Number(counter + *amount as f64))
+            }
+            (Variable::Number(counter), Variable::Number(amount)) => {
+                Some(Variable::Number(counter + amount))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for CounterStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_state_write(service_type, handler_type)
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.output.as_deref().into_iter().collect()
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        self.amount_variable.as_deref().into_iter().collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let amount = match (&self.amount_variable, &self.amount) {
+            (Some(name), _) => exec
+                .get_variable(name)
+                .cloned()
+                .ok_or_else(|| TerminalError::new(format!("unknown variable {name}")))?,
+            (None, Some(amount)) => Variable::try_from(serde_json::Value::Number(amount.clone()))
+                .map_err(|err| TerminalError::new(err.to_string()))?,
+            (None, None) => Variable::Integer(1),
+        };
+
+        let counter = ctx
+            .get::<Variable>(&self.key)
+            .await?
+            .unwrap_or(Variable::Integer(0));
+
+        let value = CounterStep::add(&counter, &amount).ok_or_else(|| {
+            TerminalError::new(format!(
+                "can not add {} to the counter {} holding {}",
+                template::text_of(&amount),
+                self.key,
+                template::text_of(&counter)
+            ))
+        })?;
+
+        ctx.set(&self.key, value.clone());
+        if let Some(output) = &self.output {
+            exec.set(output, value);
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds the target of a request to `service`/`handler`. For virtual objects and workflows,
+/// the `key` defaults to the key of the current invocation.
+fn request_target(
+    ctx: &WorkflowContext<'_>,
+    target_type: ServiceType,
+    service: &str,
+    handler: &str,
+    key: Option<&str>,
+) -> RequestTarget {
+    let key = || key.unwrap_or(ctx.key()).to_owned();
+
+    match target_type {
+        ServiceType::Service => RequestTarget::Service {
+            name: service.to_owned(),
+            handler: handler.to_owned(),
+        },
+        ServiceType::VirtualObject => RequestTarget::Object {
+            name: service.to_owned(),
+            key: key(),
+            handler: handler.to_owned(),
+        },
+        ServiceType::Workflow => RequestTarget::Workflow {
+            name: service.to_owned(),
+            key: key(),
+            handler: handler.to_owned(),
+        },
+    }
+}
+
+/// Where a part of a [`CallTarget`] is read from on every invocation: a variable, or the
+/// handler input if it starts with `$`.
+#[derive(Debug)]
+enum TargetSource {
+    Variable(String),
+    Input(JsonPath),
+}
+
+impl TryFrom<String> for TargetSource {
+    type Error = json::JsonPathError;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        if source.starts_with('$') {
+            source.parse().map(TargetSource::Input)
+        } else {
+            Ok(TargetSource::Variable(source))
+        }
+    }
+}
+
+impl TargetSource {
+    /// Returns the text of the source, failing with a terminal error if it is missing.
+    fn resolve(&self, exec: &ExecutionContext, input: &JsonValue) -> Result<String, TerminalError> {
+        let text = match self {
+            TargetSource::Variable(name) => match exec.get_variable(name) {
+                Some(Variable::Null) | None => None,
+                Some(value) => Some(template::text_of(value)),
+            },
+            TargetSource::Input(path) => match path.select(&input.0) {
+                Some(serde_json::Value::Null) | None => None,
+                Some(serde_json::Value::String(text)) => Some(text.clone()),
+                Some(value) => Some(value.to_string()),
+            },
+        };
+
+        text.ok_or_else(|| {
+            let source = match self {
+                TargetSource::Variable(name) => format!("variable {name}"),
+                TargetSource::Input(path) => format!("input {path}"),
+            };
+            TerminalError::new(format!("call target {source} is not set"))
+        })
+    }
+}
+
+/// The target of a `call` or `send` step. The service, handler and key are either static
+/// or read from a [`TargetSource`] on every invocation (e.g. `service_from`), to mock
+/// routers and dispatchers.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct CallTarget {
+    /// Specifies the type of the target handler to be called (SERVICE, VIRTUAL_OBJECT, or WORKFLOW).
+    target_type: ServiceType,
+    /// The string name of the target service, virtual object, or workflow.
+    service: Option<String>,
+    #[serde_as(as = "Option<serde_with::TryFromInto<String>>")]
+    #[serde(default)]
+    service_from: Option<TargetSource>,
+    /// The string name of the target handler to invoke on the specified service.
+    handler: Option<String>,
+    #[serde_as(as = "Option<serde_with::TryFromInto<String>>")]
+    #[serde(default)]
+    handler_from: Option<TargetSource>,
+    /// The string key to use when `target_type` is `VIRTUAL_OBJECT` or `WORKFLOW`.
+    /// If the current service (caller) is a `VIRTUAL_OBJECT` or `WORKFLOW` and no key is set,
+    /// the key of the current service instance is used.
+    /// Required if `target_type` is `VIRTUAL_OBJECT`/`WORKFLOW` and the caller is `SERVICE`,
+    /// or if a specific key different from the caller's key is needed.
+    key: Option<String>,
+    #[serde_as(as = "Option<serde_with::TryFromInto<String>>")]
+    #[serde(default)]
+    key_from: Option<TargetSource>,
+}
+
+impl CallTarget {
+    fn schema() -> Schema {
+        Schema::new()
+            .required("target_type", ParamType::Enum(SERVICE_TYPES))
+            .optional("service", ParamType::String)
+            .optional("service_from", ParamType::String)
+            .optional("handler", ParamType::String)
+            .optional("handler_from", ParamType::String)
+            .optional("key", ParamType::String)
+            .optional("key_from", ParamType::String)
+    }
+
+    /// Checks that the service and the handler are set exactly once, and the key at most once.
+    fn check(&self) -> Result<(), StepError> {
+        let invalid = |field: &str, reason: &str| StepError::InvalidParameter {
+            field: field.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        if self.service.is_some() == self.service_from.is_some() {
+            return Err(invalid(
+                "service",
+                "exactly one of `service` or `service_from` must be set",
+            ));
+        }
+        if self.handler.is_some() == self.handler_from.is_some() {
+            return Err(invalid(
+                "handler",
+                "exactly one of `handler` or `handler_from` must be set",
+            ));
+        }
+        if self.key.is_some() && self.key_from.is_some() {
+            return Err(invalid("key_from", "can not be used together with `key`"));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the target for the linter, `None` if it is only known at runtime.
+    fn target(&self, kind: InvocationKind) -> Option<Target<'_>> {
+        Some(Target {
+            service: self.service.as_deref()?,
+            handler: self.handler.as_deref()?,
+            kind,
+        })
+    }
+
+    /// Returns the names of the variables the target is read from.
+    fn inputs(&self) -> impl Iterator<Item = &str> {
+        [&self.service_from, &self.handler_from, &self.key_from]
+            .into_iter()
+            .filter_map(|source| match source {
+                Some(TargetSource::Variable(name)) => Some(name.as_str()),
+                _ => None,
+            })
+    }
+
+    /// Resolves the target of the current invocation.
+    fn resolve(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<RequestTarget, TerminalError> {
+        let key = Self::part(&self.key, &self.key_from, exec, input)?;
+        self.resolve_with_key(ctx, exec, input, key)
+    }
+
+    /// Resolves the target of the current invocation with the given key instead of its own.
+    fn resolve_with_key(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &ExecutionContext,
+        input: &JsonValue,
+        key: Option<String>,
+    ) -> Result<RequestTarget, TerminalError> {
+        let service = Self::part(&self.service, &self.service_from, exec, input)?;
+        let handler = Self::part(&self.handler, &self.handler_from, exec, input)?;
+
+        Ok(request_target(
+            ctx,
+            self.target_type,
+            &service.unwrap_or_default(),
+            &handler.unwrap_or_default(),
+            key.as_deref(),
+        ))
+    }
+
+    /// Returns the static `value` of a part of the target, or else the text of its `source`.
+    fn part(
+        value: &Option<String>,
+        source: &Option<TargetSource>,
+        exec: &ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<Option<String>, TerminalError> {
+        match source {
+            Some(source) => source.resolve(exec, input).map(Some),
+            None => Ok(value.clone()),
+        }
+    }
+}
+
+struct Call;
+
+#[async_trait::async_trait]
+impl StepFactory for Call {
+    fn schema(&self) -> Schema {
+        CallTarget::schema()
+            .optional("input", ParamType::String)
+            .optional("output", ParamType::String)
+            .optional("idempotency_key", ParamType::String)
+            .optional("idempotency_field", ParamType::JsonPath)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: CallStep = serde_yaml::from_value(params)?;
+        step.target.check()?;
+
+        if step.idempotency_key.is_some() && step.idempotency_field.is_some() {
+            return Err(StepError::InvalidParameter {
+                field: "idempotency_field".to_owned(),
+                reason: "can not be used together with `idempotency_key`".to_owned(),
+            });
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that makes a call to another handler, which can be part of any service,
+/// virtual object, or workflow defined within the mock service configuration.
+///
+/// This step allows for complex interactions and chaining of logic across different
+/// components of the mock setup.
+#[derive(Debug, Deserialize)]
+struct CallStep {
+    #[serde(flatten)]
+    target: CallTarget,
+    /// Optional: The name of a variable in the execution context whose value will be sent as input.
+    /// If `None` or the variable doesn't exist, `null` is sent.
+    input: Option<String>,
+    /// Optional: The name of a variable in the execution context to store the call's result.
+    /// If `None`, the result is discarded.
+    output: Option<String>,
+    /// Optional: The idempotency key of the call, with `${name}` placeholders replaced by
+    /// the text of the variables.
+    idempotency_key: Option<String>,
+    /// Optional: A JSONPath selecting the idempotency key of the call in the handler input.
+    idempotency_field: Option<JsonPath>,
+}
+
+impl CallStep {
+    fn idempotency_key(
+        &self,
+        exec: &ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<Option<String>, TerminalError> {
+        if let Some(key) = &self.idempotency_key {
+            return template::interpolate(key, exec)
+                .map(Some)
+                .map_err(|err| TerminalError::new(err.to_string()));
+        }
+
+        let Some(field) = &self.idempotency_field else {
+            return Ok(None);
+        };
+
+        match field.select(&input.0) {
+            Some(serde_json::Value::String(key)) => Ok(Some(key.clone())),
+            Some(serde_json::Value::Null) | None => Err(TerminalError::new(format!(
+                "idempotency key {field} not found in the input"
+            ))),
+            Some(key) => Ok(Some(key.to_string())),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for CallStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.output.as_deref().into_iter().collect()
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        let mut inputs: Vec<&str> = self.input.as_deref().into_iter().collect();
+        inputs.extend(self.target.inputs());
+        if let Some(key) = &self.idempotency_key {
+            inputs.extend(template::placeholders(key));
+        }
+
+        inputs
+    }
+
+    fn targets(&self) -> Vec<Target<'_>> {
+        self.target
+            .target(InvocationKind::Call)
+            .into_iter()
+            .collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let request_target = self.target.resolve(ctx, exec, input)?;
+
+        let req = self
+            .input
+            .as_ref()
+            .and_then(|input| exec.get_variable(input))
+            .cloned()
+            .unwrap_or(Variable::Null);
+
+        let mut request = ctx.request(request_target, req);
+        if let Some(key) = self.idempotency_key(exec, input)? {
+            request = request.idempotency_key(key);
+        }
+
+        let res: Variable = request.call().await?;
+        if let Some(output) = self.output.as_ref() {
+            exec.set(output, res);
+        }
+
+        Ok(())
+    }
+}
+
+struct Send;
+
+#[async_trait::async_trait]
+impl StepFactory for Send {
+    fn schema(&self) -> Schema {
+        CallTarget::schema()
+            .optional("input", ParamType::String)
+            .optional("delay", ParamType::String)
+            .optional("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: SendStep = serde_yaml::from_value(params)?;
+        step.target.check()?;
+
+        if let Some(delay) = &step.delay {
+            if template::placeholders(delay).is_empty() {
+                humantime::parse_duration(delay).map_err(|err| StepError::InvalidParameter {
+                    field: "delay".to_owned(),
+                    reason: format!("expected a duration (e.g. 500ms, 2s): {err}"),
+                })?;
+            }
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that sends a call to another handler, which can be part of any service,
+/// virtual object, or workflow defined within the mock service configuration.
+///
+/// This step allows for complex interactions and chaining of logic across different
+/// components of the mock setup.
+///
+/// Similar to [`CallStep`] but does not wait for output
+#[derive(Debug, Deserialize)]
+struct SendStep {
+    #[serde(flatten)]
+    target: CallTarget,
+    /// Optional: The name of a variable in the execution context whose value will be sent as input.
+    /// If `None` or the variable doesn't exist, `null` is sent.
+    input: Option<String>,
+    /// Optional: The delay after which the call is invoked, as a human-readable duration
+    /// string like "10s", with `${name}` placeholders replaced by the text of the variables.
+    /// If `None`, the call is invoked immediately.
+    delay: Option<String>,
+    /// Optional: The name of the variable where the ID of the invocation will be stored.
+    output: Option<String>,
+}
+
+impl SendStep {
+    /// Returns the ID of the sent invocation if it is stored in the `output` variable.
+    async fn invocation_id(
+        &self,
+        handle: impl InvocationHandle,
+    ) -> Result<Option<String>, TerminalError> {
+        match self.output {
+            Some(_) => handle.invocation_id().await.map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for SendStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        let mut inputs: Vec<&str> = self.input.as_deref().into_iter().collect();
+        inputs.extend(self.target.inputs());
+        if let Some(delay) = &self.delay {
+            inputs.extend(template::placeholders(delay));
+        }
+
+        inputs
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.output.as_deref().into_iter().collect()
+    }
+
+    fn targets(&self) -> Vec<Target<'_>> {
+        self.target
+            .target(InvocationKind::Send)
+            .into_iter()
+            .collect()
+    }
+
+    fn fire_and_forget(&self) -> bool {
+        true
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let request_target = self.target.resolve(ctx, exec, input)?;
+
+        let req = self
+            .input
+            .as_ref()
+            .and_then(|input| exec.get_variable(input))
+            .cloned()
+            .unwrap_or(Variable::Null);
+
+        let request = ctx.request::<_, ()>(request_target, req);
+        let invocation_id = match &self.delay {
+            Some(delay) => {
+                let delay = template::interpolate(delay, exec)
+                    .map_err(|err| TerminalError::new(err.to_string()))?;
+                let delay = humantime::parse_duration(&delay)
+                    .map_err(|err| TerminalError::new(format!("invalid delay '{delay}': {err}")))?;
+                self.invocation_id(request.send_after(delay)).await?
+            }
+            None => self.invocation_id(request.send()).await?,
+        };
+
+        if let (Some(output), Some(id)) = (&self.output, invocation_id) {
+            exec.set(output, Variable::String(id));
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `SendBatchStep` instances.
+struct SendBatch;
+
+#[async_trait::async_trait]
+impl StepFactory for SendBatch {
+    fn schema(&self) -> Schema {
+        CallTarget::schema()
+            .optional("input", ParamType::String)
+            .optional("count", ParamType::Integer)
+            .optional("count_variable", ParamType::String)
+            .optional("index", ParamType::String)
+            .optional("interval", ParamType::Duration)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: SendBatchStep = serde_yaml::from_value(params)?;
+        step.target.check()?;
+
+        if step.count.is_some() == step.count_variable.is_some() {
+            return Err(StepError::InvalidParameter {
+                field: "count".to_owned(),
+                reason: "exactly one of `count` or `count_variable` must be set".to_owned(),
+            });
+        }
+        if step.count.is_some_and(|count| count > MAX_BATCH_SIZE) {
+            return Err(StepError::InvalidParameter {
+                field: "count".to_owned(),
+                reason: format!("must be at most {MAX_BATCH_SIZE}"),
+            });
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// The maximum number of sends of a [`SendBatchStep`].
+const MAX_BATCH_SIZE: usize = 10_000;
+
+/// A step that sends a burst of one-way calls, e.g. to generate load against another
+/// mocked virtual object from a single ingress call.
+///
+/// The `key` of the target is a template, with `${name}` placeholders replaced by the text
+/// of the variables, including the `index` variable. Defaults to the key of the caller.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct SendBatchStep {
+    #[serde(flatten)]
+    target: CallTarget,
+    /// Optional: The name of the variable sent as input to every call, `null` if not set.
+    input: Option<String>,
+    /// Optional: The number of sends.
+    count: Option<usize>,
+    /// Optional: The name of the integer variable holding the number of sends, instead of
+    /// `count`.
+    count_variable: Option<String>,
+    /// The name of the variable holding the index of the current send, starting at 0.
+    /// Defaults to `index`.
+    #[serde(default = "default_batch_index")]
+    index: String,
+    /// Optional: The pacing of the sends. The send with index `i` is delayed by
+    /// `i * interval`, so the step itself does not wait.
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    interval: Option<humantime::Duration>,
+}
+
+fn default_batch_index() -> String {
+    "index".to_owned()
+}
+
+impl SendBatchStep {
+    /// Returns the number of sends, failing if it exceeds [`MAX_BATCH_SIZE`].
+    fn count(&self, exec: &ExecutionContext) -> Result<usize, TerminalError> {
+        let count = match &self.count_variable {
+            Some(name) => exec
+                .get::<isize>(name)
+                .ok_or_else(|| TerminalError::new(format!("unknown variable {name}")))?
+                .map_err(|_| TerminalError::new(format!("variable {name} is not an integer")))?
+                .max(0) as usize,
+            None => self.count.unwrap_or_default(),
+        };
+
+        if count > MAX_BATCH_SIZE {
+            return Err(TerminalError::new(format!(
+                "can not send a batch of {count} calls, the maximum is {MAX_BATCH_SIZE}"
+            )));
+        }
+
+        Ok(count)
+    }
+
+    /// Returns the rendered `key` of every send of the batch, `None` if the step has no
+    /// `key` template. The keys are rendered one at a time, as the sends go.
+    fn keys<'a>(
+        &'a self,
+        exec: &ExecutionContext,
+    ) -> Result<impl Iterator<Item = Result<Option<String>, TerminalError>> + 'a, TerminalError>
+    {
+        let count = self.count(exec)?;
+
+        let mut scope = exec.scope();
+        Ok((0..count).map(move |idx| {
+            scope.set(&self.index, idx as isize);
+            self.target
+                .key
+                .as_ref()
+                .map(|key| template::interpolate(key, &scope))
+                .transpose()
+                .map_err(|err| TerminalError::new(err.to_string()))
+        }))
+    }
+
+    /// Returns the delay of the send with the given index, `None` if the sends are not
+    /// paced.
+    fn delay(&self, idx: usize) -> Result<Option<Duration>, TerminalError> {
+        let Some(interval) = self.interval else {
+            return Ok(None);
+        };
+
+        let idx = u32::try_from(idx)
+            .map_err(|_| TerminalError::new(format!("can not pace the send {idx} of the batch")))?;
+        Ok(Some(Duration::from(interval).saturating_mul(idx)))
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for SendBatchStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        let mut inputs: Vec<&str> = self
+            .input
+            .iter()
+            .chain(&self.count_variable)
+            .map(String::as_str)
+            .collect();
+        inputs.extend(self.target.inputs());
+        if let Some(key) = &self.target.key {
+            // the index is set by the step itself
+            inputs.extend(
+                template::placeholders(key)
+                    .into_iter()
+                    .filter(|name| *name != self.index),
+            );
+        }
+
+        inputs
+    }
+
+    fn targets(&self) -> Vec<Target<'_>> {
+        self.target
+            .target(InvocationKind::Send)
+            .into_iter()
+            .collect()
+    }
+
+    fn fire_and_forget(&self) -> bool {
+        true
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let req = self
+            .input
+            .as_ref()
+            .and_then(|input| exec.get_variable(input))
+            .cloned()
+            .unwrap_or(Variable::Null);
+
+        for (idx, key) in self.keys(exec)?.enumerate() {
+            let target = match key? {
+                Some(key) => self.target.resolve_with_key(ctx, exec, input, Some(key))?,
+                None => self.target.resolve(ctx, exec, input)?,
+            };
+
+            let request = ctx.request::<_, ()>(target, req.clone());
+            match self.delay(idx)? {
+                Some(delay) => {
+                    request.send_after(delay);
+                }
+                None => {
+                    request.send();
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `MirrorStep` instances.
+struct Mirror;
+
+#[async_trait::async_trait]
+impl StepFactory for Mirror {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("target_type", ParamType::Enum(SERVICE_TYPES))
+            .required("service", ParamType::String)
+            .required("handler", ParamType::String)
+            .optional("key", ParamType::String)
+            .optional("sample", ParamType::Number)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: MirrorStep = serde_yaml::from_value(params)?;
+
+        if !(0.0..=1.0).contains(&step.config.sample) {
+            return Err(StepError::InvalidParameter {
+                field: "sample".to_owned(),
+                reason: "expected a value between 0.0 and 1.0".to_owned(),
+            });
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that fire-and-forgets a copy of the handler input to another handler, to test
+/// shadow traffic patterns. Only a `sample` of the invocations is mirrored.
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+struct MirrorStep {
+    config: config::MirrorConfig,
+}
+
+impl MirrorStep {
+    /// Returns whether the invocation of the given random `seed` is mirrored.
+    fn sampled(&self, seed: u64) -> bool {
+        StdRng::seed_from_u64(seed).random_bool(self.config.sample)
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for MirrorStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        let config = &self.config;
+        if config.target_type != ServiceType::Service
+            && config.key.is_none()
+            && service_type == ServiceType::Service
+        {
+            return Err(StepError::InvalidServiceType(service_type));
+        }
+
+        Ok(())
+    }
+
+    fn targets(&self) -> Vec<Target<'_>> {
+        vec![Target {
+            service: &self.config.service,
+            handler: &self.config.handler,
+            kind: InvocationKind::Send,
+        }]
+    }
+
+    fn fire_and_forget(&self) -> bool {
+        true
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        // seeded by the invocation, so the decision is stable across retries, while every
+        // mirror step of the invocation draws its own
+        if !self.sampled(exec.draw_seed(ctx.random_seed())) {
+            return Ok(());
+        }
+
+        let config = &self.config;
+
+        let request_target = request_target(
+            ctx,
+            config.target_type,
+            &config.service,
+            &config.handler,
+            config.key.as_deref(),
+        );
+
+        ctx.request::<_, ()>(request_target, input.clone()).send();
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `AwaitWorkflowStep` instances.
+struct AwaitWorkflow;
+
+#[async_trait::async_trait]
+impl StepFactory for AwaitWorkflow {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("service", ParamType::String)
+            .optional("key", ParamType::String)
+            .optional("handler", ParamType::String)
+            .optional("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: AwaitWorkflowStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that waits for the result of a workflow identified by its service name and key,
+/// and stores that result in a variable.
+///
+/// Restate runs a workflow's `run` handler at most once per key, so invoking it again for a
+/// key that was already submitted (for example by another handler using [`SendStep`])
+/// attaches to the existing execution and resolves with its result instead of starting a
+/// new one.
+#[derive(Debug, Deserialize)]
+struct AwaitWorkflowStep {
+    /// The string name of the target workflow.
+    service: String,
+    /// The key of the workflow to wait for. If `None`, the key of the current service
+    /// instance is used. Required if the caller is a `SERVICE`.
+    key: Option<String>,
+    /// The name of the workflow's main handler. Defaults to `run`.
+    #[serde(default = "default_workflow_handler")]
+    handler: String,
+    /// Optional: The name of a variable in the execution context to store the workflow result.
+    /// If `None`, the result is discarded.
+    output: Option<String>,
+}
+
+fn default_workflow_handler() -> String {
+    "run".to_owned()
+}
+
+#[async_trait::async_trait]
+impl Step for AwaitWorkflowStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        if self.key.is_none() && service_type == ServiceType::Service {
+            return Err(StepError::InvalidServiceType(service_type));
+        }
+
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.output.as_deref().into_iter().collect()
+    }
+
+    fn targets(&self) -> Vec<Target<'_>> {
+        vec![Target {
+            service: &self.service,
+            handler: &self.handler,
+            kind: InvocationKind::Call,
+        }]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let request_target = RequestTarget::Workflow {
+            name: self.service.clone(),
+            key: self.key.clone().unwrap_or_else(|| ctx.key().to_string()),
+            handler: self.handler.clone(),
+        };
+
+        let res: Variable = ctx.request(request_target, Variable::Null).call().await?;
+        if let Some(output) = self.output.as_ref() {
+            exec.set(output, res);
+        }
+
+        Ok(())
+    }
+}
+
+/// The HTTP client of the steps reaching out of the mock service.
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+/// Factory for creating `AttachStep` instances.
+struct Attach;
+
+#[async_trait::async_trait]
+impl StepFactory for Attach {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("invocation_id", ParamType::String)
+            .optional("ingress_url", ParamType::String)
+            .optional("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: AttachStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that attaches to an existing invocation and waits for its result.
+///
+/// The SDK does not expose attaching to an invocation, so the step goes through the
+/// attach endpoint of the Restate ingress, in a `ctx.run()` so that the result is journaled.
+#[derive(Debug, Deserialize)]
+struct AttachStep {
+    /// The name of the variable holding the ID of the invocation.
+    invocation_id: String,
+    /// The URL of the Restate ingress. Defaults to `http://localhost:8080`.
+    #[serde(default = "default_ingress_url")]
+    ingress_url: String,
+    /// Optional: The name of the variable where the result of the invocation will be stored.
+    output: Option<String>,
+}
+
+fn default_ingress_url() -> String {
+    "http://localhost:8080".to_owned()
+}
+
+#[async_trait::async_trait]
+impl Step for AttachStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        vec![&self.invocation_id]
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.output.as_deref().into_iter().collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let Some(Variable::String(id)) = exec.get_variable(&self.invocation_id) else {
+            return Err(TerminalError::new(format!(
+                "variable {} does not hold an invocation ID",
+                self.invocation_id
+            ))
+            .into());
+        };
+
+        let url = format!(
+            "{}/restate/invocation/{id}/attach",
+            self.ingress_url.trim_end_matches('/')
+        );
+        let result: Variable = ctx
+            .run(|| async move {
+                let response = HTTP_CLIENT
+                    .get(&url)
+                    .send()
+                    .await
+                    .map_err(|err| StepFailure::from(err.to_string()))?;
+
+                let status = response.status();
+                let body = response
+                    .bytes()
+                    .await
+                    .map_err(|err| StepFailure::from(err.to_string()))?;
+
+                if !status.is_success() {
+                    let message = format!(
+                        "attach to {url} failed with {status}: {}",
+                        String::from_utf8_lossy(&body)
+                    );
+                    // a failed invocation is reported with a 5xx status by the ingress
+                    return Err(TerminalError::new_with_code(status.as_u16(), message).into());
+                }
+
+                if body.is_empty() {
+                    return Ok(Variable::Null);
+                }
+
+                serde_json::from_slice::<serde_json::Value>(&body)
+                    .ok()
+                    .and_then(|value| Variable::try_from(value).ok())
+                    .ok_or_else(|| {
+                        TerminalError::new("invocation result is not a literal value").into()
+                    })
+            })
+            .name("attach")
+            .await?;
+
+        if let Some(output) = &self.output {
+            exec.set(output, result);
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `HttpStep` instances.
+struct Http;
+
+/// The accepted values of the `method` parameter of an `http` step.
+const HTTP_METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE"];
+
+#[async_trait::async_trait]
+impl StepFactory for Http {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("url", ParamType::String)
+            .optional("method", ParamType::Enum(HTTP_METHODS))
+            .optional("headers", ParamType::Any)
+            .optional("body", ParamType::Any)
+            .optional("timeout", ParamType::Duration)
+            .optional("status_output", ParamType::String)
+            .optional("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: HttpStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// The response of an `http` step, as journaled.
+#[derive(Debug, serde::Serialize, Deserialize)]
+struct HttpResponse {
+    status: u16,
+    body: String,
+}
+
+/// A step that sends an HTTP request to an external endpoint, in a `ctx.run()` so that
+/// the response is journaled and a replayed invocation does not send the request again.
+///
+/// Network errors are retried, while every response (whatever its status) is recorded.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct HttpStep {
+    /// The URL of the request, with `${name}` placeholders replaced by the text of the
+    /// variables.
+    url: String,
+    /// The method of the request. Defaults to `GET`.
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    #[serde(default)]
+    method: reqwest::Method,
+    /// The headers of the request, their values with `${name}` placeholders replaced by
+    /// the text of the variables.
+    #[serde(default)]
+    headers: std::collections::BTreeMap<String, String>,
+    /// Optional: A value template sent as the JSON body of the request.
+    body: Option<serde_json::Value>,
+    /// The timeout of every attempt of the request. Defaults to `30s`.
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    #[serde(default = "default_io_timeout")]
+    timeout: humantime::Duration,
+    /// Optional: The name of the variable where the status code of the response will be stored.
+    status_output: Option<String>,
+    /// Optional: The name of the variable where the text of the response body will be stored.
+    output: Option<String>,
+}
+
+impl HttpStep {
+    /// Returns the request, with the placeholders replaced by the variables.
+    fn request(&self, exec: &ExecutionContext) -> Result<reqwest::RequestBuilder, TerminalError> {
+        let render = |text: &str| {
+            template::interpolate(text, exec).map_err(|err| TerminalError::new(err.to_string()))
+        };
+
+        let mut request = HTTP_CLIENT
+            .request(self.method.clone(), render(&self.url)?)
+            .timeout(self.timeout.into());
+        for (name, value) in &self.headers {
+            request = request.header(name, render(value)?);
+        }
+        if let Some(body) = &self.body {
+            let body =
+                template::render(body, exec).map_err(|err| TerminalError::new(err.to_string()))?;
+            request = request.json(&body);
+        }
+
+        Ok(request)
+    }
+}
+
+/// The default timeout of the steps waiting for an external endpoint or process.
+fn default_io_timeout() -> humantime::Duration {
+    Duration::from_secs(30).into()
+}
+
+#[async_trait::async_trait]
+impl Step for HttpStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        let mut inputs = template::placeholders(&self.url);
+        inputs.extend(
+            self.headers
+                .values()
+                .flat_map(|value| template::placeholders(value)),
+        );
+        if let Some(body) = &self.body {
+            inputs.extend(template::variables(body));
+        }
+
+        inputs
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.status_output
+            .as_deref()
+            .into_iter()
+            .chain(self.output.as_deref())
+            .collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let request = self.request(exec)?;
+
+        let Json(response) = ctx
+            .run(|| async move {
+                let request = request
+                    .build()
+                    .map_err(|err| TerminalError::new(format!("invalid request: {err}")))?;
+                let response = HTTP_CLIENT
+                    .execute(request)
+                    .await
+                    .map_err(|err| StepFailure::from(err.to_string()))?;
+
+                let status = response.status().as_u16();
+                let body = response
+                    .text()
+                    .await
+                    .map_err(|err| StepFailure::from(err.to_string()))?;
+
+                Ok(Json(HttpResponse { status, body }))
+            })
+            .name("http")
+            .await?;
+
+        if let Some(output) = &self.status_output {
+            exec.set(output, response.status as isize);
+        }
+        if let Some(output) = &self.output {
+            exec.set(output, response.body);
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `ExecStep` instances.
+struct Exec;
+
+#[async_trait::async_trait]
+impl StepFactory for Exec {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("command", ParamType::String)
+            .optional("args", ParamType::list(ParamType::String))
+            .optional("input", ParamType::String)
+            .optional("timeout", ParamType::Duration)
+            .optional("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: ExecStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that runs an external command, in a `ctx.run()` so that its output is journaled
+/// and a replayed invocation does not run the command again.
+///
+/// A command exiting with a non-zero status fails the handler with a terminal error, while
+/// a command that times out is killed and retried.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct ExecStep {
+    /// The program to run, looked up in the `PATH` if it is not a path.
+    command: String,
+    /// The arguments of the program, with `${name}` placeholders replaced by the text of
+    /// the variables.
+    #[serde(default)]
+    args: Vec<String>,
+    /// Optional: The name of the variable whose text (or bytes) is written to the standard
+    /// input of the command. If `None`, the standard input is empty.
+    input: Option<String>,
+    /// The timeout of every run of the command. Defaults to `30s`.
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    #[serde(default = "default_io_timeout")]
+    timeout: humantime::Duration,
+    /// Optional: The name of the variable where the standard output of the command will be
+    /// stored as text.
+    output: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl Step for ExecStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        let mut inputs: Vec<&str> = self.input.as_deref().into_iter().collect();
+        inputs.extend(self.args.iter().flat_map(|arg| template::placeholders(arg)));
+
+        inputs
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.output.as_deref().into_iter().collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| template::interpolate(arg, exec))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| TerminalError::new(err.to_string()))?;
+
+        let stdin = match self
+            .input
+            .as_ref()
+            .and_then(|input| exec.get_variable(input))
+        {
+            Some(Variable::Bytes(bytes)) => bytes.to_vec(),
+            Some(variable) => template::text_of(variable).into_bytes(),
+            None => Vec::new(),
+        };
+
+        let stdout: String = ctx
+            .run(|| async move {
+                let output = tokio::time::timeout(self.timeout.into(), self.spawn(&args, &stdin))
+                    .await
+                    .map_err(|_| {
+                        StepFailure::from(format!(
+                            "`{}` timed out after {}",
+                            self.command, self.timeout
+                        ))
+                    })?
+                    .map_err(|err| {
+                        TerminalError::new(format!("failed to run `{}`: {err}", self.command))
+                    })?;
+
+                if !output.status.success() {
+                    return Err(TerminalError::new(format!(
+                        "`{}` failed with {}: {}",
+                        self.command,
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim_end()
+                    ))
+                    .into());
+                }
+
+                Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+            })
+            .name("exec")
+            .await?;
+
+        if let Some(output) = &self.output {
+            exec.set(output, stdout);
+        }
+
+        Ok(())
+    }
+}
+
+impl ExecStep {
+    /// Runs the command to completion, the process is killed if the future is dropped.
+    async fn spawn(&self, args: &[String], stdin: &[u8]) -> std::io::Result<std::process::Output> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut child = tokio::process::Command::new(&self.command)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        // the input is written while the output is read, so large payloads don't block on
+        // full pipes
+        let pipe = child.stdin.take();
+        let write = async move {
+            match pipe {
+                Some(mut pipe) => match pipe.write_all(stdin).await {
+                    // the command exited without reading all its input
+                    Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+                    result => result,
+                },
+                None => Ok(()),
+            }
+        };
+
+        let (written, output) = tokio::join!(write, child.wait_with_output());
+        written?;
+        output
+    }
+}
+
+/// Validates that a step using durable promises runs in a workflow.
+fn validate_workflow(service_type: ServiceType) -> Result<(), StepError> {
+    if service_type != ServiceType::Workflow {
+        return Err(StepError::InvalidServiceType(service_type));
+    }
+
+    Ok(())
+}
+
+/// Factory for creating `PromiseAwaitStep` instances.
+struct PromiseAwait;
+
+#[async_trait::async_trait]
+impl StepFactory for PromiseAwait {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("name", ParamType::String)
+            .optional("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: PromiseAwaitStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that waits for a durable promise of the workflow to be completed. A rejected
+/// promise fails the handler with the terminal error of the rejection.
+/// This step is only valid for services of type `WORKFLOW`.
+#[derive(Debug, Deserialize)]
+struct PromiseAwaitStep {
+    /// The name of the promise.
+    name: String,
+    /// Optional: The name of the variable where the value of the promise will be stored.
+    output: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl Step for PromiseAwaitStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_workflow(service_type)
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.output.as_deref().into_iter().collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let value: Variable = ctx.promise(&self.name).await?;
+        if let Some(output) = &self.output {
+            exec.set(output, value);
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `PromisePeekStep` instances.
+struct PromisePeek;
+
+#[async_trait::async_trait]
+impl StepFactory for PromisePeek {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("name", ParamType::String)
+            .required("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: PromisePeekStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that reads the value of a durable promise of the workflow without waiting for it.
+/// This step is only valid for services of type `WORKFLOW`.
+#[derive(Debug, Deserialize)]
+struct PromisePeekStep {
+    /// The name of the promise.
+    name: String,
+    /// The name of the variable where the value of the promise will be stored, `null` if
+    /// the promise is not completed yet.
+    output: String,
+}
+
+#[async_trait::async_trait]
+impl Step for PromisePeekStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_workflow(service_type)
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        vec![&self.output]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let value: Option<Variable> = ctx.peek_promise(&self.name).await?;
+        exec.set(&self.output, value.unwrap_or(Variable::Null));
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `PromiseResolveStep` instances.
+struct PromiseResolve;
+
+#[async_trait::async_trait]
+impl StepFactory for PromiseResolve {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("name", ParamType::String)
+            .optional("input", ParamType::String)
+            .optional("reject", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: PromiseResolveStep = serde_yaml::from_value(params)?;
+        if step.input.is_some() && step.reject.is_some() {
+            return Err(StepError::InvalidParameter {
+                field: "reject".to_owned(),
+                reason: "can not be used together with `input`".to_owned(),
+            });
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that completes a durable promise of the workflow, typically from a shared handler
+/// to signal the workflow handler.
+/// This step is only valid for services of type `WORKFLOW`.
+#[derive(Debug, Deserialize)]
+struct PromiseResolveStep {
+    /// The name of the promise.
+    name: String,
+    /// Optional: The name of the variable whose value resolves the promise. If `None` or
+    /// the variable doesn't exist, the promise is resolved with `null`.
+    input: Option<String>,
+    /// Optional: Rejects the promise with a terminal error of the given message instead.
+    reject: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl Step for PromiseResolveStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_workflow(service_type)
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        self.input.as_deref().into_iter().collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        if let Some(message) = &self.reject {
+            ctx.reject_promise(&self.name, TerminalError::new(message.as_str()));
+            return Ok(());
+        }
+
+        let value = self
+            .input
+            .as_ref()
+            .and_then(|input| exec.get_variable(input))
+            .cloned()
+            .unwrap_or(Variable::Null);
+        ctx.resolve_promise(&self.name, value);
+
+        Ok(())
+    }
+}
+
+struct Busy;
+
+#[async_trait::async_trait]
+impl StepFactory for Busy {
+    fn schema(&self) -> Schema {
+        Latency::schema()
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let latency: Latency = serde_yaml::from_value(params)?;
+        latency.check()?;
+        Ok(Box::new(BusyStep {
+            latency,
+            clock: clock::system(),
+        }))
+    }
+}
+
+/// A step that simulates a busy handler by causing the current thread to sleep for a specified duration.
+/// Unlike [`SleepStep`], this sleep is handled directly within the mock service by its [`Clock`]
+/// and is not managed by the Restate runtime. This is useful for simulating CPU-bound work or
+/// other synchronous delays within the handler itself, without involving durable timers.
+struct BusyStep {
+    latency: Latency,
+    clock: Arc<dyn Clock>,
+}
+
+#[async_trait::async_trait]
+impl Step for BusyStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        _exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        self.wait().await;
+
+        Ok(())
+    }
+}
+
+impl BusyStep {
+    async fn wait(&self) {
+        let duration = self.latency.sample(&mut rand::rng());
+        self.clock.sleep(duration).await;
+    }
+}
+
+/// Factory for creating `CpuStep` instances.
+struct Cpu;
+
+#[async_trait::async_trait]
+impl StepFactory for Cpu {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("duration", ParamType::Duration)
+            .optional("iterations", ParamType::Integer)
+            .optional("blocking", ParamType::Boolean)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: CpuStep = serde_yaml::from_value(params)?;
+
+        if step.duration.is_some() == step.iterations.is_some() {
+            return Err(StepError::InvalidParameter {
+                field: "duration".to_owned(),
+                reason: "exactly one of `duration` or `iterations` must be set".to_owned(),
+            });
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that burns CPU, unlike [`BusyStep`] which only waits.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct CpuStep {
+    /// Optional: How long the step spins.
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    duration: Option<humantime::Duration>,
+    /// Optional: The number of hashing iterations computed.
+    iterations: Option<u64>,
+    /// If `true`, the step spins on a blocking thread of the runtime instead of the thread
+    /// running the handler. Defaults to `false`.
+    #[serde(default)]
+    blocking: bool,
+}
+
+impl CpuStep {
+    /// Spins the current thread.
+    ///
+    /// The duration is measured by the system clock rather than the [`Clock`] of the
+    /// steps: a paused clock would never advance while the thread spins.
+    fn burn(duration: Option<Duration>, iterations: Option<u64>) {
+        let started = std::time::Instant::now();
+        let mut state = 0u64;
+        let mut iteration = 0u64;
+
+        loop {
+            // a round of an xorshift generator, kept from being optimized away
+            state ^= iteration.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            std::hint::black_box(state);
+            iteration += 1;
+
+            let done = match (duration, iterations) {
+                (_, Some(iterations)) => iteration >= iterations,
+                // reading the clock is much slower than an iteration
+                (Some(duration), None) => {
+                    iteration.is_multiple_of(1024) && started.elapsed() >= duration
+                }
+                (None, None) => true,
+            };
+            if done {
+                break;
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for CpuStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        _exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let duration = self.duration.map(Duration::from);
+        let iterations = self.iterations;
+
+        if self.blocking {
+            tokio::task::spawn_blocking(move || CpuStep::burn(duration, iterations))
+                .await
+                .map_err(|err| StepFailure::from(err.to_string()))?;
+        } else {
+            CpuStep::burn(duration, iterations);
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `MemoryStep` instances.
+struct Memory;
+
+#[async_trait::async_trait]
+impl StepFactory for Memory {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("megabytes", ParamType::Integer)
+            .optional("duration", ParamType::Duration)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: MemoryConfig = serde_yaml::from_value(params)?;
+
+        if !(1..=MAX_MEMORY_MEGABYTES).contains(&config.megabytes) {
+            return Err(StepError::InvalidParameter {
+                field: "megabytes".to_owned(),
+                reason: format!("must be between 1 and {MAX_MEMORY_MEGABYTES}"),
+            });
+        }
+        let size =
+            config
+                .megabytes
+                .checked_mul(1 << 20)
+                .ok_or_else(|| StepError::InvalidParameter {
+                    field: "megabytes".to_owned(),
+                    reason: "is too large for this platform".to_owned(),
+                })?;
+
+        Ok(Box::new(MemoryStep {
+            size,
+            duration: config.duration.map(Into::into),
+            clock: clock::system(),
+        }))
+    }
+}
+
+/// The maximum number of megabytes a [`MemoryStep`] allocates (64 GiB).
+const MAX_MEMORY_MEGABYTES: usize = 1 << 16;
+
+/// The configuration of a [`MemoryStep`].
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct MemoryConfig {
+    /// The number of megabytes allocated.
+    megabytes: usize,
+    /// Optional: How long the memory is held, the step waits meanwhile. If `None`, the
+    /// memory is held until the end of the invocation.
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    duration: Option<humantime::Duration>,
+}
+
+/// A step that allocates memory to put the mock service under memory pressure. The memory
+/// is written, so it is actually committed by the operating system.
+struct MemoryStep {
+    /// The number of bytes allocated.
+    size: usize,
+    /// Optional: How long the memory is held.
+    duration: Option<Duration>,
+    clock: Arc<dyn Clock>,
+}
+
+impl MemoryStep {
+    /// Allocates and writes the memory, failing if it can not be allocated.
+    fn allocate(&self) -> Result<Vec<u8>, TryReserveError> {
+        let mut memory = Vec::new();
+        memory.try_reserve_exact(self.size)?;
+        memory.resize(self.size, 0xa5);
+
+        Ok(memory)
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for MemoryStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let memory = self.allocate().map_err(|err| {
+            StepFailure::from(format!("failed to allocate {} bytes: {err}", self.size))
+        })?;
+
+        match self.duration {
+            Some(duration) => {
+                self.clock.sleep(duration).await;
+                drop(memory);
+            }
+            None => exec.retain(memory),
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `CrashStep` instances.
+struct Crash;
+
+/// The ways the [`CrashStep`] terminates the process.
+const CRASH_MODES: &[&str] = &["abort", "exit"];
+
+#[async_trait::async_trait]
+impl StepFactory for Crash {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("mode", ParamType::Enum(CRASH_MODES))
+            .optional("code", ParamType::Integer)
+            .optional("after", ParamType::Integer)
+            .optional("probability", ParamType::Number)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: CrashConfig = serde_yaml::from_value(params)?;
+
+        if !(0.0..=1.0).contains(&config.probability) {
+            return Err(StepError::InvalidParameter {
+                field: "probability".to_owned(),
+                reason: "must be between 0 and 1".to_owned(),
+            });
+        }
+
+        Ok(Box::new(CrashStep {
+            config,
+            invocations: AtomicU64::new(0),
+        }))
+    }
+}
+
+/// How the [`CrashStep`] terminates the process.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum CrashMode {
+    /// Aborts the process, as a panic with `panic = "abort"` would.
+    #[default]
+    Abort,
+    /// Exits the process with the configured code.
+    Exit,
+}
+
+/// The configuration of a [`CrashStep`].
+#[derive(Debug, Deserialize)]
+struct CrashConfig {
+    /// How the process is terminated. Defaults to [`CrashMode::Abort`].
+    #[serde(default)]
+    mode: CrashMode,
+    /// The exit code, used by [`CrashMode::Exit`]. Defaults to 1.
+    #[serde(default = "default_exit_code")]
+    code: i32,
+    /// Optional: The number of invocations reaching the step before it starts crashing.
+    #[serde(default)]
+    after: u64,
+    /// The probability the process crashes once the step is armed. Defaults to 1.
+    #[serde(default = "default_probability")]
+    probability: f64,
+}
+
+fn default_exit_code() -> i32 {
+    1
+}
+
+fn default_probability() -> f64 {
+    1.0
+}
+
+/// A step that kills the mock service process, to test how Restate handles deployments
+/// dying mid-invocation.
+struct CrashStep {
+    config: CrashConfig,
+    /// The number of invocations that reached the step since the process started.
+    invocations: AtomicU64,
+}
+
+impl CrashStep {
+    /// Returns whether the invocation reaching the step crashes the process.
+    fn armed(&self) -> bool {
+        let invocations = self.invocations.fetch_add(1, AtomicOrdering::Relaxed);
+        invocations >= self.config.after && rand::random_bool(self.config.probability)
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for CrashStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        if !self.armed() {
+            return Ok(());
+        }
+
+        tracing::error!(
+            invocation_id = exec.invocation_id(),
+            "crashing the process ({:?})",
+            self.config.mode
+        );
+        match self.config.mode {
+            CrashMode::Abort => std::process::abort(),
+            CrashMode::Exit => std::process::exit(self.config.code),
+        }
+    }
+}
+
+/// Factory for creating `HangStep` instances.
+struct Hang;
+
+#[async_trait::async_trait]
+impl StepFactory for Hang {
+    fn schema(&self) -> Schema {
+        Schema::new().optional("max_duration", ParamType::Duration)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: HangConfig = serde_yaml::from_value(params)?;
+        Ok(Box::new(HangStep {
+            config,
+            clock: clock::system(),
+        }))
+    }
+}
+
+/// The configuration of a [`HangStep`].
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct HangConfig {
+    /// Optional: How long the step hangs before the handler carries on. If `None`, the
+    /// step never completes.
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    max_duration: Option<humantime::Duration>,
+}
+
+/// A step that gets the handler stuck, to test timeouts and cancellation.
+struct HangStep {
+    config: HangConfig,
+    clock: Arc<dyn Clock>,
+}
+
+#[async_trait::async_trait]
+impl Step for HangStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        _exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        match self.config.max_duration {
+            Some(duration) => self.clock.sleep(duration.into()).await,
+            None => std::future::pending().await,
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `RunStep` instances.
+struct RunFactory;
+
+#[async_trait::async_trait]
+impl StepFactory for RunFactory {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("name", ParamType::String)
+            .optional("value", ParamType::Literal)
+            .optional("latency", ParamType::Object(Latency::schema()))
+            .optional("failure_rate", ParamType::Number)
+            .optional(
+                "retry",
+                ParamType::Object(
+                    Schema::new()
+                        .optional("initial_delay", ParamType::Duration)
+                        .optional("factor", ParamType::Number)
+                        .optional("max_delay", ParamType::Duration)
+                        .optional("max_attempts", ParamType::Integer)
+                        .optional("max_duration", ParamType::Duration),
+                ),
+            )
+            .optional("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: RunConfig = serde_yaml::from_value(params)?;
+
+        if let Some(latency) = &config.latency {
+            latency.check().map_err(|err| match err {
+                StepError::InvalidParameter { field, reason } => StepError::InvalidParameter {
+                    field: format!("latency.{field}"),
+                    reason,
+                },
+                err => err,
+            })?;
+        }
+
+        if !(0.0..=1.0).contains(&config.failure_rate) {
+            return Err(StepError::InvalidParameter {
+                field: "failure_rate".to_owned(),
+                reason: "must be between 0.0 and 1.0".to_owned(),
+            });
+        }
+
+        Ok(Box::new(RunStep {
+            config,
+            clock: clock::system(),
+        }))
+    }
+}
+
+/// The configuration of a [`RunStep`].
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct RunConfig {
+    /// Optional: The name of the side effect, shown in the Restate journal.
+    name: Option<String>,
+    /// Optional: The literal value produced by the side effect. If `None`, a random
+    /// integer is produced.
+    #[serde_as(as = "Option<serde_with::TryFromInto<serde_json::Value>>")]
+    #[serde(default)]
+    value: Option<Variable>,
+    /// Optional: The time spent by the side effect, on every attempt.
+    latency: Option<Latency>,
+    /// The probability (0.0 to 1.0) that an attempt of the side effect fails. Defaults to `0.0`.
+    #[serde(default)]
+    failure_rate: f64,
+    /// Optional: The retry policy of the side effect. If `None`, the side effect is retried
+    /// with the retry policy of the Restate invoker.
+    retry: Option<RunRetryConfig>,
+    /// Optional: The name of the variable where the journaled value will be stored.
+    output: Option<String>,
+}
+
+/// The retry policy of a [`RunStep`], see [`RunRetryPolicy`].
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct RunRetryConfig {
+    /// Optional: The delay before the first retry. Defaults to `100ms`.
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    initial_delay: Option<humantime::Duration>,
+    /// The factor applied to the delay after every retry. Defaults to `2.0`.
+    #[serde(default = "default_retry_factor")]
+    factor: f32,
+    /// Optional: The maximum delay between retries.
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    max_delay: Option<humantime::Duration>,
+    /// Optional: The number of attempts after which the side effect fails with a terminal error.
+    max_attempts: Option<u32>,
+    /// Optional: The duration after which the side effect fails with a terminal error.
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    max_duration: Option<humantime::Duration>,
+}
+
+fn default_retry_factor() -> f32 {
+    2.0
+}
+
+impl From<&RunRetryConfig> for RunRetryPolicy {
+    fn from(config: &RunRetryConfig) -> Self {
+        let mut policy = RunRetryPolicy::new().exponentiation_factor(config.factor);
+        if let Some(delay) = config.initial_delay {
+            policy = policy.initial_delay(delay.into());
+        }
+        if let Some(delay) = config.max_delay {
+            policy = policy.max_delay(delay.into());
+        }
+        if let Some(attempts) = config.max_attempts {
+            policy = policy.max_attempts(attempts);
+        }
+        if let Some(duration) = config.max_duration {
+            policy = policy.max_duration(duration.into());
+        }
+
+        policy
+    }
+}
+
+/// A step that runs a simulated side effect in `ctx.run()`, so that its value is journaled
+/// and replayed instead of being produced again on retries. Failed attempts of the side
+/// effect are retried according to the retry policy, and fail the handler with a terminal
+/// error once the policy gives up.
+struct RunStep {
+    config: RunConfig,
+    clock: Arc<dyn Clock>,
+}
+
+#[async_trait::async_trait]
+impl Step for RunStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.config.output.as_deref().into_iter().collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let config = &self.config;
+        let mut side_effect = ctx.run(|| async {
+            if let Some(latency) = &config.latency {
+                let duration = latency.sample(&mut rand::rng());
+                self.clock.sleep(duration).await;
+            }
+
+            if rand::random_bool(config.failure_rate) {
+                return Err(HandlerError::from(
+                    "injected side effect failure".to_owned(),
+                ));
+            }
+
+            Ok(config
+                .value
+                .clone()
+                .unwrap_or_else(|| Variable::Integer(rand::random::<u32>() as isize)))
+        });
+
+        if let Some(name) = &config.name {
+            side_effect = side_effect.name(name);
+        }
+        if let Some(retry) = &config.retry {
+            side_effect = side_effect.retry_policy(retry.into());
+        }
+
+        let value = side_effect.await?;
+        if let Some(output) = &config.output {
+            exec.set(output, value);
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `BarrierStep` instances.
+struct BarrierFactory;
+
+#[async_trait::async_trait]
+impl StepFactory for BarrierFactory {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("name", ParamType::String)
+            .required("parties", ParamType::Integer)
+            .optional("timeout", ParamType::Duration)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: BarrierStepConfig = serde_yaml::from_value(params)?;
+        if config.parties == 0 {
+            return Err(StepError::InvalidParameter {
+                field: "parties".to_owned(),
+                reason: "must be at least 1".to_owned(),
+            });
+        }
+
+        let barrier = barrier::barrier(&config.name, config.parties).map_err(|parties| {
+            StepError::InvalidParameter {
+                field: "parties".to_owned(),
+                reason: format!(
+                    "barrier `{}` is already defined with {parties} parties",
+                    config.name
+                ),
+            }
+        })?;
+
+        Ok(Box::new(BarrierStep {
+            name: config.name,
+            barrier,
+            timeout: config.timeout.map_or(DEFAULT_BARRIER_TIMEOUT, Into::into),
+            clock: clock::system(),
+        }))
+    }
+}
+
+/// How long a [`BarrierStep`] waits for the other parties by default.
+const DEFAULT_BARRIER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Configuration for a `BarrierStep` as defined in the YAML.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct BarrierStepConfig {
+    /// The name of the barrier, shared by all the steps using it across handlers and services.
+    name: String,
+    /// The number of concurrent invocations releasing the barrier.
+    parties: usize,
+    /// Optional: How long to wait for the other parties. Defaults to 30 seconds.
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    timeout: Option<humantime::Duration>,
+}
+
+/// A step blocking until `parties` invocations reached the barrier of the same name, to
+/// provoke races between concurrent invocations deterministically. Like [`BusyStep`], the
+/// wait happens within the mock service and is not journaled: a replayed invocation waits
+/// again. Timing out fails the invocation with a retryable error.
+struct BarrierStep {
+    name: String,
+    barrier: Arc<Barrier>,
+    timeout: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl BarrierStep {
+    async fn wait(&self) -> Result<(), StepFailure> {
+        if !self.barrier.wait(self.timeout, self.clock.as_ref()).await {
+            return Err(StepFailure::from(format!(
+                "barrier `{}` timed out after {}",
+                self.name,
+                humantime::format_duration(self.timeout)
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for BarrierStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn deterministic(&self) -> bool {
+        false
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        _exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        self.wait().await
+    }
+}
+
+/// Factory for creating `LoopStep` instances.
+struct Loop;
+
+#[async_trait::async_trait]
+impl StepFactory for Loop {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("count", ParamType::Integer)
+            .optional("count_variable", ParamType::String)
+            .optional("index", ParamType::String)
+            .required("steps", ParamType::Steps)
+            .optional("export", ParamType::list(ParamType::String))
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: LoopStepConfig = serde_yaml::from_value(params)?;
+        if step.count.is_some() && step.count_variable.is_some() {
+            return Err(StepError::InvalidParameter {
+                field: "count_variable".to_owned(),
+                reason: "can not be used together with `count`".to_owned(),
+            });
+        }
+
+        let steps = create_steps("steps", step.steps).await?;
+
+        Ok(Box::new(LoopStep {
+            count: step.count,
+            count_variable: step.count_variable,
+            index: step.index,
+            steps,
+            export: step.export,
+        }))
+    }
+}
+
+/// Configuration for a `LoopStep` as defined in the YAML.
+#[derive(Debug, Deserialize)]
+struct LoopStepConfig {
+    /// Optional: The number of times to execute the nested steps.
+    /// If `None` or not provided, the loop will run indefinitely (or until `usize::MAX` iterations).
+    count: Option<usize>,
+    /// Optional: The name of the integer variable holding the number of iterations, instead
+    /// of `count`.
+    count_variable: Option<String>,
+    /// Optional: The name of the variable holding the index of the current iteration,
+    /// starting at 0.
+    index: Option<String>,
+    /// A list of step configurations that will be executed in each iteration of the loop.
+    steps: Vec<config::StepConfig>,
+    /// Optional: The variables set by the nested steps that are kept after the loop.
+    #[serde(default)]
+    export: Vec<String>,
+}
+
+/// A step that executes a sequence of nested steps repeatedly.
+struct LoopStep {
+    /// The number of times the loop will iterate. `None` means effectively infinite.
+    count: Option<usize>,
+    /// The variable holding the number of iterations, read when the loop starts.
+    count_variable: Option<String>,
+    /// The variable of the loop scope holding the index of the current iteration.
+    index: Option<String>,
+    /// The actual `Step` trait objects for the nested steps to be executed in each iteration.
+    steps: Vec<BoxStep>,
+    /// The variables of the loop scope copied to the enclosing scope once the loop is done.
+    export: Vec<String>,
+}
+
+#[async_trait::async_trait]
+impl Step for LoopStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        self.steps
+            .iter()
+            .try_for_each(|step| step.validate(service_type, handler_type))
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.export.iter().map(String::as_str).collect()
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        // the exported variables are read from the loop scope
+        self.export
+            .iter()
+            .chain(&self.count_variable)
+            .map(String::as_str)
+            .collect()
+    }
+
+    fn ends_handler(&self) -> bool {
+        // a count read from a variable may be 0
+        self.count_variable.is_none()
+            && self.count != Some(0)
+            && self.steps.iter().any(|step| step.ends_handler())
+    }
+
+    fn branches(&self) -> Vec<Branch<'_>> {
+        vec![Branch {
+            path: "params.steps".to_owned(),
+            steps: &self.steps,
+            reachable: self.count != Some(0),
+        }]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let count = match &self.count_variable {
+            Some(name) => exec
+                .get::<isize>(name)
+                .ok_or_else(|| TerminalError::new(format!("unknown variable {name}")))?
+                .map_err(|_| TerminalError::new(format!("variable {name} is not an integer")))?
+                .max(0) as usize,
+            None => self.count.unwrap_or(usize::MAX),
+        };
+
+        let mut scope = exec.scope();
+
+        'iterations: for idx in 0..count {
+            if let Some(index) = &self.index {
+                scope.set(index, idx as isize);
+            }
+
+            for step in &self.steps {
+                step.run(ctx, &mut scope, input).await?;
+
+                if scope.is_finished() {
+                    break 'iterations;
+                }
+            }
+        }
+
+        exec.close(scope, &self.export);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `ParallelStep` instances.
+struct Parallel;
+
+#[async_trait::async_trait]
+impl StepFactory for Parallel {
+    fn schema(&self) -> Schema {
+        Schema::new().required("steps", ParamType::Steps)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: ParallelStepConfig = serde_yaml::from_value(params)?;
+
+        for (idx, config) in config.steps.iter().enumerate() {
+            // a step awaiting anything before its call would issue the calls in a different
+            // order on replay
+            if STEPS.get(&config.ty).is_some_and(|(ty, _)| ty != "call") {
+                return Err(StepError::InvalidParameter {
+                    field: format!("steps[{idx}].type"),
+                    reason: "only `call` steps can run in parallel".to_owned(),
+                });
+            }
+        }
+
+        let steps = create_steps("steps", config.steps).await?;
+
+        Ok(Box::new(ParallelStep { steps }))
+    }
+}
+
+/// Configuration for a `ParallelStep` as defined in the YAML.
+#[derive(Debug, Deserialize)]
+struct ParallelStepConfig {
+    /// The `call` steps executed concurrently.
+    steps: Vec<config::StepConfig>,
+}
+
+/// A step that issues nested calls concurrently and waits for all of them, to mock
+/// fan-out/fan-in patterns.
+struct ParallelStep {
+    steps: Vec<BoxStep>,
+}
+
+impl ParallelStep {
+    /// Closes the scopes of the calls, in the order of the steps, keeping their outputs.
+    fn close(&self, exec: &mut ExecutionContext, scopes: Vec<ExecutionContext>) {
+        for (step, scope) in self.steps.iter().zip(scopes) {
+            let exports: Vec<String> = step.outputs().into_iter().map(str::to_owned).collect();
+            exec.close(scope, &exports);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for ParallelStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        self.steps
+            .iter()
+            .try_for_each(|step| step.validate(service_type, handler_type))
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.steps.iter().flat_map(|step| step.outputs()).collect()
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        // the outputs are read from the scopes of the calls
+        self.outputs()
+    }
+
+    fn branches(&self) -> Vec<Branch<'_>> {
+        vec![Branch {
+            path: "params.steps".to_owned(),
+            steps: &self.steps,
+            reachable: true,
+        }]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let mut scopes: Vec<_> = self.steps.iter().map(|_| exec.scope()).collect();
+
+        // the calls are issued in order as the futures are first polled, then awaited together
+        futures::future::try_join_all(
+            self.steps
+                .iter()
+                .zip(&mut scopes)
+                .map(|(step, scope)| step.run(ctx, scope, input)),
+        )
+        .await?;
+
+        self.close(exec, scopes);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `RaceStep` instances.
+struct Race;
+
+#[async_trait::async_trait]
+impl StepFactory for Race {
+    fn schema(&self) -> Schema {
+        let call = CallTarget::schema().optional("input", ParamType::String);
+
+        Schema::new()
+            .required(
+                "branches",
+                ParamType::list(ParamType::Object(
+                    Schema::new()
+                        .required("name", ParamType::String)
+                        .optional("call", ParamType::Object(call))
+                        .optional("sleep", ParamType::Duration),
+                )),
+            )
+            .optional("winner", ParamType::String)
+            .optional("output", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: RaceStepConfig = serde_yaml::from_value(params)?;
+
+        if config.branches.is_empty() {
+            return Err(StepError::InvalidParameter {
+                field: "branches".to_owned(),
+                reason: "must not be empty".to_owned(),
+            });
+        }
+
+        let mut branches = Vec::with_capacity(config.branches.len());
+        for (idx, branch) in config.branches.into_iter().enumerate() {
+            if branches
+                .iter()
+                .any(|other: &RaceBranch| other.name == branch.name)
+            {
+                return Err(StepError::InvalidParameter {
+                    field: format!("branches[{idx}].name"),
+                    reason: format!("duplicate branch `{}`", branch.name),
+                });
+            }
+
+            if let Some(call) = &branch.call {
+                call.target.check().map_err(|err| match err {
+                    StepError::InvalidParameter { field, reason } => StepError::InvalidParameter {
+                        field: format!("branches[{idx}].call.{field}"),
+                        reason,
+                    },
+                    err => err,
+                })?;
+            }
+
+            let operation = match (branch.call, branch.sleep) {
+                (Some(call), None) => RaceOperation::Call(call),
+                (None, Some(duration)) => RaceOperation::Sleep(duration.into()),
+                _ => {
+                    return Err(StepError::InvalidParameter {
+                        field: format!("branches[{idx}]"),
+                        reason: "exactly one of `call` or `sleep` must be set".to_owned(),
+                    })
+                }
+            };
+
+            branches.push(RaceBranch {
+                name: branch.name,
+                operation,
+            });
+        }
+
+        Ok(Box::new(RaceStep {
+            branches,
+            winner: config.winner,
+            output: config.output,
+        }))
+    }
+}
+
+/// Configuration for a `RaceStep` as defined in the YAML.
+#[derive(Debug, Deserialize)]
+struct RaceStepConfig {
+    branches: Vec<RaceBranchConfig>,
+    winner: Option<String>,
+    output: Option<String>,
+}
+
+/// Configuration of a branch of a `RaceStep`, either a call or a sleep.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct RaceBranchConfig {
+    name: String,
+    call: Option<RaceCall>,
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    sleep: Option<humantime::Duration>,
+}
+
+/// A call raced by a `RaceStep`, see [`CallStep`] for the fields.
+#[derive(Debug, Deserialize)]
+struct RaceCall {
+    #[serde(flatten)]
+    target: CallTarget,
+    input: Option<String>,
+}
+
+impl RaceCall {
+    /// Returns the input of the call, `null` if the variable is not set.
+    fn request(&self, exec: &ExecutionContext) -> Variable {
+        self.input
+            .as_ref()
+            .and_then(|input| exec.get_variable(input))
+            .cloned()
+            .unwrap_or(Variable::Null)
+    }
+}
+
+/// A branch of a `RaceStep`.
+struct RaceBranch {
+    name: String,
+    operation: RaceOperation,
+}
+
+enum RaceOperation {
+    Call(RaceCall),
+    /// A durable timer, e.g. the timeout of a call.
+    Sleep(Duration),
+}
+
+/// A future raced by a `RaceStep`. Calls and sleeps are distinct future types.
+enum Racer<C, S> {
+    Call(C),
+    Sleep(S),
+}
+
+impl<C: SealedDurableFuture, S: SealedDurableFuture> Racer<C, S> {
+    /// Waits for the first of the `racers` to complete, returning its index. There must be
+    /// at least one racer.
+    ///
+    /// This is the only use of the SDK internals (`macro_support`, hidden from its docs):
+    /// the SDK only races futures through its `select!` macro, which takes a number of
+    /// futures fixed at compile time, while the branches of the step are configured. The
+    /// macro expands to these very calls, so the winner is journaled the same way. The SDK
+    /// is pinned to an exact version, and this is the place to adapt when upgrading it.
+    async fn first(racers: &[Self]) -> Result<usize, TerminalError> {
+        let handles = racers
+            .iter()
+            .map(|racer| match racer {
+                Racer::Call(fut) => fut.handle(),
+                Racer::Sleep(fut) => fut.handle(),
+            })
+            .collect();
+        let inner = match &racers[0] {
+            Racer::Call(fut) => fut.inner_context(),
+            Racer::Sleep(fut) => fut.inner_context(),
+        };
+
+        inner.select(handles).await
+    }
+}
+
+/// A step that waits for the first of several calls and timers to complete, e.g. to
+/// mock a call with a timeout. The other branches are not cancelled: their calls keep
+/// running.
+struct RaceStep {
+    branches: Vec<RaceBranch>,
+    /// Optional: The variable storing the name of the branch that completed first.
+    winner: Option<String>,
+    /// Optional: The variable storing the result of the branch that completed first, `null`
+    /// for a sleep.
+    output: Option<String>,
+}
+
+impl RaceStep {
+    /// Stores the name and the result of the branch `idx` that completed first.
+    fn finish(&self, exec: &mut ExecutionContext, idx: usize, value: Variable) {
+        if let Some(winner) = &self.winner {
+            exec.set(winner, self.branches[idx].name.clone());
+        }
+        if let Some(output) = &self.output {
+            exec.set(output, value);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for RaceStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.winner
+            .iter()
+            .chain(&self.output)
+            .map(String::as_str)
+            .collect()
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        self.branches
+            .iter()
+            .flat_map(|branch| match &branch.operation {
+                RaceOperation::Call(call) => {
+                    let mut inputs: Vec<&str> = call.input.as_deref().into_iter().collect();
+                    inputs.extend(call.target.inputs());
+                    inputs
+                }
+                RaceOperation::Sleep(_) => Vec::new(),
+            })
+            .collect()
+    }
+
+    fn targets(&self) -> Vec<Target<'_>> {
+        self.branches
+            .iter()
+            .filter_map(|branch| match &branch.operation {
+                RaceOperation::Call(call) => call.target.target(InvocationKind::Call),
+                RaceOperation::Sleep(_) => None,
+            })
+            .collect()
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let mut racers = Vec::with_capacity(self.branches.len());
+        for branch in &self.branches {
+            racers.push(match &branch.operation {
+                RaceOperation::Call(call) => {
+                    let target = call.target.resolve(ctx, exec, input)?;
+                    let req = call.request(exec);
+
+                    Racer::Call(ctx.request::<_, Variable>(target, req).call())
+                }
+                RaceOperation::Sleep(duration) => Racer::Sleep(ctx.sleep(*duration)),
+            });
+        }
+
+        let idx = Racer::first(&racers).await?;
+
+        let value = match racers.swap_remove(idx) {
+            Racer::Call(fut) => fut.await?,
+            Racer::Sleep(fut) => {
+                fut.await?;
+                Variable::Null
+            }
+        };
+
+        self.finish(exec, idx, value);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `RetryStep` instances.
+struct Retry;
+
+#[async_trait::async_trait]
+impl StepFactory for Retry {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("steps", ParamType::Steps)
+            .optional("max_attempts", ParamType::Integer)
+            .optional("initial_delay", ParamType::Duration)
+            .optional("factor", ParamType::Number)
+            .optional("max_delay", ParamType::Duration)
+            .optional("export", ParamType::list(ParamType::String))
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: RetryStepConfig = serde_yaml::from_value(params)?;
+
+        if config.max_attempts == 0 {
+            return Err(StepError::InvalidParameter {
+                field: "max_attempts".to_owned(),
+                reason: "must be at least 1".to_owned(),
+            });
+        }
+        if !config.factor.is_finite() || config.factor < 1.0 {
+            return Err(StepError::InvalidParameter {
+                field: "factor".to_owned(),
+                reason: "must be a finite number of at least 1".to_owned(),
+            });
+        }
+
+        let delays = [
+            ("initial_delay", Some(config.initial_delay)),
+            ("max_delay", config.max_delay),
+        ];
+        for (field, delay) in delays {
+            if delay.is_some_and(|delay| Duration::from(delay) > MAX_DELAY) {
+                return Err(StepError::InvalidParameter {
+                    field: field.to_owned(),
+                    reason: format!("must be at most {}", humantime::format_duration(MAX_DELAY)),
+                });
+            }
+        }
+
+        let steps = create_steps("steps", config.steps).await?;
+        if let Some(idx) = steps.iter().position(|step| !deterministic(step.as_ref())) {
+            return Err(StepError::InvalidParameter {
+                field: format!("steps[{idx}]"),
+                reason:
+                    "fails randomly or on a timeout, so the attempts would not replay the same way"
+                        .to_owned(),
+            });
+        }
+
+        Ok(Box::new(RetryStep {
+            steps,
+            max_attempts: config.max_attempts,
+            initial_delay: config.initial_delay.into(),
+            factor: config.factor,
+            max_delay: config.max_delay.map(Duration::from),
+            export: config.export,
+        }))
+    }
+}
+
+/// Returns whether the `step` and all its nested steps fail the same way when replayed.
+fn deterministic(step: &dyn Step) -> bool {
+    step.deterministic()
+        && step
+            .branches()
+            .iter()
+            .all(|branch| branch.steps.iter().all(|step| deterministic(step.as_ref())))
+}
+
+/// Configuration for a `RetryStep` as defined in the YAML.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct RetryStepConfig {
+    steps: Vec<config::StepConfig>,
+    #[serde(default = "default_max_attempts")]
+    max_attempts: u32,
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    #[serde(default = "default_initial_delay")]
+    initial_delay: humantime::Duration,
+    #[serde(default = "default_retry_factor")]
+    factor: f32,
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    max_delay: Option<humantime::Duration>,
+    #[serde(default)]
+    export: Vec<String>,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_delay() -> humantime::Duration {
+    Duration::from_millis(100).into()
+}
+
+/// A step that executes nested steps and retries them on retryable failures, waiting on
+/// durable timers between attempts, to model resilience logic within a handler.
+/// Terminal failures are not retried.
+struct RetryStep {
+    steps: Vec<BoxStep>,
+    /// The number of attempts, including the first one.
+    max_attempts: u32,
+    /// The delay before the first retry.
+    initial_delay: Duration,
+    /// The factor applied to the delay after every retry.
+    factor: f32,
+    /// Optional: The maximum delay between retries.
+    max_delay: Option<Duration>,
+    /// The variables of the scope of the successful attempt copied to the enclosing scope.
+    export: Vec<String>,
+}
+
+impl RetryStep {
+    /// Returns the delay before retrying the failed `attempt` (starting at 1), or `None` if
+    /// the error is terminal or the attempts are exhausted.
+    fn retry_delay(&self, attempt: u32, err: &StepFailure) -> Option<Duration> {
+        if err.is_terminal() || attempt >= self.max_attempts {
+            return None;
+        }
+
+        Some(self.delay(attempt))
+    }
+
+    /// Returns the delay before the given retry, starting at 1. The delay is capped by the
+    /// maximum delay, or [`MAX_DELAY`] if none is set.
+    fn delay(&self, retry: u32) -> Duration {
+        if self.initial_delay.is_zero() {
+            return Duration::ZERO;
+        }
+
+        let exponent = i32::try_from(retry.saturating_sub(1)).unwrap_or(i32::MAX);
+        let seconds = self.initial_delay.as_secs_f64() * f64::from(self.factor).powi(exponent);
+        let max_delay = self.max_delay.unwrap_or(MAX_DELAY);
+
+        Duration::try_from_secs_f64(seconds).map_or(max_delay, |delay| delay.min(max_delay))
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for RetryStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        self.steps
+            .iter()
+            .try_for_each(|step| step.validate(service_type, handler_type))
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.export.iter().map(String::as_str).collect()
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        // the exported variables are read from the attempt scope
+        self.export.iter().map(String::as_str).collect()
+    }
+
+    fn ends_handler(&self) -> bool {
+        self.steps.iter().any(|step| step.ends_handler())
+    }
+
+    fn branches(&self) -> Vec<Branch<'_>> {
+        vec![Branch {
+            path: "params.steps".to_owned(),
+            steps: &self.steps,
+            reachable: true,
+        }]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let mut attempt = 1;
+
+        loop {
+            let mut scope = exec.scope();
+
+            let mut result = Ok(());
+            for step in &self.steps {
+                result = step.run(ctx, &mut scope, input).await;
+
+                if result.is_err() || scope.is_finished() {
+                    break;
+                }
+            }
+
+            let err = match result {
+                Ok(()) => {
+                    exec.close(scope, &self.export);
+                    return Ok(());
+                }
+                Err(err) => err,
+            };
+
+            let Some(delay) = self.retry_delay(attempt, &err) else {
+                return Err(err);
+            };
+            tracing::debug!(attempt, ?delay, "retrying the nested steps: {err:?}");
+
+            ctx.sleep(delay).await?;
+            attempt += 1;
+        }
+    }
+}
+
+/// Factory for creating `ConditionStep` instances.
+struct Condition;
+
+/// The accepted values of the `op` parameter of a `condition` or `assert` step.
+const OPERATORS: &[&str] = &["eq", "ne", "lt", "le", "gt", "ge", "contains"];
+
+#[async_trait::async_trait]
+impl StepFactory for Condition {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("variable", ParamType::String)
+            .optional("path", ParamType::JsonPath)
+            .optional("op", ParamType::Enum(OPERATORS))
+            .optional("value", ParamType::Literal)
+            .required("then", ParamType::Steps)
+            .optional("else", ParamType::Steps)
+            .optional("export", ParamType::list(ParamType::String))
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: ConditionStepConfig = serde_yaml::from_value(params)?;
+
+        let operand = Operand::new(config.variable, config.path)?;
+
+        let step = ConditionStep::new(
+            operand,
+            config.op,
+            config.value,
+            config.then,
+            config.otherwise,
+            config.export,
+        )
+        .await?;
+
+        Ok(Box::new(step))
+    }
+}
+
+/// Configuration for a `ConditionStep` as defined in the YAML.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct ConditionStepConfig {
+    /// Optional: The name of the variable compared to the value.
+    variable: Option<String>,
+    /// Optional: A JSONPath selecting the part of the input compared to the value.
+    path: Option<JsonPath>,
+    /// The comparison operator. Defaults to `eq`.
+    #[serde(default)]
+    op: Operator,
+    /// The literal value the operand is compared to. Defaults to `null`.
+    #[serde_as(as = "serde_with::TryFromInto<serde_json::Value>")]
+    #[serde(default = "default_condition_value")]
+    value: Variable,
+    /// The steps executed if the comparison holds.
+    then: Vec<config::StepConfig>,
+    /// Optional: The steps executed otherwise.
+    #[serde(default, rename = "else")]
+    otherwise: Vec<config::StepConfig>,
+    /// Optional: The variables set by the nested steps that are kept after the condition.
+    #[serde(default)]
+    export: Vec<String>,
+}
+
+fn default_condition_value() -> Variable {
+    Variable::Null
+}
+
+/// Factory for creating `ConditionStep` instances comparing a state key.
+struct StateIf;
+
+#[async_trait::async_trait]
+impl StepFactory for StateIf {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("key", ParamType::String)
+            .optional("op", ParamType::Enum(OPERATORS))
+            .optional("value", ParamType::Literal)
+            .required("then", ParamType::Steps)
+            .optional("else", ParamType::Steps)
+            .optional("export", ParamType::list(ParamType::String))
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: StateIfStepConfig = serde_yaml::from_value(params)?;
+
+        let step = ConditionStep::new(
+            Operand::State(config.key),
+            config.op,
+            config.value,
+            config.then,
+            config.otherwise,
+            config.export,
+        )
+        .await?;
+
+        Ok(Box::new(step))
+    }
+}
+
+/// Configuration for a `state-if` step as defined in the YAML, a [`ConditionStep`] on a
+/// state key.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct StateIfStepConfig {
+    /// The state key compared to the value, `null` if not set.
+    key: String,
+    /// The comparison operator. Defaults to `eq`.
+    #[serde(default)]
+    op: Operator,
+    /// The literal value the state is compared to. Defaults to `null`.
+    #[serde_as(as = "serde_with::TryFromInto<serde_json::Value>")]
+    #[serde(default = "default_condition_value")]
+    value: Variable,
+    /// The steps executed if the comparison holds.
+    then: Vec<config::StepConfig>,
+    /// Optional: The steps executed otherwise.
+    #[serde(default, rename = "else")]
+    otherwise: Vec<config::StepConfig>,
+    /// Optional: The variables set by the nested steps that are kept after the condition.
+    #[serde(default)]
+    export: Vec<String>,
+}
+
+/// A comparison operator of a `condition` or `assert` step.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Operator {
+    #[default]
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// The operand string (or bytes) contains the value.
+    Contains,
+}
+
+impl Operator {
+    /// Returns whether the operator holds between the operand and the value.
+    fn test(self, operand: &Variable, value: &Variable) -> bool {
+        match (self, operand, value) {
+            (Operator::Contains, Variable::String(operand), Variable::String(value)) => {
+                operand.contains(value.as_str())
+            }
+            (Operator::Contains, Variable::Bytes(operand), Variable::Bytes(value)) => {
+                value.is_empty()
+                    || operand
+                        .windows(value.len())
+                        .any(|window| window == value.as_ref())
+            }
+            (Operator::Contains, Variable::Array(items), value) => items
+                .iter()
+                .any(|item| compare(item, value) == Some(Ordering::Equal)),
+            (Operator::Contains, _, _) => false,
+            (op, operand, value) => op.holds(compare(operand, value)),
+        }
+    }
+
+    /// Applies the operator to the ordering of the operand relative to the value, `None` if
+    /// they are not comparable (e.g. a string and a number).
+    fn holds(self, ordering: Option<Ordering>) -> bool {
+        match (self, ordering) {
+            (Operator::Ne, ordering) => ordering != Some(Ordering::Equal),
+            (_, None) | (Operator::Contains, _) => false,
+            (Operator::Eq, Some(ordering)) => ordering == Ordering::Equal,
+            (Operator::Lt, Some(ordering)) => ordering == Ordering::Less,
+            (Operator::Le, Some(ordering)) => ordering != Ordering::Greater,
+            (Operator::Gt, Some(ordering)) => ordering == Ordering::Greater,
+            (Operator::Ge, Some(ordering)) => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            Operator::Eq => "eq",
+            Operator::Ne => "ne",
+            Operator::Lt => "lt",
+            Operator::Le => "le",
+            Operator::Gt => "gt",
+            Operator::Ge => "ge",
+            Operator::Contains => "contains",
+        };
+        f.write_str(op)
+    }
+}
+
+/// The value compared by a `condition`, `switch` or `assert` step.
+enum Operand {
+    /// A variable of the execution context, `null` if not set.
+    Variable(String),
+    /// A part of the handler input, `null` if missing.
+    Input(JsonPath),
+    /// A key of the Restate state, `null` if not set.
+    State(String),
+}
+
+impl Operand {
+    /// Creates the operand from the `variable` and `path` parameters, exactly one of them
+    /// must be set.
+    fn new(variable: Option<String>, path: Option<JsonPath>) -> Result<Self, StepError> {
+        match (variable, path) {
+            (Some(variable), None) => Ok(Operand::Variable(variable)),
+            (None, Some(path)) => Ok(Operand::Input(path)),
+            _ => Err(StepError::InvalidParameter {
+                field: "variable".to_owned(),
+                reason: "exactly one of `variable` or `path` must be set".to_owned(),
+            }),
+        }
+    }
+
+    /// Returns the name of the variable read by the operand, if any.
+    fn input(&self) -> Option<&str> {
+        match self {
+            Operand::Variable(variable) => Some(variable),
+            Operand::Input(_) | Operand::State(_) => None,
+        }
+    }
+
+    /// Returns the current value of the operand.
+    async fn resolve(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<Variable, StepFailure> {
+        let value = match self {
+            Operand::Variable(name) => exec.get_variable(name).cloned(),
+            Operand::State(key) => ctx.get::<Variable>(key).await?,
+            Operand::Input(path) => path
+                .select(&input.0)
+                .map(|value| {
+                    Variable::try_from(value.clone()).map_err(|_| {
+                        TerminalError::new(format!("input {path} is not a literal value"))
+                    })
+                })
+                .transpose()?,
+        };
+
+        Ok(value.unwrap_or(Variable::Null))
+    }
+}
+
+/// Runs a nested list of steps in a child scope of `exec`, keeping the `export` variables.
+async fn run_scoped(
+    steps: &[BoxStep],
+    export: &[String],
+    ctx: &WorkflowContext<'_>,
+    exec: &mut ExecutionContext,
+    input: &JsonValue,
+) -> Result<(), StepFailure> {
+    let mut scope = exec.scope();
+    for step in steps {
+        step.run(ctx, &mut scope, input).await?;
+
+        if scope.is_finished() {
+            break;
+        }
+    }
+
+    exec.close(scope, export);
+
+    Ok(())
+}
+
+/// Compares two variables. Integers and floats compare as numbers, the other variables
+/// only compare to variables of the same kind. Lists compare item by item and objects
+/// are only ever equal (or not comparable).
+fn compare(left: &Variable, right: &Variable) -> Option<Ordering> {
+    match (left, right) {
+        (Variable::Integer(left), Variable::Integer(right)) => Some(left.cmp(right)),
+        (
+            Variable::Integer(_) | Variable::Number(_),
+            Variable::Integer(_) | Variable::Number(_),
+        ) => {
+            let number = |variable: &Variable| match variable {
+                Variable::Integer(i) => *i as f64,
+                Variable::Number(n) => *n,
+                _ => unreachable!(),
+            };
+            number(left).partial_cmp(&number(right))
+        }
+        (Variable::String(left), Variable::String(right)) => Some(left.cmp(right)),
+        (Variable::Boolean(left), Variable::Boolean(right)) => Some(left.cmp(right)),
+        (Variable::Bytes(left), Variable::Bytes(right)) => Some(left.cmp(right)),
+        (Variable::Array(left), Variable::Array(right)) => {
+            for (left, right) in left.iter().zip(right) {
+                match compare(left, right)? {
+                    Ordering::Equal => continue,
+                    ordering => return Some(ordering),
+                }
+            }
+            Some(left.len().cmp(&right.len()))
+        }
+        (Variable::Object(left), Variable::Object(right)) => {
+            let equal = left.len() == right.len()
+                && left.iter().all(|(name, left)| {
+                    right
+                        .get(name)
+                        .is_some_and(|right| compare(left, right) == Some(Ordering::Equal))
+                });
+            equal.then_some(Ordering::Equal)
+        }
+        (Variable::Null, Variable::Null) => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+/// A step that compares a variable (or a part of the input) to a value and executes one
+/// of two nested lists of steps.
+struct ConditionStep {
+    operand: Operand,
+    op: Operator,
+    value: Variable,
+    /// The steps executed if the comparison holds.
+    then: Vec<BoxStep>,
+    /// The steps executed otherwise.
+    otherwise: Vec<BoxStep>,
+    /// The variables of the branch scope copied to the enclosing scope.
+    export: Vec<String>,
+}
+
+impl ConditionStep {
+    /// Creates the step, along with the nested steps of both branches.
+    async fn new(
+        operand: Operand,
+        op: Operator,
+        value: Variable,
+        then: Vec<config::StepConfig>,
+        otherwise: Vec<config::StepConfig>,
+        export: Vec<String>,
+    ) -> Result<Self, StepError> {
+        let then_steps = create_steps("then", then).await?;
+        let otherwise_steps = create_steps("else", otherwise).await?;
+
+        Ok(ConditionStep {
+            operand,
+            op,
+            value,
+            then: then_steps,
+            otherwise: otherwise_steps,
+            export,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for ConditionStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        if matches!(self.operand, Operand::State(_)) && service_type == ServiceType::Service {
+            return Err(StepError::InvalidServiceType(service_type));
+        }
+
+        self.then
+            .iter()
+            .chain(&self.otherwise)
+            .try_for_each(|step| step.validate(service_type, handler_type))
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.export.iter().map(String::as_str).collect()
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        // the exported variables are read from the branch scope
+        let mut inputs: Vec<&str> = self.export.iter().map(String::as_str).collect();
+        inputs.extend(self.operand.input());
+
+        inputs
+    }
+
+    fn ends_handler(&self) -> bool {
+        let ends = |steps: &[BoxStep]| steps.iter().any(|step| step.ends_handler());
+        ends(&self.then) && ends(&self.otherwise)
+    }
+
+    fn branches(&self) -> Vec<Branch<'_>> {
+        vec![
+            Branch {
+                path: "params.then".to_owned(),
+                steps: &self.then,
+                reachable: true,
+            },
+            Branch {
+                path: "params.else".to_owned(),
+                steps: &self.otherwise,
+                reachable: true,
+            },
+        ]
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let operand = self.operand.resolve(ctx, exec, input).await?;
+        let steps = if self.op.test(&operand, &self.value) {
+            &self.then
+        } else {
+            &self.otherwise
+        };
+
+        run_scoped(steps, &self.export, ctx, exec, input).await
+    }
+}
+
+/// Factory for creating `SwitchStep` instances.
+struct Switch;
+
+#[async_trait::async_trait]
+impl StepFactory for Switch {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("variable", ParamType::String)
+            .optional("path", ParamType::JsonPath)
+            .required(
+                "cases",
+                ParamType::list(ParamType::Object(
+                    Schema::new()
+                        .required("value", ParamType::Literal)
+                        .required("steps", ParamType::Steps),
+                )),
+            )
+            .optional("default", ParamType::Steps)
+            .optional("export", ParamType::list(ParamType::String))
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: SwitchStepConfig = serde_yaml::from_value(params)?;
+        let operand = Operand::new(config.variable, config.path)?;
+
+        let mut cases: Vec<(Variable, Vec<BoxStep>)> = Vec::new();
+        for (idx, case) in config.cases.into_iter().enumerate() {
+            if cases
+                .iter()
+                .any(|(value, _)| compare(value, &case.value) == Some(Ordering::Equal))
+            {
+                return Err(StepError::InvalidParameter {
+                    field: "cases".to_owned(),
+                    reason: format!("case {idx} repeats the value of an earlier case"),
+                });
+            }
+
+            let steps = create_steps(&format!("cases[{idx}].steps"), case.steps).await?;
+            cases.push((case.value, steps));
+        }
+
+        let default = create_steps("default", config.default).await?;
+
+        Ok(Box::new(SwitchStep {
+            operand,
+            cases,
+            default,
+            export: config.export,
+        }))
+    }
+}
+
+/// Configuration for a `SwitchStep` as defined in the YAML.
+#[derive(Debug, Deserialize)]
+struct SwitchStepConfig {
+    /// Optional: The name of the variable matched against the cases.
+    variable: Option<String>,
+    /// Optional: A JSONPath selecting the part of the input matched against the cases.
+    path: Option<JsonPath>,
+    /// The cases, matched in order.
+    cases: Vec<SwitchCaseConfig>,
+    /// Optional: The steps executed if no case matches.
+    #[serde(default)]
+    default: Vec<config::StepConfig>,
+    /// Optional: The variables set by the nested steps that are kept after the switch.
+    #[serde(default)]
+    export: Vec<String>,
+}
+
+/// A case of a `switch` step.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct SwitchCaseConfig {
+    /// The literal value selecting the case.
+    #[serde_as(as = "serde_with::TryFromInto<serde_json::Value>")]
+    value: Variable,
+    /// The steps executed if the case is selected.
+    steps: Vec<config::StepConfig>,
+}
+
+/// A step that matches a variable (or a part of the input) against a list of cases and
+/// executes the nested steps of the first equal one, or the default steps.
+struct SwitchStep {
+    operand: Operand,
+    /// The values of the cases along with their steps.
+    cases: Vec<(Variable, Vec<BoxStep>)>,
+    /// The steps executed if no case matches.
+    default: Vec<BoxStep>,
+    /// The variables of the case scope copied to the enclosing scope.
+    export: Vec<String>,
+}
+
+impl SwitchStep {
+    /// Returns the steps of the first case equal to the `operand`, or the default steps.
+    fn select(&self, operand: &Variable) -> &[BoxStep] {
+        self.cases
+            .iter()
+            .find(|(value, _)| compare(operand, value) == Some(Ordering::Equal))
+            .map_or(&self.default, |(_, steps)| steps)
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for SwitchStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        self.cases
+            .iter()
+            .flat_map(|(_, steps)| steps)
+            .chain(&self.default)
+            .try_for_each(|step| step.validate(service_type, handler_type))
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        self.export.iter().map(String::as_str).collect()
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        // the exported variables are read from the case scope
+        let mut inputs: Vec<&str> = self.export.iter().map(String::as_str).collect();
+        inputs.extend(self.operand.input());
+
+        inputs
+    }
+
+    fn ends_handler(&self) -> bool {
+        let ends = |steps: &[BoxStep]| steps.iter().any(|step| step.ends_handler());
+        self.cases.iter().all(|(_, steps)| ends(steps)) && ends(&self.default)
+    }
+
+    fn branches(&self) -> Vec<Branch<'_>> {
+        let mut branches: Vec<Branch<'_>> = self
+            .cases
+            .iter()
+            .enumerate()
+            .map(|(idx, (_, steps))| Branch {
+                path: format!("params.cases[{idx}].steps"),
+                steps,
+                reachable: true,
+            })
+            .collect();
+        branches.push(Branch {
+            path: "params.default".to_owned(),
+            steps: &self.default,
+            reachable: true,
+        });
+
+        branches
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let operand = self.operand.resolve(ctx, exec, input).await?;
+        let steps = self.select(&operand);
+
+        run_scoped(steps, &self.export, ctx, exec, input).await
+    }
+}
+
+/// Factory for creating `AssertStep` instances.
+struct Assert;
+
+#[async_trait::async_trait]
+impl StepFactory for Assert {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("variable", ParamType::String)
+            .optional("path", ParamType::JsonPath)
+            .optional("key", ParamType::String)
+            .optional("op", ParamType::Enum(OPERATORS))
+            .optional("value", ParamType::Literal)
+            .optional("code", ParamType::Integer)
+            .optional("message", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: AssertStepConfig = serde_yaml::from_value(params)?;
+
+        let operand = match (config.variable, config.path, config.key) {
+            (variable, path, None) => {
+                Operand::new(variable, path).map_err(|_| invalid_operand())?
+            }
+            (None, None, Some(key)) => Operand::State(key),
+            _ => return Err(invalid_operand()),
+        };
+
+        Ok(Box::new(AssertStep {
+            operand,
+            op: config.op,
+            value: config.value,
+            code: config.code,
+            message: config.message,
+        }))
+    }
+}
+
+fn invalid_operand() -> StepError {
+    StepError::InvalidParameter {
+        field: "variable".to_owned(),
+        reason: "exactly one of `variable`, `path` or `key` must be set".to_owned(),
+    }
+}
+
+/// Configuration for an `AssertStep` as defined in the YAML.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct AssertStepConfig {
+    /// Optional: The name of the variable checked.
+    variable: Option<String>,
+    /// Optional: A JSONPath selecting the part of the input checked.
+    path: Option<JsonPath>,
+    /// Optional: The state key checked.
+    key: Option<String>,
+    /// The comparison operator. Defaults to `eq`.
+    #[serde(default)]
+    op: Operator,
+    /// The literal value the operand is compared to. Defaults to `null`.
+    #[serde_as(as = "serde_with::TryFromInto<serde_json::Value>")]
+    #[serde(default = "default_condition_value")]
+    value: Variable,
+    /// The code of the terminal error raised on mismatch. Defaults to `500`.
+    #[serde(default = "default_fail_code")]
+    code: u16,
+    /// Optional: The message of the terminal error, with `${name}` placeholders replaced by
+    /// the text of the variables. Defaults to a description of the mismatch.
+    message: Option<String>,
+}
+
+/// A step that compares a variable, a part of the input or a state value to an expected
+/// value, failing the handler with a terminal error if the comparison does not hold.
+struct AssertStep {
+    operand: Operand,
+    op: Operator,
+    value: Variable,
+    code: u16,
+    message: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl Step for AssertStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        if matches!(self.operand, Operand::State(_)) && service_type == ServiceType::Service {
+            return Err(StepError::InvalidServiceType(service_type));
+        }
+
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        let mut inputs: Vec<&str> = self.operand.input().into_iter().collect();
+        if let Some(message) = &self.message {
+            inputs.extend(template::placeholders(message));
+        }
+
+        inputs
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let operand = self.operand.resolve(ctx, exec, input).await?;
+        if self.op.test(&operand, &self.value) {
+            return Ok(());
+        }
+
+        let message = match &self.message {
+            Some(message) => template::interpolate(message, exec)
+                .map_err(|err| TerminalError::new(err.to_string()))?,
+            None => {
+                let subject = match &self.operand {
+                    Operand::Variable(name) => format!("variable {name}"),
+                    Operand::Input(path) => format!("input {path}"),
+                    Operand::State(key) => format!("state {key}"),
+                };
+                format!(
+                    "assertion failed: {subject} is {}, expected {} {}",
+                    template::text_of(&operand),
+                    self.op,
+                    template::text_of(&self.value)
+                )
+            }
+        };
+
+        Err(TerminalError::new_with_code(self.code, message).into())
+    }
+}
+
+/// Factory for creating `ExpectInputStep` instances.
+struct ExpectInput;
+
+#[async_trait::async_trait]
+impl StepFactory for ExpectInput {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("path", ParamType::JsonPath)
+            .required("expected", ParamType::Any)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: ExpectInputStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that asserts the handler input (or a part of it) matches an expected value.
+/// On mismatch, the handler fails with a terminal error listing all the differences.
+#[derive(Debug, Deserialize)]
+struct ExpectInputStep {
+    /// Optional: A JSONPath selecting the part of the input to compare. Defaults to the whole input.
+    path: Option<JsonPath>,
+    /// The expected value.
+    expected: serde_json::Value,
+}
+
+#[async_trait::async_trait]
+impl Step for ExpectInputStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        _exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let (path, actual) = match &self.path {
+            Some(path) => (path.to_string(), path.select(&input.0)),
+            None => ("$".to_owned(), Some(&input.0)),
+        };
+
+        let differences = match actual {
+            Some(actual) => json::diff(&path, &self.expected, actual),
+            None => vec![format!("{path}: missing, expected {}", self.expected)],
+        };
+
+        if !differences.is_empty() {
+            return Err(TerminalError::new_with_code(
+                400,
+                format!("unexpected input:\n{}", differences.join("\n")),
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `ValidateInputStep` instances.
+struct ValidateInput;
+
+#[async_trait::async_trait]
+impl StepFactory for ValidateInput {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("schema", ParamType::Any)
+            .optional("code", ParamType::Integer)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: ValidateInputConfig = serde_yaml::from_value(params)?;
+
+        let validator = jsonschema::validator_for(&config.schema).map_err(|err| {
+            StepError::InvalidParameter {
+                field: "schema".to_owned(),
+                reason: format!("invalid JSON Schema: {err}"),
+            }
+        })?;
+
+        Ok(Box::new(ValidateInputStep {
+            validator,
+            code: config.code,
+        }))
+    }
+}
+
+/// The configuration of a [`ValidateInputStep`].
+#[derive(Debug, Deserialize)]
+struct ValidateInputConfig {
+    /// The JSON Schema the input must conform to.
+    schema: serde_json::Value,
+    /// The code of the terminal error. Defaults to `400`.
+    #[serde(default = "default_validate_input_code")]
+    code: u16,
+}
+
+fn default_validate_input_code() -> u16 {
+    400
+}
+
+/// A step that checks the handler input against a JSON Schema. On violations, the handler
+/// fails with a terminal error listing all of them.
+struct ValidateInputStep {
+    validator: jsonschema::Validator,
+    code: u16,
+}
+
+impl ValidateInputStep {
+    /// Returns the violations of the schema by the input, each prefixed by the JSON pointer
+    /// of the violating part.
+    fn violations(&self, input: &serde_json::Value) -> Vec<String> {
+        self.validator
+            .iter_errors(input)
+            .map(|err| {
+                let path = err.instance_path().to_string();
+                let path = if path.is_empty() { "/" } else { &path };
+                format!("{path}: {err}")
+            })
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for ValidateInputStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        _exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let violations = self.violations(&input.0);
+
+        if !violations.is_empty() {
+            return Err(TerminalError::new_with_code(
+                self.code,
+                format!("invalid input:\n{}", violations.join("\n")),
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `VerifyProduceStep` instances.
+struct VerifyProduce;
+
+#[async_trait::async_trait]
+impl StepFactory for VerifyProduce {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("service", ParamType::String)
+            .optional("handler", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: VerifyProduceStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// The input of a [`VerifyProduceStep`].
+#[derive(Debug, Deserialize)]
+struct VerifyProduceInput {
+    /// The key of the counter object receiving the increments.
+    counter: String,
+    /// The number of increments to send.
+    increments: usize,
+}
+
+/// A step of the exactly-once verification topology that sends the numbered increments
+/// `1..=increments` to a counter object. The input is `{"counter": <key>, "increments": <n>}`.
+#[derive(Debug, Deserialize)]
+struct VerifyProduceStep {
+    /// The name of the counter virtual object.
+    service: String,
+    /// The handler of the counter receiving the increments. Defaults to `add`.
+    #[serde(default = "default_verify_add_handler")]
+    handler: String,
+}
+
+fn default_verify_add_handler() -> String {
+    "add".to_owned()
+}
+
+#[async_trait::async_trait]
+impl Step for VerifyProduceStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        _exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let input: VerifyProduceInput = serde_json::from_value(input.0.clone())
+            .map_err(|err| TerminalError::new(format!("invalid input: {err}")))?;
+
+        for seq in 1..=input.increments {
+            let target = RequestTarget::Object {
+                name: self.service.clone(),
+                key: input.counter.clone(),
+                handler: self.handler.clone(),
+            };
+
+            ctx.request::<_, ()>(target, JsonValue(serde_json::json!({ "amount": seq })))
+                .send();
+        }
+
+        Ok(())
+    }
+}
+
+/// The totals maintained by [`VerifyAddStep`].
+#[derive(Debug, Default, serde::Serialize, Deserialize)]
+struct IncrementTotals {
+    /// The sum of all the received amounts.
+    total: u64,
+    /// The number of received increments.
+    deliveries: u64,
+}
+
+impl IncrementTotals {
+    /// Adds a received amount, failing if the total overflows.
+    fn add(&mut self, amount: u64) -> Result<(), TerminalError> {
+        self.total = self.total.checked_add(amount).ok_or_else(|| {
+            TerminalError::new(format!(
+                "adding {amount} to the total {} overflows",
+                self.total
+            ))
+        })?;
+        self.deliveries += 1;
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `VerifyAddStep` instances.
+struct VerifyAdd;
+
+#[async_trait::async_trait]
+impl StepFactory for VerifyAdd {
+    fn schema(&self) -> Schema {
+        Schema::new().required("key", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: VerifyAddStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step of the exactly-once verification topology that adds the received increment
+/// (input `{"amount": <n>}`) to the totals stored in the Restate state.
+/// This step is only valid for services of type `VIRTUAL_OBJECT` or `WORKFLOW`.
+#[derive(Debug, Deserialize)]
+struct VerifyAddStep {
+    /// The state key holding the totals.
+    key: String,
+}
+
+#[async_trait::async_trait]
+impl Step for VerifyAddStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        validate_state_write(service_type, handler_type)
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        _exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let amount = input.0["amount"]
+            .as_u64()
+            .ok_or_else(|| TerminalError::new("invalid input: expected an `amount`"))?;
+
+        let mut totals: IncrementTotals = match ctx.get::<JsonValue>(&self.key).await? {
+            Some(JsonValue(totals)) => serde_json::from_value(totals)?,
+            None => IncrementTotals::default(),
+        };
+
+        totals.add(amount)?;
+
+        ctx.set(&self.key, JsonValue(serde_json::to_value(&totals)?));
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `VerifyTotalsStep` instances.
+struct VerifyTotals;
+
+#[async_trait::async_trait]
+impl StepFactory for VerifyTotals {
+    fn schema(&self) -> Schema {
+        Schema::new().required("key", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: VerifyTotalsStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step of the exactly-once verification topology that returns the totals recorded
+/// by [`VerifyAddStep`] as `{"total": <sum>, "deliveries": <count>}`.
+/// This step is only valid for services of type `VIRTUAL_OBJECT` or `WORKFLOW`.
+#[derive(Debug, Deserialize)]
+struct VerifyTotalsStep {
+    /// The state key holding the totals.
+    key: String,
+}
+
+#[async_trait::async_trait]
+impl Step for VerifyTotalsStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        if service_type == ServiceType::Service {
+            return Err(StepError::InvalidServiceType(service_type));
+        }
+
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let totals = ctx
+            .get::<JsonValue>(&self.key)
+            .await?
+            .unwrap_or_else(|| JsonValue(serde_json::json!(IncrementTotals::default())));
+
+        exec.return_value(totals);
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `RequireHeaderStep` instances.
+struct RequireHeader;
+
+#[async_trait::async_trait]
+impl StepFactory for RequireHeader {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .optional("header", ParamType::String)
+            .optional("value", ParamType::String)
+            .optional("pattern", ParamType::Regex)
+            .optional("bearer", ParamType::Boolean)
+            .optional("code", ParamType::Integer)
+            .optional("message", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let config: config::AuthConfig = serde_yaml::from_value(params)?;
+
+        if config.value.is_some() && config.pattern.is_some() {
+            return Err(StepError::InvalidParameter {
+                field: "pattern".to_owned(),
+                reason: "can not be used together with `value`".to_owned(),
+            });
+        }
+
+        let pattern = config
+            .pattern
+            .as_ref()
+            .map(|pattern| regex::Regex::new(&format!("^(?:{pattern})$")))
+            .transpose()
+            .map_err(|err| StepError::InvalidParameter {
+                field: "pattern".to_owned(),
+                reason: err.to_string(),
+            })?;
+
+        Ok(Box::new(RequireHeaderStep { config, pattern }))
+    }
+}
+
+/// A step that simulates authentication by requiring a request header. Missing or wrong
+/// credentials fail the handler with a configurable terminal error.
+struct RequireHeaderStep {
+    config: config::AuthConfig,
+    /// The compiled `pattern` of the configuration.
+    pattern: Option<regex::Regex>,
+}
+
+impl RequireHeaderStep {
+    fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        // header names are case-insensitive, the map lowercases the name on lookup
+        let value = headers.get(self.config.header.as_str());
+        let credentials = match (value, self.config.bearer) {
+            (None, _) => return false,
+            (Some(value), false) => value.as_str(),
+            (Some(value), true) => match value.strip_prefix("Bearer ") {
+                Some(token) => token,
+                None => return false,
+            },
+        };
+
+        match (&self.config.value, &self.pattern) {
+            (Some(expected), _) => credentials == expected,
+            (None, Some(pattern)) => pattern.is_match(credentials),
+            (None, None) => true,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for RequireHeaderStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        _exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        if !self.is_authorized(ctx.headers()) {
+            let config = &self.config;
+            return Err(TerminalError::new_with_code(config.code, &config.message).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Factory for creating `NotImplementedStep` instances.
+struct NotImplemented;
+
+#[async_trait::async_trait]
+impl StepFactory for NotImplemented {
+    fn schema(&self) -> Schema {
+        Schema::new()
+    }
+
+    async fn create(&self, _params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        Ok(Box::new(NotImplementedStep))
+    }
+}
+
+/// A step that fails the handler with a "not implemented" terminal error.
+/// This is the only step of stub handlers.
+struct NotImplementedStep;
+
+#[async_trait::async_trait]
+impl Step for NotImplementedStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        _exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        Err(TerminalError::new_with_code(501, "not implemented").into())
+    }
+}
+
+/// Factory for creating `FailStep` instances.
+struct Fail;
+
+#[async_trait::async_trait]
+impl StepFactory for Fail {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("message", ParamType::String)
+            .optional("code", ParamType::Integer)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: FailStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that deterministically fails the handler with a terminal error.
+#[derive(Debug, Deserialize)]
+struct FailStep {
+    /// The message of the terminal error, with `${name}` placeholders replaced by the text
+    /// of the variables.
+    message: String,
+    /// The code of the terminal error. Defaults to `500`.
+    #[serde(default = "default_fail_code")]
+    code: u16,
+}
+
+fn default_fail_code() -> u16 {
+    config::DEFAULT_ERROR_CODE
+}
+
+#[async_trait::async_trait]
+impl Step for FailStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        template::placeholders(&self.message)
+    }
+
+    fn ends_handler(&self) -> bool {
+        true
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let message = template::interpolate(&self.message, exec)
+            .map_err(|err| TerminalError::new(err.to_string()))?;
+
+        Err(TerminalError::new_with_code(self.code, message).into())
+    }
+}
+
+/// Factory for creating `FailIfStep` instances.
+struct FailIf;
+
+/// The accepted values of the `error` parameter of a `fail-if` step.
+const ERROR_KINDS: &[&str] = &["terminal", "retryable"];
+
+#[async_trait::async_trait]
+impl StepFactory for FailIf {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("path", ParamType::JsonPath)
+            .optional("op", ParamType::Enum(OPERATORS))
+            .optional("value", ParamType::Literal)
+            .optional("error", ParamType::Enum(ERROR_KINDS))
+            .optional("code", ParamType::Integer)
+            .optional("message", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: FailIfStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that fails the handler if a part of the input matches a value, and does nothing
+/// otherwise, so load drivers can trigger failures per request (e.g. with a `simulate`
+/// field).
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct FailIfStep {
+    /// A JSONPath selecting the part of the input compared to the value, `null` if missing.
+    path: JsonPath,
+    /// The comparison operator. Defaults to `eq`.
+    #[serde(default)]
+    op: Operator,
+    /// The literal value the input is compared to. Defaults to `null`.
+    #[serde_as(as = "serde_with::TryFromInto<serde_json::Value>")]
+    #[serde(default = "default_condition_value")]
+    value: Variable,
+    /// The kind of the error. Defaults to terminal.
+    #[serde(default = "default_error_kind")]
+    error: config::ErrorKind,
+    /// The code of a terminal error. Defaults to `500`.
+    #[serde(default = "default_fail_code")]
+    code: u16,
+    /// Optional: The message of the error, with `${name}` placeholders replaced by the text
+    /// of the variables.
+    message: Option<String>,
+}
+
+fn default_error_kind() -> config::ErrorKind {
+    config::ErrorKind::Terminal
+}
+
+#[async_trait::async_trait]
+impl Step for FailIfStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        self.message
+            .as_deref()
+            .map(template::placeholders)
+            .unwrap_or_default()
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let operand = match self.path.select(&input.0) {
+            Some(value) => Variable::try_from(value.clone()).map_err(|_| {
+                TerminalError::new(format!("input {} is not a literal value", self.path))
+            })?,
+            None => Variable::Null,
+        };
+
+        if !self.op.test(&operand, &self.value) {
+            return Ok(());
+        }
+
+        let message = match &self.message {
+            Some(message) => template::interpolate(message, exec)
+                .map_err(|err| TerminalError::new(err.to_string()))?,
+            None => format!(
+                "simulated failure: input {} is {}",
+                self.path,
+                template::text_of(&operand)
+            ),
+        };
+
+        Err(injected_error(self.error, self.code, message))
+    }
+}
+
+/// Factory for creating `RetryableErrorStep` instances.
+struct RetryableError;
+
+#[async_trait::async_trait]
+impl StepFactory for RetryableError {
+    fn schema(&self) -> Schema {
+        Schema::new().optional("message", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: RetryableErrorStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that fails the handler with a retryable error, so the invocation is retried by
+/// Restate according to its retry policy.
+#[derive(Debug, Deserialize)]
+struct RetryableErrorStep {
+    /// The message of the error, with `${name}` placeholders replaced by the text of the
+    /// variables.
+    #[serde(default = "default_retryable_message")]
+    message: String,
+}
+
+fn default_retryable_message() -> String {
+    "injected retryable error".to_owned()
+}
+
+#[async_trait::async_trait]
+impl Step for RetryableErrorStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        template::placeholders(&self.message)
+    }
+
+    fn ends_handler(&self) -> bool {
+        true
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let message = template::interpolate(&self.message, exec)
+            .map_err(|err| TerminalError::new(err.to_string()))?;
+
+        Err(StepFailure::from(message))
+    }
+}
+
+/// Factory for creating `MaybeFailStep` instances.
+struct MaybeFail;
+
+#[async_trait::async_trait]
+impl StepFactory for MaybeFail {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("probability", ParamType::Number)
+            .optional("terminal_ratio", ParamType::Number)
+            .optional("code", ParamType::Integer)
+            .optional("message", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: MaybeFailStep = serde_yaml::from_value(params)?;
+
+        for (field, value) in [
+            ("probability", step.probability),
+            ("terminal_ratio", step.terminal_ratio),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(StepError::InvalidParameter {
+                    field: field.to_owned(),
+                    reason: "must be between 0.0 and 1.0".to_owned(),
+                });
+            }
+        }
+
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that fails a fraction of the invocations, with a mix of terminal and retryable
+/// errors, to simulate flaky services.
+#[derive(Debug, Deserialize)]
+struct MaybeFailStep {
+    /// The probability (0.0 to 1.0) that the step fails.
+    probability: f64,
+    /// The fraction (0.0 to 1.0) of the failures that are terminal, the others are
+    /// retryable. Defaults to `0.0`.
+    #[serde(default)]
+    terminal_ratio: f64,
+    /// The code of the terminal errors. Defaults to `500`.
+    #[serde(default = "default_fail_code")]
+    code: u16,
+    /// The message of the errors.
+    #[serde(default = "default_maybe_fail_message")]
+    message: String,
+}
+
+fn default_maybe_fail_message() -> String {
+    "injected random failure".to_owned()
+}
+
+#[async_trait::async_trait]
+impl Step for MaybeFailStep {
+    fn validate(
+        &self,
+        _service_type: ServiceType,
+        _handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        Ok(())
+    }
+
+    fn deterministic(&self) -> bool {
+        false
+    }
+
+    async fn run(
+        &self,
+        _ctx: &WorkflowContext<'_>,
+        _exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let mut rng = rand::rng();
+        if !rng.random_bool(self.probability) {
+            return Ok(());
+        }
+
+        let kind = if rng.random_bool(self.terminal_ratio) {
+            config::ErrorKind::Terminal
+        } else {
+            config::ErrorKind::Retryable
+        };
+
+        Err(injected_error(kind, self.code, self.message.clone()))
+    }
+}
+
+/// Factory for creating `FailTimesStep` instances.
+struct FailTimes;
+
+#[async_trait::async_trait]
+impl StepFactory for FailTimes {
+    fn schema(&self) -> Schema {
+        Schema::new()
+            .required("times", ParamType::Integer)
+            .optional("key", ParamType::String)
+            .optional("error", ParamType::Enum(ERROR_KINDS))
+            .optional("code", ParamType::Integer)
+            .optional("message", ParamType::String)
+    }
+
+    async fn create(&self, params: serde_yaml::Value) -> Result<BoxStep, StepError> {
+        let step: FailTimesStep = serde_yaml::from_value(params)?;
+        Ok(Box::new(step))
+    }
+}
+
+/// A step that fails the first `times` attempts, then succeeds.
+///
+/// With terminal errors, the attempts are the invocations of a virtual object or workflow
+/// key, counted in the Restate state of the key: the retries of an invocation replay the
+/// journaled state, so the counter could never move forward. Each attempt is thus a new
+/// invocation for the same key.
+///
+/// With retryable errors, the attempts are the retries of a single invocation, counted in
+/// the memory of the process (by invocation ID) since they must not be journaled.
+#[derive(Debug, Deserialize)]
+struct FailTimesStep {
+    /// The number of attempts failing before the step succeeds.
+    times: isize,
+    /// The state key holding the number of attempts. Defaults to `attempts`.
+    #[serde(default = "default_attempts_key")]
+    key: String,
+    /// The kind of the error. Defaults to terminal.
+    #[serde(default = "default_error_kind")]
+    error: config::ErrorKind,
+    /// The code of a terminal error. Defaults to `500`.
+    #[serde(default = "default_fail_code")]
+    code: u16,
+    /// The message of the error.
+    #[serde(default = "default_fail_times_message")]
+    message: String,
+    /// The number of attempts of the retried invocations, by invocation ID.
+    #[serde(skip)]
+    retries: Mutex<RetryCounts>,
+}
+
+/// The maximum number of invocations whose retries are counted by a [`FailTimesStep`].
+const MAX_COUNTED_RETRIES: usize = 10_000;
+
+/// The number of attempts of the invocations retried by a [`FailTimesStep`], along with
+/// when they were last counted, so the invocations that never succeed (e.g. killed or
+/// cancelled) can be evicted.
+#[derive(Debug, Default)]
+struct RetryCounts {
+    /// The number of attempts and the tick of the last attempt, by invocation ID.
+    attempts: HashMap<String, (isize, u64)>,
+    /// Incremented on every attempt.
+    tick: u64,
+}
+
+fn default_attempts_key() -> String {
+    "attempts".to_owned()
+}
+
+fn default_fail_times_message() -> String {
+    "injected failure".to_owned()
+}
+
+impl FailTimesStep {
+    /// Counts a retry of the invocation, returning the number of the previous attempts.
+    ///
+    /// The count of the invocation is dropped once it reaches `times`, as the attempt
+    /// succeeds: a later retry of the invocation fails `times` times again. At most
+    /// [`MAX_COUNTED_RETRIES`] invocations are counted, the one counted the longest ago is
+    /// evicted to make room for a new one.
+    fn retry(&self, invocation_id: &str) -> isize {
+        let mut retries = self.retries.lock().expect("retries lock");
+        retries.tick += 1;
+        let tick = retries.tick;
+
+        if !retries.attempts.contains_key(invocation_id)
+            && retries.attempts.len() >= MAX_COUNTED_RETRIES
+        {
+            let oldest = retries
+                .attempts
+                .iter()
+                .min_by_key(|(_, (_, counted))| *counted)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                retries.attempts.remove(&oldest);
+            }
+        }
+
+        let (attempts, counted) = retries
+            .attempts
+            .entry(invocation_id.to_owned())
+            .or_default();
+        *counted = tick;
+        if *attempts < self.times {
+            *attempts += 1;
+            return *attempts - 1;
+        }
+
+        retries.attempts.remove(invocation_id);
+        self.times
+    }
+}
+
+#[async_trait::async_trait]
+impl Step for FailTimesStep {
+    fn validate(
+        &self,
+        service_type: ServiceType,
+        handler_type: Option<HandlerType>,
+    ) -> Result<(), StepError> {
+        match self.error {
+            config::ErrorKind::Terminal => validate_state_write(service_type, handler_type),
+            config::ErrorKind::Retryable => Ok(()),
+        }
+    }
+
+    fn deterministic(&self) -> bool {
+        // the retryable attempts are counted in memory
+        self.error == config::ErrorKind::Terminal
+    }
+
+    async fn run(
+        &self,
+        ctx: &WorkflowContext<'_>,
+        exec: &mut ExecutionContext,
+        _input: &JsonValue,
+    ) -> Result<(), StepFailure> {
+        let attempts = match self.error {
+            config::ErrorKind::Terminal => match ctx.get::<Variable>(&self.key).await? {
+                Some(Variable::Integer(attempts)) => attempts,
+                _ => 0,
+            },
+            config::ErrorKind::Retryable => self.retry(exec.invocation_id()),
+        };
+        if attempts >= self.times {
+            return Ok(());
+        }
+
+        if self.error == config::ErrorKind::Terminal {
+            ctx.set(&self.key, Variable::Integer(attempts + 1));
+        }
+
+        let message = format!(
+            "{} (attempt {} of {})",
+            self.message,
+            attempts + 1,
+            self.times
+        );
+        Err(injected_error(self.error, self.code, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a step of type `ty` from `params`, the entries of its inline YAML params.
+    fn create(
+        ty: &str,
+        params: &str,
+    ) -> impl std::future::Future<Output = Result<BoxStep, StepError>> {
+        let config = format!("{{type: {ty}, params: {{{params}}}}}");
+        create_step(serde_yaml::from_str(&config).unwrap())
+    }
+
+    fn latency(source: &str) -> Latency {
+        serde_yaml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn test_latency_sample() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let fixed = latency("{duration: 100ms}");
+        assert_eq!(fixed.sample(&mut rng), Duration::from_millis(100));
+
+        let jittered = latency("{duration: 100ms, jitter: 0.5}");
+        for _ in 0..100 {
+            let duration = jittered.sample(&mut rng);
+            assert!(duration >= Duration::from_millis(100));
+            assert!(duration <= Duration::from_millis(150));
+        }
+
+        // the same seed picks the same durations
+        let sample = |seed| jittered.sample(&mut StdRng::seed_from_u64(seed));
+        assert_eq!(sample(7), sample(7));
+
+        let weighted = latency("{durations: [{duration: 5ms}, {duration: 1s, weight: 0}]}");
+        for _ in 0..100 {
+            assert_eq!(weighted.sample(&mut rng), Duration::from_millis(5));
+        }
+
+        let constant = latency("{distribution: {type: normal, mean: 20ms, stddev: 0ms}}");
+        assert_eq!(constant.sample(&mut rng), Duration::from_millis(20));
+
+        for distribution in [
+            "{type: normal, mean: 20ms, stddev: 50ms}",
+            "{type: exponential, mean: 20ms}",
+            "{type: log-normal, mean: 20ms, stddev: 50ms}",
+            "{type: pareto, scale: 20ms, shape: 1.5}",
+        ] {
+            let clamped = latency(&format!(
+                "{{distribution: {distribution}, min: 10ms, max: 30ms}}"
+            ));
+            for _ in 0..100 {
+                let duration = clamped.sample(&mut rng);
+                assert!(duration >= Duration::from_millis(10), "{distribution}");
+                assert!(duration <= Duration::from_millis(30), "{distribution}");
             }
         }
 
-        Ok(())
+        // the heavy tails of a distribution without a maximum are capped, jitter included
+        let heavy = latency("{distribution: {type: pareto, scale: 1s, shape: 0.01}, jitter: 1.0}");
+        let durations: Vec<_> = (0..1000).map(|_| heavy.sample(&mut rng)).collect();
+        assert!(durations.iter().all(|duration| *duration <= MAX_DELAY));
+        assert!(durations.contains(&MAX_DELAY));
+        let long = latency("{duration: 100years, min: 200years}");
+        assert_eq!(long.sample(&mut rng), MAX_DELAY);
+    }
+
+    #[test]
+    fn test_latency_check() {
+        assert!(latency("{duration: 1s}").check().is_ok());
+        assert!(latency("{duration: 1s, jitter: 1.0}").check().is_ok());
+        assert!(latency("{durations: [{duration: 1s, weight: 2}]}")
+            .check()
+            .is_ok());
+
+        for invalid in [
+            "{}",
+            "{duration: 1s, durations: [{duration: 1s}]}",
+            "{durations: [{duration: 1s, weight: 0}]}",
+            "{duration: 1s, distribution: {type: exponential, mean: 1s}}",
+            "{distribution: {type: pareto, scale: 1s, shape: -1}}",
+            "{duration: 1s, min: 2s, max: 1s}",
+            "{duration: 1s, jitter: -0.5}",
+            "{duration: 1s, jitter: .nan}",
+            "{duration: 1s, jitter: .inf}",
+            "{duration: 1s, jitter: 1.5}",
+            "{duration: 1s, jitter: 50}",
+        ] {
+            assert!(
+                matches!(
+                    latency(invalid).check(),
+                    Err(StepError::InvalidParameter { .. })
+                ),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_condition() {
+        let holds = |left: Variable, op: Operator, right: Variable| op.test(&left, &right);
+
+        assert!(holds(
+            Variable::Integer(2),
+            Operator::Eq,
+            Variable::Number(2.0)
+        ));
+        assert!(holds(
+            Variable::Integer(1),
+            Operator::Lt,
+            Variable::Number(1.5)
+        ));
+        assert!(holds(
+            Variable::Integer(2),
+            Operator::Ge,
+            Variable::Integer(2)
+        ));
+        assert!(holds(
+            Variable::String("b".to_owned()),
+            Operator::Gt,
+            Variable::String("a".to_owned())
+        ));
+        assert!(holds(Variable::Null, Operator::Eq, Variable::Null));
+
+        // variables of different kinds are never equal nor ordered
+        assert!(!holds(
+            Variable::String("1".to_owned()),
+            Operator::Eq,
+            Variable::Integer(1)
+        ));
+        assert!(holds(
+            Variable::String("1".to_owned()),
+            Operator::Ne,
+            Variable::Integer(1)
+        ));
+        assert!(!holds(Variable::Null, Operator::Le, Variable::Integer(1)));
+
+        assert!(holds(
+            Variable::String("order-42".to_owned()),
+            Operator::Contains,
+            Variable::String("42".to_owned())
+        ));
+        assert!(holds(
+            Variable::Bytes(b"abc".to_vec().into()),
+            Operator::Contains,
+            Variable::Bytes(b"bc".to_vec().into())
+        ));
+        assert!(!holds(
+            Variable::Integer(42),
+            Operator::Contains,
+            Variable::Integer(4)
+        ));
+
+        let list = |value: serde_json::Value| Variable::try_from(value).unwrap();
+        assert!(holds(
+            list(serde_json::json!([1, 2])),
+            Operator::Lt,
+            list(serde_json::json!([1, 3]))
+        ));
+        assert!(holds(
+            list(serde_json::json!([1])),
+            Operator::Lt,
+            list(serde_json::json!([1, 0]))
+        ));
+        assert!(holds(
+            list(serde_json::json!([1, "a"])),
+            Operator::Contains,
+            Variable::String("a".to_owned())
+        ));
+        assert!(holds(
+            list(serde_json::json!({"a": [1], "b": 2.0})),
+            Operator::Eq,
+            list(serde_json::json!({"a": [1], "b": 2}))
+        ));
+        assert!(!holds(
+            list(serde_json::json!({"a": 1})),
+            Operator::Ge,
+            list(serde_json::json!({"a": 2}))
+        ));
+        assert!(holds(
+            list(serde_json::json!({"a": 1})),
+            Operator::Ne,
+            list(serde_json::json!({"a": 1, "b": 1}))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_input_violations() {
+        let params = serde_yaml::from_str(
+            "schema: {type: object, required: [id], properties: {id: {type: integer}}}",
+        )
+        .unwrap();
+        let config: ValidateInputConfig = serde_yaml::from_value(params).unwrap();
+        let step = ValidateInputStep {
+            validator: jsonschema::validator_for(&config.schema).unwrap(),
+            code: config.code,
+        };
+
+        assert!(step.violations(&serde_json::json!({"id": 1})).is_empty());
+        assert_eq!(
+            step.violations(&serde_json::json!({"id": "one"})),
+            vec![r#"/id: "one" is not of type "integer""#.to_owned()]
+        );
+        assert_eq!(
+            step.violations(&serde_json::json!({})),
+            vec![r#"/: "id" is a required property"#.to_owned()]
+        );
+
+        let invalid = serde_yaml::from_str("schema: {type: 12}").unwrap();
+        assert!(ValidateInput.create(invalid).await.is_err());
+    }
+
+    #[test]
+    fn test_sequence_next() {
+        let step: SequenceStep = serde_yaml::from_str("{key: seq, output: n, start: 5}").unwrap();
+
+        assert_eq!(step.next(None).unwrap(), 5);
+        assert_eq!(step.next(Some(Variable::Integer(5))).unwrap(), 6);
+        // an exhausted sequence fails instead of overflowing
+        assert!(step.next(Some(Variable::Integer(isize::MAX))).is_err());
+        assert!(step.next(Some(Variable::Boolean(true))).is_err());
+    }
+
+    #[test]
+    fn test_increment_totals_add() {
+        let mut totals = IncrementTotals::default();
+        totals.add(3).unwrap();
+        totals.add(4).unwrap();
+        assert_eq!((totals.total, totals.deliveries), (7, 2));
+
+        // an overflowing amount is rejected and leaves the totals unchanged
+        assert!(totals.add(u64::MAX).is_err());
+        assert_eq!((totals.total, totals.deliveries), (7, 2));
+    }
+
+    #[test]
+    fn test_counter_add() {
+        let add = |counter, amount| CounterStep::add(&counter, &amount);
+
+        assert!(matches!(
+            add(Variable::Integer(2), Variable::Integer(3)),
+            Some(Variable::Integer(5))
+        ));
+        assert!(matches!(
+            add(Variable::Integer(2), Variable::Integer(-3)),
+            Some(Variable::Integer(-1))
+        ));
+        assert!(matches!(
+            add(Variable::Integer(2), Variable::Number(0.5)),
+            Some(Variable::Number(n)) if n == 2.5
+        ));
+        assert!(add(Variable::Integer(isize::MAX), Variable::Integer(1)).is_none());
+        assert!(add(Variable::String("2".to_owned()), Variable::Integer(1)).is_none());
+    }
+
+    #[test]
+    fn test_call_target() {
+        let target = |source: &str| serde_yaml::from_str::<CallTarget>(source).unwrap();
+
+        let dynamic = target("{target_type: SERVICE, service_from: svc, handler_from: $.op}");
+        assert!(dynamic.check().is_ok());
+        assert!(dynamic.target(InvocationKind::Call).is_none());
+        assert_eq!(dynamic.inputs().collect::<Vec<_>>(), vec!["svc"]);
+
+        let mut exec = ExecutionContext::default();
+        exec.set("svc", "billing".to_owned());
+        let input = JsonValue(serde_json::json!({"op": "charge"}));
+        let resolve =
+            |source: &Option<TargetSource>| source.as_ref().unwrap().resolve(&exec, &input).ok();
+        assert_eq!(resolve(&dynamic.service_from).as_deref(), Some("billing"));
+        assert_eq!(resolve(&dynamic.handler_from).as_deref(), Some("charge"));
+
+        let missing = target("{target_type: SERVICE, service_from: other, handler: h}");
+        assert!(resolve(&missing.service_from).is_none());
+
+        for invalid in [
+            "{target_type: SERVICE, handler: h}",
+            "{target_type: SERVICE, service: s, service_from: svc, handler: h}",
+            "{target_type: VIRTUAL_OBJECT, service: s, handler: h, key: k, key_from: k}",
+        ] {
+            assert!(target(invalid).check().is_err(), "{invalid}");
+        }
+        assert!(serde_yaml::from_str::<CallTarget>(
+            "{target_type: SERVICE, service_from: '$.[', handler: h}"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_sleep_until_parse() {
+        let parse = SleepUntilStep::parse;
+
+        assert_eq!(
+            parse(&Variable::Integer(1_700_000_000_000)),
+            Some(1_700_000_000_000)
+        );
+        assert_eq!(parse(&Variable::String("1500".to_owned())), Some(1500));
+        assert_eq!(
+            parse(&Variable::String("2023-11-14T22:13:20.5Z".to_owned())),
+            Some(1_700_000_000_500)
+        );
+        assert_eq!(parse(&Variable::Integer(-1)), None);
+        assert_eq!(parse(&Variable::String("tomorrow".to_owned())), None);
+        assert_eq!(parse(&Variable::Null), None);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = |factor, max_delay| RetryStep {
+            steps: Vec::new(),
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(100),
+            factor,
+            max_delay,
+            export: Vec::new(),
+        };
+        let step = retry(2.0, Some(Duration::from_millis(300)));
+
+        assert_eq!(step.delay(1), Duration::from_millis(100));
+        assert_eq!(step.delay(2), Duration::from_millis(200));
+        assert_eq!(step.delay(3), Duration::from_millis(300));
+        assert_eq!(step.delay(4), Duration::from_millis(300));
+        // the delays too long to be represented are capped instead of overflowing
+        assert_eq!(step.delay(100), Duration::from_millis(300));
+        assert_eq!(step.delay(u32::MAX), Duration::from_millis(300));
+        // without a maximum delay, the delays are capped to a year
+        assert_eq!(retry(2.0, None).delay(30), MAX_DELAY);
+        assert_eq!(retry(2.0, None).delay(1000), MAX_DELAY);
+        assert_eq!(retry(1e30, None).delay(2), MAX_DELAY);
+
+        // only the retryable errors are retried, until the attempts are exhausted
+        let retryable = StepFailure::from("boom".to_owned());
+        assert_eq!(
+            step.retry_delay(1, &retryable),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            step.retry_delay(4, &retryable),
+            Some(Duration::from_millis(300))
+        );
+        assert_eq!(step.retry_delay(5, &retryable), None);
+        assert_eq!(
+            step.retry_delay(1, &TerminalError::new("boom").into()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_delay_bounds() {
+        // the delays of the most extreme retries never exceed the timers of the SDK
+        let retry = |factor, initial_delay| RetryStep {
+            steps: Vec::new(),
+            max_attempts: u32::MAX,
+            initial_delay,
+            factor,
+            max_delay: None,
+            export: Vec::new(),
+        };
+        for step in [
+            retry(1.0, MAX_DELAY),
+            retry(f32::MAX, Duration::from_millis(1)),
+            retry(1.5, Duration::from_secs(u32::MAX as u64)),
+        ] {
+            let mut previous = Duration::ZERO;
+            for attempt in (1..64).chain([u32::MAX - 1]) {
+                let delay = step.delay(attempt);
+                assert!(delay <= MAX_DELAY, "{attempt}: {delay:?}");
+                assert!(delay >= previous, "{attempt}: {delay:?}");
+                previous = delay;
+            }
+        }
+
+        // so do the samples of the latencies, whatever their tails or jitter
+        let mut rng = StdRng::seed_from_u64(42);
+        for source in [
+            "{duration: 200years, jitter: 1.0}",
+            "{durations: [{duration: 1ms}, {duration: 500years, weight: 1000}], jitter: 1.0}",
+            "{distribution: {type: normal, mean: 300years, stddev: 300years}, jitter: 1.0}",
+            "{distribution: {type: exponential, mean: 300years}, jitter: 1.0}",
+            "{distribution: {type: log-normal, mean: 1s, stddev: 300years}, jitter: 1.0}",
+            "{distribution: {type: pareto, scale: 1s, shape: 0.01}, jitter: 1.0}",
+        ] {
+            let latency = latency(source);
+            assert!(latency.check().is_ok(), "{source}");
+            for _ in 0..100 {
+                let duration = latency.sample(&mut rng);
+                assert!(duration <= MAX_DELAY, "{source}: {duration:?}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_nested_steps() {
+        let step = |nested: &str| create("retry", &format!("steps: [{nested}]"));
+
+        assert!(step("{type: retryable-error}").await.is_ok());
+        assert!(step("{type: fail-times, params: {times: 2}}").await.is_ok());
+
+        // the failures that are not journaled would not replay the same way
+        for nested in [
+            "{type: echo, flaky: {probability: 0.5}}",
+            "{type: maybe-fail, params: {probability: 0.5}}",
+            "{type: fail-times, params: {times: 2, error: retryable}}",
+            "{type: loop, params: {count: 2, steps: [{type: barrier, params: {name: retry, parties: 2}}]}}",
+        ] {
+            let Err(StepError::InvalidParameter { field, .. }) = step(nested).await else {
+                panic!("expected {nested} to be rejected");
+            };
+            assert_eq!(field, "steps[0]");
+        }
+
+        for (field, params) in [
+            ("initial_delay", "initial_delay: 400days"),
+            ("max_delay", "max_delay: 400days"),
+        ] {
+            let Err(StepError::InvalidParameter {
+                field: rejected, ..
+            }) = create("retry", &format!("{params}, steps: [{{type: echo}}]")).await
+            else {
+                panic!("expected {params} to be rejected");
+            };
+            assert_eq!(rejected, field);
+        }
+
+        for factor in ["0.5", ".nan", ".inf"] {
+            let Err(StepError::InvalidParameter { field, .. }) = create(
+                "retry",
+                &format!("factor: {factor}, steps: [{{type: echo}}]"),
+            )
+            .await
+            else {
+                panic!("expected the factor {factor} to be rejected");
+            };
+            assert_eq!(field, "factor");
+        }
+    }
+
+    #[test]
+    fn test_uuid_generate() {
+        let uuid = UuidStep::generate(7);
+        assert_eq!(uuid, UuidStep::generate(7));
+        assert_ne!(uuid, UuidStep::generate(8));
+
+        let parsed = uuid::Uuid::parse_str(&uuid).unwrap();
+        assert_eq!(parsed.get_version(), Some(uuid::Version::Random));
+    }
+
+    #[test]
+    fn test_payload_generate() {
+        let step = |pattern, text: Option<&str>| PayloadStep {
+            size: Size(12),
+            pattern,
+            text: text.map(str::to_owned),
+            output: "payload".to_owned(),
+        };
+
+        let random = step(PayloadPattern::Random, None);
+        let payload = random.generate(7).unwrap();
+        assert_eq!(serde_json::to_string(&payload).unwrap().len(), 12);
+        assert!(payload.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(payload, random.generate(7).unwrap());
+
+        assert_eq!(
+            step(PayloadPattern::Zeros, None).generate(7).unwrap(),
+            "0000000000"
+        );
+        assert_eq!(
+            step(PayloadPattern::Repeat, Some("abc"))
+                .generate(7)
+                .unwrap(),
+            "abcabcabca"
+        );
+
+        // a payload exceeding the address space fails instead of aborting
+        let huge = PayloadStep {
+            size: Size(u64::MAX),
+            ..step(PayloadPattern::Zeros, None)
+        };
+        assert!(huge.generate(7).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_payload_size() {
+        let step = |size: &str| create("payload", &format!("size: {size}, output: payload"));
+
+        assert!(step("2").await.is_ok());
+        assert!(step("1GB").await.is_ok());
+        for size in ["0", "1", "1025MB", "100GB"] {
+            assert!(matches!(
+                step(size).await,
+                Err(StepError::InvalidParameter { .. })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fill_state_size() {
+        let step = |count: usize, size: usize| {
+            create("fill-state", &format!("count: {count}, size: {size}"))
+        };
+
+        assert!(step(1024, 1 << 20).await.is_ok());
+        for (count, size) in [
+            (1025, 1 << 20),
+            (1, MAX_FILL_STATE_BYTES + 1),
+            (2, usize::MAX),
+        ] {
+            assert!(matches!(
+                step(count, size).await,
+                Err(StepError::InvalidParameter { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_fill_state_replay() {
+        let step: FillStateStep =
+            serde_yaml::from_str("{count: 3, size: 16, pattern: \"k{}\"}").unwrap();
+        let fill = |seed| {
+            let mut state = Vec::new();
+            step.fill(seed, |key, bytes| state.push((key, bytes)))
+                .unwrap();
+            state
+        };
+
+        let state = fill(7);
+        assert_eq!(
+            state
+                .iter()
+                .map(|(key, _)| key.as_str())
+                .collect::<Vec<_>>(),
+            ["k0", "k1", "k2"]
+        );
+        assert!(state.iter().all(|(_, bytes)| bytes.len() == 16));
+        // a replay writes the very same state
+        assert_eq!(state, fill(7));
+        assert_ne!(state, fill(8));
+    }
+
+    #[test]
+    fn test_seeded_replay() {
+        let payload: PayloadStep = serde_yaml::from_str("{size: 32, output: p}").unwrap();
+        let fill: FillStateStep = serde_yaml::from_str("{count: 2, size: 8}").unwrap();
+        let mirror: MirrorStep =
+            serde_yaml::from_str("{target_type: SERVICE, service: s, handler: h, sample: 0.5}")
+                .unwrap();
+
+        // runs the seeded steps of a handler in order, drawing their seeds like `run` does
+        let execute = |seed| {
+            let exec = ExecutionContext::default();
+            let uuids: Vec<String> = (0..2)
+                .map(|_| UuidStep::generate(exec.draw_seed(seed)))
+                .collect();
+            let payload = payload.generate(exec.draw_seed(seed)).unwrap();
+            let mut state = Vec::new();
+            fill.fill(exec.draw_seed(seed), |key, bytes| state.push((key, bytes)))
+                .unwrap();
+            let mirrored: Vec<bool> = (0..16)
+                .map(|_| mirror.sampled(exec.draw_seed(seed)))
+                .collect();
+            (uuids, payload, state, mirrored)
+        };
+
+        let (uuids, payload, state, mirrored) = execute(7);
+        // every step of the execution draws its own values
+        assert_ne!(uuids[0], uuids[1]);
+        assert!(mirrored.contains(&true) && mirrored.contains(&false));
+        // a replayed execution draws the very same values, another invocation does not
+        assert_eq!(
+            (
+                uuids.clone(),
+                payload.clone(),
+                state.clone(),
+                mirrored.clone()
+            ),
+            execute(7)
+        );
+        assert_ne!(uuids, execute(8).0);
+        assert_ne!(payload, execute(8).1);
+        assert_ne!(state, execute(8).2);
+    }
+
+    #[test]
+    fn test_crash_armed() {
+        let step = CrashStep {
+            config: CrashConfig {
+                mode: CrashMode::Exit,
+                code: 1,
+                after: 2,
+                probability: 1.0,
+            },
+            invocations: AtomicU64::new(0),
+        };
+        assert!(!step.armed());
+        assert!(!step.armed());
+        assert!(step.armed());
+        assert!(step.armed());
+
+        let step = CrashStep {
+            config: CrashConfig {
+                mode: CrashMode::Abort,
+                code: 1,
+                after: 0,
+                probability: 0.0,
+            },
+            invocations: AtomicU64::new(0),
+        };
+        assert!(!step.armed());
+    }
+
+    #[test]
+    fn test_cpu_burn() {
+        let started = std::time::Instant::now();
+        CpuStep::burn(Some(Duration::from_millis(20)), None);
+        assert!(started.elapsed() >= Duration::from_millis(20));
+
+        CpuStep::burn(None, Some(1000));
+    }
+
+    #[test]
+    fn test_return_value() {
+        let step = |source: &str| -> ReturnStep { serde_yaml::from_str(source).unwrap() };
+
+        let mut exec = ExecutionContext::default();
+        exec.set("n", 3);
+        exec.set("name", "one".to_owned());
+        exec.set(
+            "object",
+            Variable::try_from(serde_json::json!({"a": 1, "b": [true]})).unwrap(),
+        );
+
+        // lists and objects are returned as plain JSON, the other values tagged
+        assert_eq!(
+            step("{output: n}").value(&exec).unwrap(),
+            serde_json::json!({"Integer": 3})
+        );
+        assert_eq!(
+            step("{output: object}").value(&exec).unwrap(),
+            serde_json::json!({"a": 1, "b": [true]})
+        );
+        assert_eq!(
+            step("{merge: [n, name]}").value(&exec).unwrap(),
+            serde_json::json!({"n": {"Integer": 3}, "name": {"String": "one"}})
+        );
+        assert_eq!(
+            step(r#"{value: "${n}"}"#).value(&exec).unwrap(),
+            serde_json::json!(3)
+        );
+        assert!(step("{merge: [n, missing]}").value(&exec).is_err());
+    }
+
+    #[test]
+    fn test_return_raw_bytes() {
+        let step: ReturnStep = serde_yaml::from_str("{output: input}").unwrap();
+
+        // a raw handler echoing its input writes back the very same bytes
+        let mut exec = ExecutionContext::default();
+        let input = bytes::Bytes::from_static(b"\xff\x00\xfe binary");
+        exec.set(super::super::RAW_INPUT_VARIABLE, input.clone());
+
+        let output = super::super::raw_output(JsonValue(step.value(&exec).unwrap()));
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_build_output() {
+        let step: BuildOutputStep = serde_yaml::from_str(
+            r#"{fields: [{path: $.a, variable: x}, {path: $.b, value: "${x}"}, {path: $.c, value: {n: "${n}"}}]}"#,
+        )
+        .unwrap();
+
+        let mut exec = ExecutionContext::default();
+        exec.set(
+            "x",
+            vec![Variable::from(1), Variable::from("one".to_owned())],
+        );
+        exec.set("n", 3);
+        step.build(&mut exec).unwrap();
+
+        // a variable and a lone placeholder set the same value
+        assert_eq!(
+            exec.take_return().unwrap().0,
+            serde_json::json!({"a": [1, "one"], "b": [1, "one"], "c": {"n": 3}})
+        );
+    }
+
+    #[test]
+    fn test_http_body() {
+        let step: HttpStep = serde_yaml::from_str(
+            r#"{url: "http://localhost/${id}", method: POST, body: {amount: "${amount}", note: "id ${id}"}}"#,
+        )
+        .unwrap();
+
+        let mut exec = ExecutionContext::default();
+        exec.set("amount", 3);
+        exec.set("id", "a1".to_owned());
+
+        let request = step.request(&exec).unwrap().build().unwrap();
+        assert_eq!(request.url().as_str(), "http://localhost/a1");
+
+        // the placeholders are replaced by plain JSON on the wire
+        let body = request.body().and_then(|body| body.as_bytes()).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(body).unwrap(),
+            serde_json::json!({"amount": 3, "note": "id a1"})
+        );
+    }
+
+    #[test]
+    fn test_send_batch_keys() {
+        let step = |params: &str| -> SendBatchStep {
+            serde_yaml::from_str(&format!(
+                "{{target_type: VIRTUAL_OBJECT, service: s, handler: h, {params}}}"
+            ))
+            .unwrap()
+        };
+
+        let mut exec = ExecutionContext::default();
+        exec.set("prefix", "user".to_owned());
+        exec.set("n", 2);
+        exec.set("many", MAX_BATCH_SIZE as isize + 1);
+        let keys = |params: &str| {
+            step(params)
+                .keys(&exec)
+                .and_then(|keys| keys.collect::<Result<Vec<_>, _>>())
+        };
+
+        assert_eq!(
+            keys("count: 3, key: \"${prefix}-${index}\"").unwrap(),
+            vec![
+                Some("user-0".to_owned()),
+                Some("user-1".to_owned()),
+                Some("user-2".to_owned()),
+            ]
+        );
+        assert_eq!(
+            keys("count_variable: n, index: i, key: \"k${i}\"").unwrap(),
+            vec![Some("k0".to_owned()), Some("k1".to_owned())]
+        );
+        // the sends go to the key of the caller
+        assert_eq!(keys("count: 2").unwrap(), vec![None, None]);
+        assert!(keys("count_variable: prefix").is_err());
+        assert!(keys("count: 1, key: \"${missing}\"").is_err());
+        // a batch too large fails before sending anything, a negative count sends nothing
+        exec.set("max", MAX_BATCH_SIZE as isize);
+        exec.set("negative", -3);
+        assert_eq!(
+            step("count_variable: max").count(&exec).unwrap(),
+            MAX_BATCH_SIZE
+        );
+        assert!(step("count_variable: many").keys(&exec).is_err());
+        assert_eq!(step("count_variable: negative").count(&exec).unwrap(), 0);
+
+        // the sends are paced by the interval, an index too large fails instead of wrapping
+        assert_eq!(step("count: 2").delay(3).unwrap(), None);
+        let paced = step("count: 2, interval: 10ms");
+        assert_eq!(paced.delay(3).unwrap(), Some(Duration::from_millis(30)));
+        assert!(paced.delay(u32::MAX as usize + 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_batch_count() {
+        let step = |count: usize| {
+            create(
+                "send-batch",
+                &format!("target_type: SERVICE, service: s, handler: h, count: {count}"),
+            )
+        };
+
+        assert!(step(MAX_BATCH_SIZE).await.is_ok());
+        assert!(matches!(
+            step(MAX_BATCH_SIZE + 1).await,
+            Err(StepError::InvalidParameter { field, .. }) if field == "count"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_exec_spawn() {
+        let step = |command: &str| -> ExecStep {
+            serde_yaml::from_str(&format!("{{command: {command}}}")).unwrap()
+        };
+
+        // large enough to fill the pipes if the input was not written concurrently
+        let input = vec![b'x'; 1 << 20];
+        let output = step("cat").spawn(&[], &input).await.unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, input);
+
+        // a command ignoring its input
+        let args = ["-c".to_owned(), "exit 3".to_owned()];
+        let output = step("sh").spawn(&args, &input).await.unwrap();
+        assert_eq!(output.status.code(), Some(3));
+    }
+
+    #[test]
+    fn test_headers() {
+        let step = |source: &str| -> HeadersStep { serde_yaml::from_str(source).unwrap() };
+
+        let mut headers = HeaderMap::default();
+        headers.insert("x-tenant", "acme".to_owned());
+        headers.append("accept", "text/plain".to_owned());
+        headers.append("accept", "application/json".to_owned());
+
+        let json = |variable: Variable| serde_json::Value::from(variable);
+        assert_eq!(
+            json(step("{output: h}").headers(&headers)),
+            serde_json::json!({"x-tenant": "acme", "accept": "text/plain, application/json"})
+        );
+        assert!(matches!(
+            step("{output: h}").headers(&headers),
+            Variable::Object(_)
+        ));
+        assert_eq!(
+            json(step("{name: X-Tenant, output: h}").headers(&headers)),
+            serde_json::json!("acme")
+        );
+        assert!(matches!(
+            step("{name: x-missing, output: h}").headers(&headers),
+            Variable::Null
+        ));
+        assert!(matches!(
+            step("{output: h}").headers(&HeaderMap::default()),
+            Variable::Object(fields) if fields.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_require_header() {
+        let step = |source: &str| RequireHeaderStep {
+            config: serde_yaml::from_str(source).unwrap(),
+            pattern: None,
+        };
+
+        let mut headers = HeaderMap::default();
+        headers.insert("x-api-key", "secret".to_owned());
+        headers.insert("authorization", "Bearer token".to_owned());
+
+        // the configured name is matched regardless of its case
+        assert!(step("{header: X-Api-Key, value: secret}").is_authorized(&headers));
+        assert!(!step("{header: X-API-KEY, value: other}").is_authorized(&headers));
+        assert!(step("{header: Authorization, bearer: true}").is_authorized(&headers));
+        assert!(step("{value: token, bearer: true}").is_authorized(&headers));
+        assert!(!step("{header: x-missing}").is_authorized(&headers));
+    }
+
+    #[test]
+    fn test_fail_times_retries() {
+        let step: FailTimesStep = serde_yaml::from_str("{times: 2, error: retryable}").unwrap();
+
+        assert_eq!(step.retry("inv-1"), 0);
+        assert_eq!(step.retry("inv-2"), 0);
+        assert_eq!(step.retry("inv-1"), 1);
+        // the following attempt succeeds, forgetting the invocation
+        assert_eq!(step.retry("inv-1"), 2);
+        assert!(!step.retries.lock().unwrap().attempts.contains_key("inv-1"));
+        assert_eq!(step.retry("inv-1"), 0);
+        assert_eq!(step.retries.lock().unwrap().attempts.len(), 2);
+
+        // the invocations that never succeed are evicted, the oldest first
+        for idx in 0..MAX_COUNTED_RETRIES {
+            step.retry(&format!("stuck-{idx}"));
+        }
+        let retries = step.retries.lock().unwrap();
+        assert_eq!(retries.attempts.len(), MAX_COUNTED_RETRIES);
+        assert!(!retries.attempts.contains_key("inv-2"));
+        assert!(!retries.attempts.contains_key("inv-1"));
+        assert!(retries.attempts.contains_key("stuck-0"));
+    }
+
+    #[tokio::test]
+    async fn test_timing_steps() {
+        assert!(create("sleep", "duration: 1s, jitter: 0.1").await.is_ok());
+        assert!(create("busy", "durations: [{duration: 5ms}]").await.is_ok());
+        assert!(matches!(
+            create("busy", "jitter: 0.1").await,
+            Err(StepError::InvalidParameter { .. })
+        ));
+        assert!(matches!(
+            create("barrier", "name: steps, parties: 0").await,
+            Err(StepError::InvalidParameter { .. })
+        ));
+
+        // the waits go through the clock of the steps, which does not actually wait here
+        let clock = Arc::new(clock::TestClock::default());
+        let started = clock.now();
+
+        let busy = BusyStep {
+            latency: latency("{duration: 5s}"),
+            clock: clock.clone(),
+        };
+        busy.wait().await;
+        assert_eq!(clock.now() - started, Duration::from_secs(5));
+
+        let barrier = |name: &str, parties| BarrierStep {
+            name: name.to_owned(),
+            barrier: barrier::barrier(name, parties).unwrap(),
+            timeout: Duration::from_secs(30),
+            clock: clock.clone(),
+        };
+        assert!(barrier("timing-single", 1).wait().await.is_ok());
+        // a lone party times out
+        assert!(barrier("timing-pair", 2).wait().await.is_err());
+        assert_eq!(clock.now() - started, Duration::from_secs(35));
+    }
+
+    #[tokio::test]
+    async fn test_race_call_target() {
+        let step = |call: &str| {
+            create(
+                "race",
+                &format!("branches: [{{name: a, call: {call}}}, {{name: b, sleep: 1s}}]"),
+            )
+        };
+
+        assert!(
+            step("{target_type: SERVICE, service: s, handler_from: $.handler}")
+                .await
+                .is_ok()
+        );
+        let Err(StepError::InvalidParameter { field, .. }) =
+            step("{target_type: SERVICE, handler: h}").await
+        else {
+            panic!("expected an invalid target");
+        };
+        assert_eq!(field, "branches[0].call.service");
+    }
+
+    #[tokio::test]
+    async fn test_memory_size() {
+        let step = |megabytes: usize| create("memory", &format!("megabytes: {megabytes}"));
+
+        assert!(step(1).await.is_ok());
+        for megabytes in [0, MAX_MEMORY_MEGABYTES + 1, usize::MAX] {
+            assert!(matches!(
+                step(megabytes).await,
+                Err(StepError::InvalidParameter { .. })
+            ));
+        }
+
+        let memory = MemoryStep {
+            size: 1 << 20,
+            duration: None,
+            clock: clock::system(),
+        };
+        let allocated = memory.allocate().unwrap();
+        assert_eq!(allocated.len(), 1 << 20);
+        assert!(allocated.iter().all(|byte| *byte == 0xa5));
+
+        // an allocation exceeding the address space fails instead of aborting
+        let memory = MemoryStep {
+            size: usize::MAX,
+            ..memory
+        };
+        assert!(memory.allocate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mirror() {
+        let mirror = |params: &str| {
+            create(
+                "mirror",
+                &format!("target_type: VIRTUAL_OBJECT, service: shadow, handler: h, {params}"),
+            )
+        };
+
+        // an object target needs a key, unless the mirrored handler has one
+        let step = mirror("sample: 0.5").await.unwrap();
+        assert!(step
+            .validate(ServiceType::VirtualObject, Some(HandlerType::Exclusive))
+            .is_ok());
+        assert!(matches!(
+            step.validate(ServiceType::Service, None),
+            Err(StepError::InvalidServiceType(ServiceType::Service))
+        ));
+        assert!(mirror("key: k")
+            .await
+            .unwrap()
+            .validate(ServiceType::Service, None)
+            .is_ok());
+        assert_eq!(step.targets()[0].service, "shadow");
+        assert!(step.fire_and_forget());
+
+        assert!(matches!(
+            mirror("sample: 1.5").await,
+            Err(StepError::InvalidParameter { field, .. }) if field == "sample"
+        ));
+
+        let step = |sample: f64| -> MirrorStep {
+            serde_yaml::from_str(&format!(
+                "{{target_type: SERVICE, service: shadow, handler: h, sample: {sample}}}"
+            ))
+            .unwrap()
+        };
+        assert!((0..100).all(|seed| step(1.0).sampled(seed)));
+        assert!((0..100).all(|seed| !step(0.0).sampled(seed)));
+        // a retried invocation makes the same decision
+        let half = step(0.5);
+        let sampled: Vec<bool> = (0..100).map(|seed| half.sampled(seed)).collect();
+        assert!(sampled.contains(&true) && sampled.contains(&false));
+        assert!((0..100).all(|seed| half.sampled(seed) == sampled[seed as usize]));
+        // the mirrors of an invocation make independent decisions
+        let both: Vec<(bool, bool)> = (0..100)
+            .map(|seed| {
+                let exec = ExecutionContext::default();
+                (
+                    half.sampled(exec.draw_seed(seed)),
+                    half.sampled(exec.draw_seed(seed)),
+                )
+            })
+            .collect();
+        assert!(both.iter().any(|(first, second)| first != second));
+    }
+
+    #[tokio::test]
+    async fn test_history() {
+        let record: RecordHistoryStep =
+            serde_yaml::from_str("{key: history, handler: add, max: 2}").unwrap();
+        assert!(record
+            .validate(ServiceType::VirtualObject, Some(HandlerType::Exclusive))
+            .is_ok());
+        assert!(record
+            .validate(ServiceType::VirtualObject, Some(HandlerType::Shared))
+            .is_err());
+
+        // the oldest entries are dropped beyond `max`
+        let mut history = None;
+        for n in 1..=3 {
+            let entry = record.entry(&JsonValue(serde_json::json!({"n": n})));
+            history = Some(JsonValue(
+                appended(&record.key, history, entry, record.max).unwrap(),
+            ));
+        }
+        assert_eq!(
+            history.clone().unwrap().0,
+            serde_json::json!([
+                {"handler": "add", "input": {"n": 2}},
+                {"handler": "add", "input": {"n": 3}},
+            ])
+        );
+        assert!(appended(
+            "history",
+            Some(JsonValue(serde_json::json!(1))),
+            2.into(),
+            None
+        )
+        .is_err());
+
+        // the companion handler returns the recorded history, or an empty one
+        assert_eq!(history_or_empty(history.clone()).0, history.unwrap().0);
+        assert_eq!(history_or_empty(None).0, serde_json::json!([]));
+
+        let get = create("get-history", "key: history").await.unwrap();
+        assert!(get
+            .validate(ServiceType::VirtualObject, Some(HandlerType::Shared))
+            .is_ok());
+        assert!(matches!(
+            get.validate(ServiceType::Service, None),
+            Err(StepError::InvalidServiceType(_))
+        ));
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let step = |source: &str| -> DetectDuplicatesStep { serde_yaml::from_str(source).unwrap() };
+
+        let mut headers = HeaderMap::default();
+        headers.insert("idempotency-key", "h-1".to_owned());
+        headers.insert("x-request-id", "r-1".to_owned());
+        let input = JsonValue(serde_json::json!({"id": 7, "name": "a"}));
+
+        assert_eq!(
+            step("{key: stats}").request_id(&headers, &input).as_deref(),
+            Some("h-1")
+        );
+        assert_eq!(
+            step("{key: stats, header: X-Request-Id}")
+                .request_id(&headers, &input)
+                .as_deref(),
+            Some("r-1")
+        );
+        assert_eq!(
+            step("{key: stats, field: $.id}")
+                .request_id(&headers, &input)
+                .as_deref(),
+            Some("7")
+        );
+        assert!(step("{key: stats, field: $.missing}")
+            .request_id(&headers, &input)
+            .is_none());
+
+        let mut stats = DuplicateStats::default();
+        assert!(!stats.record(Some("a")));
+        assert!(!stats.record(Some("b")));
+        assert!(stats.record(Some("a")));
+        assert!(stats.record(Some("a")));
+        assert!(!stats.record(None));
+        assert_eq!(
+            stats.report(),
+            serde_json::json!({
+                "requests": 5,
+                "unique": 2,
+                "missing_id": 1,
+                "duplicates": 2,
+                "duplicated_ids": {"a": 3},
+            })
+        );
+
+        // duplicates only fail the handler if configured so
+        assert!(step("{key: stats}")
+            .check(true, Some("a".to_owned()))
+            .is_ok());
+        let failing = step("{key: stats, fail: true}");
+        assert!(failing.check(false, Some("a".to_owned())).is_ok());
+        assert_eq!(
+            failing
+                .check(true, Some("a".to_owned()))
+                .unwrap_err()
+                .code(),
+            409
+        );
+    }
+
+    #[tokio::test]
+    async fn test_switch() {
+        let switch = |cases: &str| {
+            create(
+                "switch",
+                &format!(
+                    "variable: status, cases: [{cases}], default: [{{type: echo}}], export: [x]"
+                ),
+            )
+        };
+
+        let step = switch(
+            "{value: open, steps: [{type: echo}]}, {value: 2, steps: [{type: echo}, {type: echo}]}",
+        )
+        .await
+        .unwrap();
+        assert_eq!(step.outputs(), vec!["x"]);
+        assert_eq!(step.inputs(), vec!["x", "status"]);
+        let paths: Vec<String> = step
+            .branches()
+            .into_iter()
+            .map(|branch| branch.path)
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "params.cases[0].steps",
+                "params.cases[1].steps",
+                "params.default"
+            ]
+        );
+
+        assert!(matches!(
+            switch("{value: 1, steps: []}, {value: 1.0, steps: []}").await,
+            Err(StepError::InvalidParameter { field, .. }) if field == "cases"
+        ));
+        let Err(StepError::Nested { path, .. }) =
+            switch("{value: 1, steps: [{type: bogus}]}").await
+        else {
+            panic!("expected a nested error");
+        };
+        assert_eq!(path, "cases[0].steps[0]");
+
+        let echo = || async { vec![create("echo", "").await.unwrap()] };
+        let switch = SwitchStep {
+            operand: Operand::Variable("status".to_owned()),
+            cases: vec![
+                (Variable::from("open".to_owned()), echo().await),
+                (Variable::Integer(2), echo().await),
+            ],
+            default: echo().await,
+            export: Vec::new(),
+        };
+        let selected = |operand: Variable| switch.select(&operand).as_ptr();
+        assert_eq!(
+            selected(Variable::from("open".to_owned())),
+            switch.cases[0].1.as_ptr()
+        );
+        // the cases are compared like the conditions, regardless of the number kind
+        assert_eq!(selected(Variable::Number(2.0)), switch.cases[1].1.as_ptr());
+        assert_eq!(
+            selected(Variable::from("2".to_owned())),
+            switch.default.as_ptr()
+        );
+        assert_eq!(selected(Variable::Null), switch.default.as_ptr());
+    }
+
+    #[tokio::test]
+    async fn test_parallel() {
+        let parallel = |steps: &str| create("parallel", &format!("steps: [{steps}]"));
+        let params = |handler: &str| {
+            format!("target_type: SERVICE, service: s, handler: {handler}, output: {handler}")
+        };
+        let call = |handler: &str| format!("{{type: call, params: {{{}}}}}", params(handler));
+
+        let Err(StepError::InvalidParameter { field, .. }) = parallel(&format!(
+            "{}, {{type: sleep, params: {{duration: 1s}}}}",
+            call("a")
+        ))
+        .await
+        else {
+            panic!("expected only calls to be accepted");
+        };
+        assert_eq!(field, "steps[1].type");
+
+        let step = parallel(&format!("{}, {}", call("a"), call("b")))
+            .await
+            .unwrap();
+        assert_eq!(step.outputs(), vec!["a", "b"]);
+        let targets: Vec<&str> = step.branches()[0]
+            .steps
+            .iter()
+            .flat_map(|step| step.targets())
+            .map(|target| target.handler)
+            .collect();
+        assert_eq!(targets, ["a", "b"]);
+
+        let parallel = ParallelStep {
+            steps: vec![
+                create("call", &params("a")).await.unwrap(),
+                create("call", &params("b")).await.unwrap(),
+            ],
+        };
+
+        // only the outputs of the calls are kept, each from its own scope
+        let mut exec = ExecutionContext::default();
+        let mut scopes: Vec<_> = parallel.steps.iter().map(|_| exec.scope()).collect();
+        scopes[0].set("a", 1);
+        scopes[0].set("temp", 1);
+        scopes[1].set("b", 2);
+        parallel.close(&mut exec, scopes);
+
+        assert!(matches!(exec.get_variable("a"), Some(Variable::Integer(1))));
+        assert!(matches!(exec.get_variable("b"), Some(Variable::Integer(2))));
+        assert!(exec.get_variable("temp").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_race() {
+        let create = |source: String| Race.create(serde_yaml::from_str(&source).unwrap());
+        let call = "{target_type: SERVICE, service: s, handler: h, input: req}";
+
+        let step = create(format!(
+            "{{branches: [{{name: fetch, call: {call}}}, {{name: timeout, sleep: 1s}}], winner: w, output: o}}"
+        ))
+        .await
+        .unwrap();
+        assert_eq!(step.outputs(), vec!["w", "o"]);
+        assert!(step.inputs().contains(&"req"));
+        assert_eq!(step.targets().len(), 1);
+
+        for (source, field) in [
+            ("{branches: []}".to_owned(), "branches"),
+            (
+                "{branches: [{name: a, sleep: 1s}, {name: a, sleep: 2s}]}".to_owned(),
+                "branches[1].name",
+            ),
+            ("{branches: [{name: a}]}".to_owned(), "branches[0]"),
+            (
+                format!("{{branches: [{{name: a, sleep: 1s, call: {call}}}]}}"),
+                "branches[0]",
+            ),
+        ] {
+            let Err(StepError::InvalidParameter { field: actual, .. }) =
+                create(source.clone()).await
+            else {
+                panic!("expected {source} to be rejected");
+            };
+            assert_eq!(actual, field);
+        }
+
+        // the call input defaults to `null` if the variable is not set
+        let race_call: RaceCall = serde_yaml::from_str(call).unwrap();
+        let mut exec = ExecutionContext::default();
+        assert!(matches!(race_call.request(&exec), Variable::Null));
+        exec.set("req", 5);
+        assert!(matches!(race_call.request(&exec), Variable::Integer(5)));
+
+        // the winner and its result are stored, `null` for a sleep
+        let race = RaceStep {
+            branches: vec![
+                RaceBranch {
+                    name: "fetch".to_owned(),
+                    operation: RaceOperation::Call(race_call),
+                },
+                RaceBranch {
+                    name: "timeout".to_owned(),
+                    operation: RaceOperation::Sleep(Duration::from_secs(1)),
+                },
+            ],
+            winner: Some("w".to_owned()),
+            output: Some("o".to_owned()),
+        };
+        race.finish(&mut exec, 1, Variable::Null);
+        assert_eq!(
+            exec.get_variable("w").cloned().map(serde_json::Value::from),
+            Some(serde_json::json!("timeout"))
+        );
+        assert!(matches!(exec.get_variable("o"), Some(Variable::Null)));
+
+        race.finish(&mut exec, 0, Variable::from("done".to_owned()));
+        assert_eq!(
+            exec.get_variable("w").cloned().map(serde_json::Value::from),
+            Some(serde_json::json!("fetch"))
+        );
+        assert_eq!(
+            exec.get_variable("o").cloned().map(serde_json::Value::from),
+            Some(serde_json::json!("done"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_nested_step_errors() {
+        let config = "{type: loop, params: {count: 1, steps: [
+            {type: echo},
+            {type: condition, params: {variable: x, then: [{type: bogus}]}},
+        ]}}";
+
+        let Err(StepError::Nested { path, source }) =
+            create_step(serde_yaml::from_str(config).unwrap()).await
+        else {
+            panic!("expected a nested error");
+        };
+        assert_eq!(path, "steps[1]");
+
+        let StepError::Nested { path, source } = *source else {
+            panic!("expected a nested error");
+        };
+        assert_eq!(path, "then[0]");
+        assert!(matches!(*source, StepError::UnknownStepType(_)));
     }
 }
@@ -0,0 +1,171 @@
+use serde_json::Value;
+
+use super::{context::Variable, ExecutionContext};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("unknown variable {0}")]
+    UnknownVariable(String),
+    #[error("missing `}}` after `${{`")]
+    Unterminated,
+}
+
+/// Renders a value template against the variables of the execution context.
+///
+/// Every string of the template is rendered: a string made of a single `${name}` placeholder
+/// is replaced by the plain JSON value of the variable, while placeholders embedded in a longer string
+/// are replaced by the text of the variable. Use `$${` for a literal `${`.
+pub fn render(template: &Value, exec: &ExecutionContext) -> Result<Value, TemplateError> {
+    let value = match template {
+        Value::String(text) => match placeholder(text) {
+            Some(name) => Value::from(variable(exec, name)?.clone()),
+            None => Value::String(interpolate(text, exec)?),
+        },
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| render(item, exec))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, field)| Ok((name.clone(), render(field, exec)?)))
+                .collect::<Result<_, TemplateError>>()?,
+        ),
+        value => value.clone(),
+    };
+
+    Ok(value)
+}
+
+/// Replaces the `${name}` placeholders of `text` by the text of the variables.
+pub fn interpolate(text: &str, exec: &ExecutionContext) -> Result<String, TemplateError> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            output.push_str(&rest[..start - 1]);
+            output.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        output.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or(TemplateError::Unterminated)?;
+
+        let name = &rest[start + 2..start + end];
+        output.push_str(&text_of(variable(exec, name)?));
+        rest = &rest[start + end + 1..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Returns the names of the variables referenced by the placeholders of the template.
+pub fn variables(template: &Value) -> Vec<&str> {
+    match template {
+        Value::String(text) => placeholders(text),
+        Value::Array(items) => items.iter().flat_map(variables).collect(),
+        Value::Object(fields) => fields.values().flat_map(variables).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the names of the variables referenced by the `${name}` placeholders of `text`.
+pub fn placeholders(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        names.push(&rest[start + 2..start + end]);
+        rest = &rest[start + end + 1..];
+    }
+
+    names
+}
+
+/// Returns the variable name if `text` is made of a single placeholder.
+fn placeholder(text: &str) -> Option<&str> {
+    text.strip_prefix("${")
+        .and_then(|text| text.strip_suffix('}'))
+        .filter(|name| !name.contains('}'))
+}
+
+fn variable<'a>(exec: &'a ExecutionContext, name: &str) -> Result<&'a Variable, TemplateError> {
+    exec.get_variable(name)
+        .ok_or_else(|| TemplateError::UnknownVariable(name.to_owned()))
+}
+
+/// Returns the text of a variable, as inserted by the placeholders.
+pub fn text_of(variable: &Variable) -> String {
+    match variable {
+        Variable::String(s) => s.clone(),
+        Variable::Integer(i) => i.to_string(),
+        Variable::Number(n) => n.to_string(),
+        Variable::Boolean(b) => b.to_string(),
+        Variable::Bytes(b) => String::from_utf8_lossy(b).into_owned(),
+        Variable::Array(_) | Variable::Object(_) => {
+            serde_json::Value::from(variable.clone()).to_string()
+        }
+        Variable::Null => "null".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut exec = ExecutionContext::default();
+        exec.set("name", "mock".to_owned());
+        exec.set("count", 3);
+        exec.set("items", vec![Variable::from(1), Variable::from(true)]);
+
+        assert_eq!(
+            render(
+                &json!({
+                    "greeting": "hello ${name}!",
+                    "count": "${count}",
+                    "items": "${items}",
+                    "list": [1, "$${name}"],
+                }),
+                &exec
+            )
+            .unwrap(),
+            json!({
+                "greeting": "hello mock!",
+                "count": 3,
+                "items": [1, true],
+                "list": [1, "${name}"],
+            })
+        );
+
+        assert!(matches!(
+            render(&json!("${missing}"), &exec),
+            Err(TemplateError::UnknownVariable(_))
+        ));
+        assert!(matches!(
+            interpolate("${name", &exec),
+            Err(TemplateError::Unterminated)
+        ));
+
+        assert_eq!(
+            variables(&json!({"a": "${name} and ${count}", "b": ["$${escaped}", "${name"]})),
+            vec!["name", "count"]
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use std::net::SocketAddr;
+
+use hyper::server::conn::http2;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use restate_sdk::{endpoint::Endpoint, hyper::HyperEndpoint};
+use tokio::sync::watch;
+
+use crate::stats::STATS;
+
+/// Serves the endpoint received on `endpoint` at the given address.
+///
+/// Unlike the SDK `HttpServer`, the endpoint can be replaced while serving: new connections
+/// use the latest endpoint, while the open connections keep the endpoint they started with
+/// until they are closed. This is how redeployments are simulated.
+pub async fn serve(address: SocketAddr, endpoint: watch::Receiver<Endpoint>) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    tracing::info!("Starting server on {address}");
+
+    loop {
+        let (stream, remote) = listener.accept().await?;
+        let service = HyperEndpoint::new(endpoint.borrow().clone());
+
+        STATS.connection_opened();
+        tokio::spawn(async move {
+            if let Err(err) = http2::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::warn!("Error serving connection {remote}: {err:?}");
+            }
+            STATS.connection_closed();
+        });
+    }
+}
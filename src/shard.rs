@@ -0,0 +1,84 @@
+use std::{fmt::Display, str::FromStr};
+
+/// A shard of the configured services, selected with `--shard <index>/<count>`.
+///
+/// Services are assigned to shards by hashing their name, so every process sharing the
+/// same configuration agrees on the partition and adding a service does not move the others.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shard {
+    index: u64,
+    count: u64,
+}
+
+impl Shard {
+    /// Returns `true` if the service belongs to this shard.
+    pub fn contains(&self, service: &str) -> bool {
+        fnv1a(service.as_bytes()) % self.count == self.index
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .split_once('/')
+            .ok_or_else(|| "expected <index>/<count>, e.g. 0/4".to_owned())?;
+
+        let index: u64 = index
+            .trim()
+            .parse()
+            .map_err(|err| format!("invalid shard index: {err}"))?;
+        let count: u64 = count
+            .trim()
+            .parse()
+            .map_err(|err| format!("invalid shard count: {err}"))?;
+
+        if index >= count {
+            return Err(format!(
+                "shard index must be lower than the shard count ({count})"
+            ));
+        }
+
+        Ok(Self { index, count })
+    }
+}
+
+impl Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// The 64-bit FNV-1a hash, which unlike the std hashers is stable across Rust releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shard() {
+        assert!("1".parse::<Shard>().is_err());
+        assert!("4/4".parse::<Shard>().is_err());
+        assert!("0/0".parse::<Shard>().is_err());
+
+        let shards: Vec<Shard> = (0..4)
+            .map(|idx| format!("{idx}/4").parse().unwrap())
+            .collect();
+        assert_eq!(shards[1].to_string(), "1/4");
+
+        for idx in 0..100 {
+            let service = format!("Service{idx}");
+            let owners = shards
+                .iter()
+                .filter(|shard| shard.contains(&service))
+                .count();
+            assert_eq!(owners, 1);
+        }
+    }
+}
@@ -0,0 +1,639 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, LazyLock, Mutex,
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::{body::Incoming, server::conn::http1, service::service_fn, Request, Response};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+
+/// The runtime statistics of all the mock handlers.
+pub static STATS: LazyLock<Stats> = LazyLock::new(Stats::default);
+
+/// The upper bounds of the payload size histogram buckets, in bytes.
+const PAYLOAD_BUCKETS: &[u64] = &[
+    64,
+    256,
+    1024,
+    4 * 1024,
+    16 * 1024,
+    64 * 1024,
+    256 * 1024,
+    1024 * 1024,
+];
+
+/// The upper bounds of the step duration histogram buckets, in milliseconds.
+const DURATION_BUCKETS: &[u64] = &[1, 5, 10, 50, 100, 500, 1000, 5000, 10000];
+
+/// A histogram counting the observed values per bucket.
+///
+/// The counters are atomic, so a histogram shared with a step is updated without a lock.
+#[derive(Debug)]
+struct Histogram {
+    /// The upper bounds (inclusive) of the buckets, the last bucket being unbounded.
+    bounds: &'static [u64],
+    counts: Vec<AtomicU64>,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [u64]) -> Self {
+        Self {
+            bounds,
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, value: u64) {
+        let bucket = self.bounds.partition_point(|bound| *bound < value);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+}
+
+impl Serialize for Histogram {
+    /// Serializes as `{"count", "sum", "buckets": [{"le", "count"}]}`, the bucket counts
+    /// are not cumulative and the `le` of the last bucket is `null`.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let counts: Vec<_> = self
+            .counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect();
+        let buckets: Vec<_> = counts
+            .iter()
+            .enumerate()
+            .map(|(idx, count)| serde_json::json!({"le": self.bounds.get(idx), "count": count}))
+            .collect();
+
+        serde_json::json!({
+            "count": counts.iter().sum::<u64>(),
+            "sum": self.sum.load(Ordering::Relaxed),
+            "buckets": buckets,
+        })
+        .serialize(serializer)
+    }
+}
+
+/// The outcome of a handler execution.
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome {
+    /// The execution succeeded with an output of the given size, in bytes.
+    Success(usize),
+    /// The execution failed with a terminal error.
+    TerminalError,
+    /// The execution failed with a retryable error, so it will be retried.
+    RetryableError,
+}
+
+impl Outcome {
+    /// Returns the name of the outcome category.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success(_) => "success",
+            Self::TerminalError => "terminal_error",
+            Self::RetryableError => "retryable_error",
+        }
+    }
+}
+
+/// The summary of a single handler execution, kept in the recent invocations.
+#[derive(Debug, Serialize)]
+pub struct Invocation {
+    /// When the execution started, as an RFC 3339 timestamp.
+    pub started_at: String,
+    pub invocation_id: String,
+    pub service: String,
+    pub handler: String,
+    /// The key of the invocation, for virtual objects and workflows.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// The input of the handler, with the redacted fields replaced.
+    pub input: serde_json::Value,
+    pub duration_ms: u64,
+    /// The outcome category of the execution, see [`Outcome::as_str`].
+    pub outcome: &'static str,
+}
+
+/// The number of recent invocations kept by default.
+const RECENT_INVOCATIONS: usize = 100;
+
+/// The most recent invocations, oldest first.
+#[derive(Debug)]
+struct RecentInvocations {
+    capacity: usize,
+    invocations: VecDeque<Invocation>,
+}
+
+impl Default for RecentInvocations {
+    fn default() -> Self {
+        Self {
+            capacity: RECENT_INVOCATIONS,
+            invocations: VecDeque::new(),
+        }
+    }
+}
+
+/// The failures of all the handlers, by category.
+#[derive(Debug, Default, Serialize)]
+struct ErrorStats {
+    /// The executions that failed with a terminal error.
+    terminal: u64,
+    /// The executions that failed with a retryable error.
+    retryable: u64,
+    /// The invocations of handlers that do not exist, per `service/handler`.
+    unknown_handler: BTreeMap<String, u64>,
+}
+
+/// The statistics of a single handler.
+#[derive(Debug, Serialize)]
+struct HandlerStats {
+    /// The number of handler executions (including retries).
+    invocations: u64,
+    /// The number of executions that failed.
+    failures: u64,
+    /// The number of executions that failed with a terminal error.
+    terminal_failures: u64,
+    /// The number of executions that failed with a retryable error.
+    retryable_failures: u64,
+    /// The total duration of all the executions, in milliseconds.
+    total_duration_ms: u64,
+    /// The duration of the slowest execution, in milliseconds.
+    max_duration_ms: u64,
+    /// The latency SLO of the handler, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slo_ms: Option<u64>,
+    /// The number of executions that took longer than the SLO.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slo_violations: Option<u64>,
+    /// The sizes of the JSON encoded inputs, in bytes.
+    request_bytes: Histogram,
+    /// The sizes of the JSON encoded outputs of the successful executions, in bytes.
+    response_bytes: Histogram,
+}
+
+impl Default for HandlerStats {
+    fn default() -> Self {
+        Self {
+            invocations: 0,
+            failures: 0,
+            terminal_failures: 0,
+            retryable_failures: 0,
+            total_duration_ms: 0,
+            max_duration_ms: 0,
+            slo_ms: None,
+            slo_violations: None,
+            request_bytes: Histogram::new(PAYLOAD_BUCKETS),
+            response_bytes: Histogram::new(PAYLOAD_BUCKETS),
+        }
+    }
+}
+
+/// The summary of the SLOs of all the handlers.
+#[derive(Debug, Serialize)]
+struct SloSummary {
+    /// The number of handlers declaring an SLO.
+    handlers: usize,
+    /// The handlers that breached their SLO at least once.
+    breached: Vec<String>,
+}
+
+/// The resource usage of the mock service process.
+///
+/// Memory and CPU are read from `/proc` and are only reported on Linux.
+#[derive(Debug, Serialize)]
+struct ProcessStats {
+    /// The resident set size, in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rss_bytes: Option<u64>,
+    /// The CPU time (user and system) consumed so far, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu_time_ms: Option<u64>,
+    /// The number of open connections to the endpoints.
+    open_connections: u64,
+    /// The number of alive tokio tasks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tokio_tasks: Option<usize>,
+    /// The number of tokio worker threads.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tokio_workers: Option<usize>,
+}
+
+/// The clock ticks per second used by the CPU times of `/proc/self/stat`, which is 100
+/// on all the common Linux platforms.
+const CLOCK_TICKS: u64 = 100;
+
+impl ProcessStats {
+    fn collect(open_connections: u64) -> Self {
+        let rss_bytes = std::fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|status| {
+                let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+                let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+                Some(kb * 1024)
+            });
+
+        let cpu_time_ms = std::fs::read_to_string("/proc/self/stat")
+            .ok()
+            .and_then(|stat| {
+                // the fields following the command name, which may contain spaces
+                let mut fields = stat.rsplit_once(')')?.1.split_whitespace().skip(11);
+                let utime: u64 = fields.next()?.parse().ok()?;
+                let stime: u64 = fields.next()?.parse().ok()?;
+                Some((utime + stime) * 1000 / CLOCK_TICKS)
+            });
+
+        let metrics = tokio::runtime::Handle::try_current()
+            .ok()
+            .map(|handle| handle.metrics());
+
+        Self {
+            rss_bytes,
+            cpu_time_ms,
+            open_connections,
+            tokio_tasks: metrics.as_ref().map(|metrics| metrics.num_alive_tasks()),
+            tokio_workers: metrics.as_ref().map(|metrics| metrics.num_workers()),
+        }
+    }
+}
+
+/// The execution durations of the steps, in milliseconds.
+#[derive(Debug, Default)]
+struct StepStats {
+    /// The durations per step type.
+    types: BTreeMap<String, Arc<Histogram>>,
+    /// The durations per step name, for the steps defining a `name`.
+    names: BTreeMap<String, Arc<Histogram>>,
+}
+
+impl StepStats {
+    fn histogram(histograms: &mut BTreeMap<String, Arc<Histogram>>, key: &str) -> Arc<Histogram> {
+        histograms
+            .entry(key.to_owned())
+            .or_insert_with(|| Arc::new(Histogram::new(DURATION_BUCKETS)))
+            .clone()
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let histograms = |histograms: &BTreeMap<String, Arc<Histogram>>| {
+            histograms
+                .iter()
+                .map(|(key, histogram)| (key.clone(), serde_json::json!(**histogram)))
+                .collect::<serde_json::Map<_, _>>()
+        };
+
+        serde_json::json!({
+            "types": histograms(&self.types),
+            "names": histograms(&self.names),
+        })
+    }
+}
+
+/// The duration histograms of a single step, registered when the step is created so its
+/// executions are recorded without locking the stats.
+#[derive(Debug)]
+pub struct StepTimer {
+    ty: Arc<Histogram>,
+    name: Option<Arc<Histogram>>,
+}
+
+impl StepTimer {
+    /// Records a single execution of the step.
+    pub fn record(&self, duration: Duration) {
+        let duration_ms = duration.as_millis() as u64;
+
+        self.ty.observe(duration_ms);
+        if let Some(name) = &self.name {
+            name.observe(duration_ms);
+        }
+    }
+}
+
+/// Collects the runtime statistics of the mock handlers, exposed as JSON on `/stats`.
+#[derive(Debug, Default)]
+pub struct Stats {
+    handlers: Mutex<BTreeMap<String, HandlerStats>>,
+    steps: Mutex<StepStats>,
+    errors: Mutex<ErrorStats>,
+    recent: Mutex<RecentInvocations>,
+    open_connections: AtomicU64,
+}
+
+impl Stats {
+    /// Registers a handler, so it is reported even if it was never invoked.
+    pub fn register(&self, service: &str, handler: &str, slo: Option<Duration>) {
+        let mut handlers = self.handlers.lock().unwrap();
+        let stats = handlers.entry(format!("{service}/{handler}")).or_default();
+
+        stats.slo_ms = slo.map(|slo| slo.as_millis() as u64);
+        stats.slo_violations = slo.map(|_| 0);
+    }
+
+    /// Records a single execution of a handler, along with the size of its input and its outcome.
+    pub fn record(
+        &self,
+        service: &str,
+        handler: &str,
+        duration: Duration,
+        request_bytes: usize,
+        outcome: Outcome,
+    ) {
+        let mut handlers = self.handlers.lock().unwrap();
+        let stats = handlers.entry(format!("{service}/{handler}")).or_default();
+        let duration_ms = duration.as_millis() as u64;
+
+        stats.invocations += 1;
+        stats.request_bytes.observe(request_bytes as u64);
+        match outcome {
+            Outcome::Success(response_bytes) => stats.response_bytes.observe(response_bytes as u64),
+            Outcome::TerminalError => {
+                stats.failures += 1;
+                stats.terminal_failures += 1;
+                self.errors.lock().unwrap().terminal += 1;
+            }
+            Outcome::RetryableError => {
+                stats.failures += 1;
+                stats.retryable_failures += 1;
+                self.errors.lock().unwrap().retryable += 1;
+            }
+        }
+        stats.total_duration_ms += duration_ms;
+        stats.max_duration_ms = stats.max_duration_ms.max(duration_ms);
+
+        if let (Some(slo_ms), Some(violations)) = (stats.slo_ms, stats.slo_violations.as_mut()) {
+            if duration_ms > slo_ms {
+                *violations += 1;
+            }
+        }
+    }
+
+    /// Sets the number of recent invocations kept, `0` disables them.
+    pub fn keep_recent(&self, capacity: usize) {
+        let mut recent = self.recent.lock().unwrap();
+        recent.capacity = capacity;
+
+        let excess = recent.invocations.len().saturating_sub(capacity);
+        recent.invocations.drain(..excess);
+    }
+
+    /// Returns `true` if the recent invocations are kept, so they are worth building.
+    pub fn keeps_recent(&self) -> bool {
+        self.recent.lock().unwrap().capacity > 0
+    }
+
+    /// Records a handler execution in the recent invocations, evicting the oldest one if full.
+    pub fn record_invocation(&self, invocation: Invocation) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.capacity == 0 {
+            return;
+        }
+
+        if recent.invocations.len() == recent.capacity {
+            recent.invocations.pop_front();
+        }
+        recent.invocations.push_back(invocation);
+    }
+
+    /// Returns the recent invocations as a JSON document, most recent first.
+    pub fn recent_to_json(&self) -> serde_json::Value {
+        let recent = self.recent.lock().unwrap();
+        serde_json::json!(recent.invocations.iter().rev().collect::<Vec<_>>())
+    }
+
+    /// Records an invocation of a handler that does not exist.
+    pub fn record_unknown_handler(&self, service: &str, handler: &str) {
+        *self
+            .errors
+            .lock()
+            .unwrap()
+            .unknown_handler
+            .entry(format!("{service}/{handler}"))
+            .or_default() += 1;
+    }
+
+    /// Registers a step of type `ty`, named `name` if set, returning the timer recording its
+    /// executions. The steps of the same type (or name) share their histograms.
+    pub fn step_timer(&self, ty: &str, name: Option<&str>) -> StepTimer {
+        let mut steps = self.steps.lock().unwrap();
+
+        StepTimer {
+            ty: StepStats::histogram(&mut steps.types, ty),
+            name: name.map(|name| StepStats::histogram(&mut steps.names, name)),
+        }
+    }
+
+    /// Records a new connection to an endpoint.
+    pub fn connection_opened(&self) {
+        self.open_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a closed connection to an endpoint.
+    pub fn connection_closed(&self) {
+        self.open_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Returns the statistics as a JSON document.
+    pub fn to_json(&self) -> serde_json::Value {
+        let handlers = self.handlers.lock().unwrap();
+
+        let slo = SloSummary {
+            handlers: handlers
+                .values()
+                .filter(|stats| stats.slo_ms.is_some())
+                .count(),
+            breached: handlers
+                .iter()
+                .filter(|(_, stats)| stats.slo_violations.unwrap_or_default() > 0)
+                .map(|(name, _)| name.clone())
+                .collect(),
+        };
+
+        serde_json::json!({
+            "handlers": *handlers,
+            "slo": slo,
+            "steps": self.steps.lock().unwrap().to_json(),
+            "errors": *self.errors.lock().unwrap(),
+            "process": ProcessStats::collect(self.open_connections.load(Ordering::Relaxed)),
+        })
+    }
+}
+
+async fn handle(request: Request<Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+    let response = match request.uri().path() {
+        "/stats" => Response::builder()
+            .header("content-type", "application/json")
+            .body(Full::new(Bytes::from(STATS.to_json().to_string()))),
+        "/debug/invocations" => Response::builder()
+            .header("content-type", "application/json")
+            .body(Full::new(Bytes::from(STATS.recent_to_json().to_string()))),
+        _ => Response::builder()
+            .status(404)
+            .body(Full::new(Bytes::from_static(b"not found"))),
+    };
+
+    Ok(response.expect("valid response"))
+}
+
+/// Serves the statistics on `GET /stats` and the recent invocations on
+/// `GET /debug/invocations` at the given address.
+pub async fn serve(address: SocketAddr) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    tracing::info!("Serving stats on http://{address}/stats");
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+
+        tokio::spawn(async move {
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service_fn(handle))
+                .await
+            {
+                tracing::debug!("Stats connection failed: {err}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slo_violations() {
+        let stats = Stats::default();
+        stats.register("echo", "echo", Some(Duration::from_millis(50)));
+        stats.register("echo", "other", None);
+
+        stats.record(
+            "echo",
+            "echo",
+            Duration::from_millis(10),
+            10,
+            Outcome::Success(100),
+        );
+        stats.record(
+            "echo",
+            "echo",
+            Duration::from_millis(80),
+            2000,
+            Outcome::TerminalError,
+        );
+        stats.record(
+            "echo",
+            "other",
+            Duration::from_millis(80),
+            0,
+            Outcome::Success(0),
+        );
+
+        let json = stats.to_json();
+        let echo = &json["handlers"]["echo/echo"];
+        assert_eq!(echo["invocations"], 2);
+        assert_eq!(echo["failures"], 1);
+        assert_eq!(echo["max_duration_ms"], 80);
+        assert_eq!(echo["slo_violations"], 1);
+        assert!(json["handlers"]["echo/other"]
+            .get("slo_violations")
+            .is_none());
+        assert_eq!(json["slo"]["handlers"], 1);
+        assert_eq!(json["slo"]["breached"], serde_json::json!(["echo/echo"]));
+        assert_eq!(json["process"]["open_connections"], 0);
+
+        let request_bytes = &echo["request_bytes"];
+        assert_eq!(request_bytes["count"], 2);
+        assert_eq!(request_bytes["sum"], 2010);
+        assert_eq!(
+            request_bytes["buckets"][0],
+            serde_json::json!({"le": 64, "count": 1})
+        );
+        assert_eq!(
+            request_bytes["buckets"][3],
+            serde_json::json!({"le": 4096, "count": 1})
+        );
+        assert_eq!(echo["response_bytes"]["count"], 1);
+        assert_eq!(
+            echo["response_bytes"]["buckets"][8],
+            serde_json::json!({"le": null, "count": 0})
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let stats = Stats::default();
+        stats.record("echo", "echo", Duration::ZERO, 0, Outcome::TerminalError);
+        stats.record("echo", "echo", Duration::ZERO, 0, Outcome::RetryableError);
+        stats.record("echo", "echo", Duration::ZERO, 0, Outcome::RetryableError);
+        stats.record_unknown_handler("echo", "missing");
+
+        let json = stats.to_json();
+        let echo = &json["handlers"]["echo/echo"];
+        assert_eq!(echo["failures"], 3);
+        assert_eq!(echo["terminal_failures"], 1);
+        assert_eq!(echo["retryable_failures"], 2);
+        assert_eq!(
+            json["errors"],
+            serde_json::json!({
+                "terminal": 1,
+                "retryable": 2,
+                "unknown_handler": {"echo/missing": 1},
+            })
+        );
+    }
+
+    #[test]
+    fn test_recent_invocations() {
+        let invocation = |idx: u64| Invocation {
+            started_at: String::new(),
+            invocation_id: format!("inv-{idx}"),
+            service: "echo".to_owned(),
+            handler: "echo".to_owned(),
+            key: None,
+            input: serde_json::Value::Null,
+            duration_ms: idx,
+            outcome: Outcome::Success(0).as_str(),
+        };
+
+        let stats = Stats::default();
+        stats.keep_recent(2);
+        for idx in 0..3 {
+            stats.record_invocation(invocation(idx));
+        }
+
+        let recent = stats.recent_to_json();
+        assert_eq!(recent.as_array().unwrap().len(), 2);
+        assert_eq!(recent[0]["invocation_id"], "inv-2");
+        assert_eq!(recent[1]["invocation_id"], "inv-1");
+        assert!(recent[0].get("key").is_none());
+
+        stats.keep_recent(0);
+        assert!(!stats.keeps_recent());
+        stats.record_invocation(invocation(3));
+        assert_eq!(stats.recent_to_json(), serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_step_durations() {
+        let stats = Stats::default();
+        stats
+            .step_timer("sleep", Some("nap"))
+            .record(Duration::from_millis(7));
+        stats
+            .step_timer("sleep", None)
+            .record(Duration::from_millis(700));
+
+        let json = stats.to_json();
+        assert_eq!(json["steps"]["types"]["sleep"]["count"], 2);
+        assert_eq!(json["steps"]["types"]["sleep"]["buckets"][2]["count"], 1);
+        assert_eq!(json["steps"]["names"]["nap"]["sum"], 7);
+        assert_eq!(json["steps"]["names"]["nap"]["count"], 1);
+    }
+}
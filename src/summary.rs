@@ -0,0 +1,128 @@
+use std::fmt::{self, Display};
+
+use restate_sdk::discovery::{HandlerType, ServiceType};
+
+const HEADERS: [&str; 5] = ["SERVICE", "TYPE", "HANDLER", "HANDLER TYPE", "STEPS"];
+
+/// A single handler entry of the startup [`Summary`].
+pub struct SummaryRow {
+    pub service: String,
+    pub service_type: ServiceType,
+    pub handler: String,
+    pub handler_type: Option<HandlerType>,
+    /// The types of the steps of the handler, in execution order.
+    pub steps: Vec<String>,
+}
+
+impl SummaryRow {
+    fn columns(&self) -> [String; 5] {
+        [
+            self.service.clone(),
+            self.service_type.to_string(),
+            self.handler.clone(),
+            self.handler_type
+                .map(|ty| ty.to_string())
+                .unwrap_or_else(|| "-".to_owned()),
+            self.steps.join(" -> "),
+        ]
+    }
+}
+
+/// A table summarizing all the services and handlers bound to the endpoint,
+/// printed on startup.
+#[derive(Default)]
+pub struct Summary {
+    rows: Vec<SummaryRow>,
+}
+
+impl Summary {
+    /// Adds a handler with its chain of step types to the summary.
+    pub fn add(
+        &mut self,
+        service: impl Into<String>,
+        service_type: ServiceType,
+        handler: impl Into<String>,
+        handler_type: Option<HandlerType>,
+        steps: Vec<String>,
+    ) {
+        self.rows.push(SummaryRow {
+            service: service.into(),
+            service_type,
+            handler: handler.into(),
+            handler_type,
+            steps,
+        });
+    }
+
+    /// Returns the handlers added to the summary.
+    pub fn rows(&self) -> &[SummaryRow] {
+        &self.rows
+    }
+
+    /// Returns `true` if no handlers were added to the summary.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rows: Vec<[String; 5]> = self.rows.iter().map(SummaryRow::columns).collect();
+        rows.sort();
+
+        let mut widths = HEADERS.map(str::len);
+        for row in &rows {
+            for (width, column) in widths.iter_mut().zip(row) {
+                *width = (*width).max(column.len());
+            }
+        }
+
+        let headers = HEADERS.map(str::to_owned);
+        for row in std::iter::once(&headers).chain(rows.iter()) {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(column, width)| format!("{column:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_table() {
+        let mut summary = Summary::default();
+        assert!(summary.is_empty());
+
+        summary.add(
+            "random",
+            ServiceType::VirtualObject,
+            "get",
+            Some(HandlerType::Shared),
+            vec!["get".to_owned(), "return".to_owned()],
+        );
+        summary.add(
+            "echo",
+            ServiceType::Service,
+            "echo",
+            None,
+            vec!["echo".to_owned()],
+        );
+
+        assert!(!summary.is_empty());
+        assert_eq!(
+            summary.to_string(),
+            "SERVICE  TYPE            HANDLER  HANDLER TYPE  STEPS\n\
+             echo     SERVICE         echo     -             echo\n\
+             random   VIRTUAL_OBJECT  get      SHARED        get -> return\n"
+        );
+    }
+}
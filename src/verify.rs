@@ -0,0 +1,209 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use restate_sdk::{endpoint::Endpoint, http_server::HttpServer};
+use serde::Deserialize;
+
+/// Arguments of the `verify` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct VerifyArgs {
+    /// URL of the Restate ingress
+    #[clap(long, value_parser, default_value = "http://localhost:8080")]
+    ingress_url: String,
+    /// URL of the Restate admin API. If set, the mock service is registered before the run
+    #[clap(long, value_parser)]
+    admin_url: Option<String>,
+    /// URL of the mock service as seen by Restate, used for the registration
+    #[clap(long, value_parser, default_value = "http://localhost:9200")]
+    deployment_url: String,
+    /// Name of the producer service
+    #[clap(long, value_parser, default_value = "VerifyProducer")]
+    producer: String,
+    /// Name of the counter virtual object
+    #[clap(long, value_parser, default_value = "VerifyCounter")]
+    counter: String,
+    /// Number of counter objects receiving increments
+    #[clap(long, value_parser, default_value_t = 10)]
+    counters: usize,
+    /// Number of increments sent to every counter
+    #[clap(long, value_parser, default_value_t = 100)]
+    increments: u64,
+    /// Maximum time to wait for all the increments to be delivered
+    #[clap(long, value_parser, default_value = "60s")]
+    timeout: humantime::Duration,
+    /// Time to wait once all the counters received their increments before checking them
+    /// again, to catch the duplicates delivered late
+    #[clap(long, value_parser, default_value = "2s")]
+    settle: humantime::Duration,
+}
+
+/// The totals returned by the `totals` handler of a counter.
+#[derive(Debug, Deserialize)]
+struct Totals {
+    total: u64,
+    deliveries: u64,
+}
+
+/// Runs the exactly-once verification loop: the mock service is started with the
+/// verification topology, the producer sends numbered increments to every counter, then
+/// the counter totals are polled until they match the expected sums.
+///
+/// Counters receiving more than the expected increments are reported as duplicate
+/// deliveries, counters still missing increments on timeout as lost deliveries. Once all
+/// the counters are complete, they are checked once more after the `settle` time, so
+/// duplicates delivered after the last expected increment are reported too.
+pub async fn run(
+    args: &VerifyArgs,
+    listen_address: &str,
+    endpoint: Endpoint,
+) -> anyhow::Result<()> {
+    let address: std::net::SocketAddr = listen_address.parse()?;
+    // bound here rather than in the server task, so a busy address fails the run
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Failed to bind the mock service to {address}"))?;
+    let server = tokio::spawn(HttpServer::new(endpoint).serve(listener));
+
+    let client = reqwest::Client::new();
+    let ingress = args.ingress_url.trim_end_matches('/');
+
+    if let Some(admin) = &args.admin_url {
+        register(&client, admin.trim_end_matches('/'), &args.deployment_url).await?;
+    }
+
+    check_server(&server)?;
+
+    let run = format!("{:08x}", rand::random::<u32>());
+    let keys: Vec<String> = (0..args.counters)
+        .map(|idx| format!("verify-{run}-{idx}"))
+        .collect();
+
+    println!(
+        "Sending {} increments to {} counters (run {run})",
+        args.increments, args.counters
+    );
+
+    for key in &keys {
+        client
+            .post(format!("{ingress}/{}/produce/send", args.producer))
+            .json(&serde_json::json!({ "counter": key, "increments": args.increments }))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| format!("Failed to start the producer for counter {key}"))?;
+    }
+
+    let expected = args.increments * (args.increments + 1) / 2;
+    let deadline = Instant::now() + Duration::from(args.timeout);
+    let mut pending = keys.clone();
+    let mut violations = Vec::new();
+
+    while !pending.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        check_server(&server)?;
+
+        let mut still_pending = Vec::new();
+        for key in pending {
+            let totals = totals(&client, ingress, &args.counter, &key).await?;
+
+            if let Some(violation) = duplicates(&key, &totals, args.increments, expected) {
+                violations.push(violation);
+            } else if totals.deliveries < args.increments {
+                still_pending.push(key);
+            } else if totals.total != expected {
+                violations.push(format!(
+                    "{key}: total is {}, expected {expected}",
+                    totals.total
+                ));
+            }
+        }
+
+        pending = still_pending;
+    }
+
+    if pending.is_empty() && violations.is_empty() {
+        tokio::time::sleep(args.settle.into()).await;
+
+        for key in &keys {
+            let totals = totals(&client, ingress, &args.counter, key).await?;
+            violations.extend(duplicates(key, &totals, args.increments, expected));
+        }
+    }
+
+    for key in pending {
+        violations.push(format!(
+            "{key}: lost deliveries, not all increments received in time"
+        ));
+    }
+
+    if !violations.is_empty() {
+        for violation in &violations {
+            println!("[FAIL] {violation}");
+        }
+        anyhow::bail!("{} invariant violation(s) found", violations.len());
+    }
+
+    println!(
+        "[ OK ] all {} counters received exactly {} increments",
+        args.counters, args.increments
+    );
+
+    Ok(())
+}
+
+/// Fails if the mock service stopped serving, e.g. because its task panicked, since the
+/// counters could not receive the increments anymore.
+fn check_server(server: &tokio::task::JoinHandle<()>) -> anyhow::Result<()> {
+    if server.is_finished() {
+        anyhow::bail!("The mock service stopped serving");
+    }
+
+    Ok(())
+}
+
+/// Queries the totals of the counter object with the given key.
+async fn totals(
+    client: &reqwest::Client,
+    ingress: &str,
+    counter: &str,
+    key: &str,
+) -> anyhow::Result<Totals> {
+    let totals = client
+        .post(format!("{ingress}/{counter}/{key}/totals"))
+        .json(&serde_json::Value::Null)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("Failed to query counter {key}"))?
+        .json()
+        .await?;
+
+    Ok(totals)
+}
+
+/// Returns the violation if the counter received more than the expected increments.
+fn duplicates(key: &str, totals: &Totals, increments: u64, expected: u64) -> Option<String> {
+    (totals.deliveries > increments || totals.total > expected).then(|| {
+        format!(
+            "{key}: duplicate deliveries, {} increments (total {}) received, expected {increments} (total {expected})",
+            totals.deliveries, totals.total
+        )
+    })
+}
+
+/// Registers the mock service deployment with the Restate admin API.
+pub async fn register(
+    client: &reqwest::Client,
+    admin: &str,
+    deployment_url: &str,
+) -> anyhow::Result<()> {
+    client
+        .post(format!("{admin}/deployments"))
+        .json(&serde_json::json!({ "uri": deployment_url, "force": true }))
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("Failed to register {deployment_url} with {admin}"))?;
+
+    Ok(())
+}